@@ -0,0 +1,616 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use session_events::{Record, SessionEvent};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// 比较两次会话：命令序列、退出码和输出
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// 作为基准的会话，例如上一次成功的运行或标准记录 (事件 JSON lines、.cast 或文本日志)
+    old: PathBuf,
+
+    /// 要检查的会话
+    new: PathBuf,
+
+    /// 把日期和时间 (2025-01-31、12:34:56.789) 视为相同
+    #[arg(long)]
+    ignore_timestamps: bool,
+
+    /// 把绝对路径视为相同，也不比较命令的工作目录
+    #[arg(long)]
+    ignore_paths: bool,
+
+    /// 输出差异前后显示的相同行数
+    #[arg(long, default_value_t = 3)]
+    context: usize,
+}
+
+/// 一条命令及其结果，已按选项规范化
+struct Step {
+    command: String,
+    cwd: String,
+    exit_code: Option<i32>,
+    output: Vec<String>,
+}
+
+// 逐行对齐时 LCS 表最多的格数 (u32，64 MiB)，更大时只报告第一处不同
+const MAX_TABLE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// 打印两次会话的差异，相同时返回 true
+pub fn run(args: &DiffArgs) -> Result<bool> {
+    let old = steps(&args.old, args)?;
+    let new = steps(&args.new, args)?;
+    let (report, same) = report(
+        &old,
+        &new,
+        &args.old.display().to_string(),
+        &args.new.display().to_string(),
+        args.context,
+    );
+    print!("{}", report);
+    Ok(same)
+}
+
+/// 两次会话的差异报告，以及两者是否相同
+fn report(
+    old: &[Step],
+    new: &[Step],
+    old_name: &str,
+    new_name: &str,
+    context: usize,
+) -> (String, bool) {
+    let mut report = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let (mut same, mut changed, mut removed, mut added) = (0, 0, 0, 0);
+    // 第一处不同：新会话中的命令序号和命令
+    let mut diverged: Option<(usize, &str)> = None;
+
+    let commands = |steps: &[Step]| steps.iter().map(|s| s.command.clone()).collect::<Vec<_>>();
+    // 命令多到放不下 LCS 表时，中间部分按全部删除再全部新增处理
+    let (edits, _) = align(&commands(old), &commands(new));
+    for edit in edits {
+        match edit {
+            Edit::Same(i, j) => {
+                let (a, b) = (&old[i], &new[j]);
+                let details = compare(a, b, context);
+                if details.is_empty() {
+                    same += 1;
+                    let _ = writeln!(report, "  $ {}", a.command);
+                } else {
+                    changed += 1;
+                    diverged.get_or_insert((j + 1, b.command.as_str()));
+                    let _ = writeln!(report, "! $ {}", a.command);
+                    report.push_str(&details);
+                }
+            }
+            Edit::Removed(i) => {
+                removed += 1;
+                diverged.get_or_insert((added + same + changed + 1, old[i].command.as_str()));
+                let _ = writeln!(report, "- $ {}", old[i].command);
+            }
+            Edit::Added(j) => {
+                added += 1;
+                diverged.get_or_insert((j + 1, new[j].command.as_str()));
+                let _ = writeln!(report, "+ $ {}", new[j].command);
+            }
+        }
+    }
+
+    let _ = writeln!(
+        report,
+        "\n{} 条相同，{} 条结果不同，{} 条只在 {}，{} 条只在 {}",
+        same, changed, removed, old_name, added, new_name
+    );
+    if let Some((n, command)) = diverged {
+        let _ = writeln!(
+            report,
+            "从 {} 的第 {} 条命令开始不同: {}",
+            new_name, n, command
+        );
+    }
+
+    (report, diverged.is_none())
+}
+
+/// 读取会话中的命令，规范化后用于比较
+fn steps(path: &Path, args: &DiffArgs) -> Result<Vec<Step>> {
+    let records: Vec<Record> = std::fs::read_to_string(path)
+        .and_then(|text| session_events::parse(&text))
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(to_steps(records, args))
+}
+
+/// 按命令切分记录
+fn to_steps(records: Vec<Record>, args: &DiffArgs) -> Vec<Step> {
+    let normalize = |text: &str| {
+        let mut text = text.trim_end().to_string();
+        if args.ignore_timestamps {
+            text = mask_timestamps(&text);
+        }
+        if args.ignore_paths {
+            text = mask_paths(&text);
+        }
+        text
+    };
+
+    let mut steps = Vec::new();
+    // 正在执行的命令及其原始输出
+    let mut current: Option<(Step, String)> = None;
+    let mut finish = |current: Option<(Step, String)>| {
+        if let Some((mut step, output)) = current {
+            step.output = session_events::plain_text(&output)
+                .lines()
+                .map(normalize)
+                .collect();
+            while step.output.last().is_some_and(|line| line.is_empty()) {
+                step.output.pop();
+            }
+            steps.push(step);
+        }
+    };
+
+    for record in records {
+        match record.event {
+            SessionEvent::CommandStarted { command, cwd, .. } => {
+                // 上一条命令没有结束标记 (会话中断)，照样比较
+                finish(current.take());
+                let step = Step {
+                    command: normalize(command.as_deref().unwrap_or_default()),
+                    cwd: if args.ignore_paths {
+                        String::new()
+                    } else {
+                        cwd
+                    },
+                    exit_code: None,
+                    output: Vec::new(),
+                };
+                current = Some((step, String::new()));
+            }
+            SessionEvent::Output { data } => {
+                if let Some((_, output)) = &mut current {
+                    output.push_str(&data);
+                }
+            }
+            SessionEvent::CommandEnded { exit_code, .. } => {
+                if let Some((step, _)) = &mut current {
+                    step.exit_code = exit_code;
+                }
+                finish(current.take());
+            }
+            SessionEvent::SessionStarted { .. }
+            | SessionEvent::Resize { .. }
+            | SessionEvent::TitleChanged { .. } => {}
+        }
+    }
+    finish(current);
+
+    steps
+}
+
+/// 同一条命令在两次会话中的不同之处，没有不同时为空
+fn compare(old: &Step, new: &Step, context: usize) -> String {
+    let mut details = String::new();
+    if old.cwd != new.cwd {
+        let _ = writeln!(details, "    cwd: {} -> {}", old.cwd, new.cwd);
+    }
+    if old.exit_code != new.exit_code {
+        let code =
+            |code: Option<i32>| code.map_or_else(|| "unknown".to_string(), |c| c.to_string());
+        let _ = writeln!(
+            details,
+            "    exit code: {} -> {}",
+            code(old.exit_code),
+            code(new.exit_code)
+        );
+    }
+    if old.output != new.output {
+        hunks(&old.output, &new.output, context, &mut details);
+    }
+    details
+}
+
+/// 以 unified diff 的格式写出输出的差异，每行缩进在命令之下
+fn hunks(old: &[String], new: &[String], context: usize, out: &mut String) {
+    let (edits, exact) = align(old, new);
+    if !exact {
+        let first = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let _ = writeln!(
+            out,
+            "    输出不同 (旧 {} 行，新 {} 行，太长没有逐行比较)，从第 {} 行开始:",
+            old.len(),
+            new.len(),
+            first + 1
+        );
+        if let Some(line) = old.get(first) {
+            let _ = writeln!(out, "    -{}", line);
+        }
+        if let Some(line) = new.get(first) {
+            let _ = writeln!(out, "    +{}", line);
+        }
+        return;
+    }
+
+    // 每个编辑之前两边各有多少行
+    let mut positions = vec![(0, 0)];
+    for edit in &edits {
+        let (i, j) = *positions.last().unwrap();
+        positions.push(match edit {
+            Edit::Same(..) => (i + 1, j + 1),
+            Edit::Removed(_) => (i + 1, j),
+            Edit::Added(_) => (i, j + 1),
+        });
+    }
+
+    // 把相距不超过 2 * context 的改动合成一段
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for (k, _) in edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Same(..)))
+    {
+        let start = k.saturating_sub(context);
+        let end = (k + context + 1).min(edits.len());
+        match groups.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => groups.push((start, end)),
+        }
+    }
+
+    for (start, end) in groups {
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        let _ = writeln!(
+            out,
+            "    @@ -{},{} +{},{} @@",
+            old_start + 1,
+            old_end - old_start,
+            new_start + 1,
+            new_end - new_start
+        );
+        for edit in &edits[start..end] {
+            let _ = match *edit {
+                Edit::Same(i, _) => writeln!(out, "     {}", old[i]),
+                Edit::Removed(i) => writeln!(out, "    -{}", old[i]),
+                Edit::Added(j) => writeln!(out, "    +{}", new[j]),
+            };
+        }
+    }
+}
+
+/// 按最长公共子序列对齐两个序列。相同的开头和结尾先去掉，只对中间部分做 O(n*m) 的计算
+/// 中间部分超过 [`MAX_TABLE`] 时不做计算，当作全部删除再全部新增，并返回 false
+fn align<T: PartialEq>(old: &[T], new: &[T]) -> (Vec<Edit>, bool) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|i| Edit::Same(i, i)).collect();
+    let same_suffix =
+        (0..suffix).map(|k| Edit::Same(old.len() - suffix + k, new.len() - suffix + k));
+
+    let width = b.len() + 1;
+    let cells = (a.len() + 1)
+        .checked_mul(width)
+        .filter(|&cells| cells <= MAX_TABLE);
+    let Some(cells) = cells else {
+        edits.extend((0..a.len()).map(|i| Edit::Removed(prefix + i)));
+        edits.extend((0..b.len()).map(|j| Edit::Added(prefix + j)));
+        edits.extend(same_suffix);
+        return (edits, false);
+    };
+
+    // lengths[i * width + j]: a[i..] 与 b[j..] 的最长公共子序列长度
+    let mut lengths = vec![0u32; cells];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i * width + j] = if a[i] == b[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            edits.push(Edit::Same(prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if i < a.len()
+            && (j == b.len() || lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+        {
+            // 删除的行排在新增的行之前
+            edits.push(Edit::Removed(prefix + i));
+            i += 1;
+        } else {
+            edits.push(Edit::Added(prefix + j));
+            j += 1;
+        }
+    }
+    edits.extend(same_suffix);
+    (edits, true)
+}
+
+/// 把日期 (2025-01-31、2025/01/31) 换成 <DATE>，时间 (9:05、12:34:56.789) 换成 <TIME>
+fn mask_timestamps(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let digits = |from: usize| {
+        chars[from..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count()
+    };
+
+    let mut masked = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        // 只从数字的开头匹配，不截断更长的数字；ISO 8601 中日期后的 T 也算分隔
+        let at_start =
+            i == 0 || !chars[i - 1].is_ascii_alphanumeric() || masked.ends_with("<DATE>T");
+        if !at_start || !chars[i].is_ascii_digit() {
+            masked.push(chars[i]);
+            i += 1;
+            continue;
+        }
+
+        let run = digits(i);
+        let next = i + run;
+        let is_date = run == 4
+            && matches!(chars.get(next), Some('-' | '/'))
+            && digits(next + 1) == 2
+            && chars.get(next + 3) == chars.get(next)
+            && digits(next + 4) == 2;
+        if is_date {
+            masked.push_str("<DATE>");
+            i = next + 6;
+            continue;
+        }
+
+        if (1..=2).contains(&run) && chars.get(next) == Some(&':') && digits(next + 1) == 2 {
+            let mut end = next + 3;
+            if chars.get(end) == Some(&':') && digits(end + 1) == 2 {
+                end += 3;
+            }
+            if matches!(chars.get(end), Some('.' | ',')) && digits(end + 1) > 0 {
+                end += 1 + digits(end + 1);
+            }
+            masked.push_str("<TIME>");
+            i = end;
+            continue;
+        }
+
+        masked.extend(&chars[i..next]);
+        i = next;
+    }
+    masked
+}
+
+/// 把绝对路径 (/srv/app、~/build、C:\work) 换成 <PATH>
+fn mask_paths(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    // 路径在空白、引号或括号处结束，也不跨过 file:line 中的冒号
+    let ends =
+        |c: &char| c.is_whitespace() || matches!(c, '"' | '\'' | '`' | '(' | ')' | ',' | ';' | ':');
+
+    let mut masked = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        // 只在词首匹配，URL 中的 // 不算
+        let at_start = i == 0
+            || chars[i - 1].is_whitespace()
+            || matches!(chars[i - 1], '"' | '\'' | '`' | '=' | '(');
+        let rest = &chars[i..];
+        let prefix = match rest {
+            ['/', c, ..] if !ends(c) => 1,
+            ['~', '/', ..] => 2,
+            [drive, ':', '\\', ..] if drive.is_ascii_alphabetic() => 3,
+            _ => 0,
+        };
+        if !at_start || prefix == 0 {
+            masked.push(chars[i]);
+            i += 1;
+            continue;
+        }
+
+        masked.push_str("<PATH>");
+        i += prefix;
+        while i < chars.len() && !ends(&chars[i]) {
+            i += 1;
+        }
+    }
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(ignore_timestamps: bool, ignore_paths: bool) -> DiffArgs {
+        DiffArgs {
+            old: PathBuf::new(),
+            new: PathBuf::new(),
+            ignore_timestamps,
+            ignore_paths,
+            context: 3,
+        }
+    }
+
+    // (命令, 输出, 退出码)，都在 cwd 中执行
+    fn session(cwd: &str, commands: &[(&str, &str, i32)]) -> Vec<Record> {
+        let record = |event| Record { time: 0.0, event };
+        commands
+            .iter()
+            .flat_map(|&(command, output, exit_code)| {
+                [
+                    record(SessionEvent::CommandStarted {
+                        command: Some(command.to_string()),
+                        user: String::new(),
+                        host: String::new(),
+                        cwd: cwd.to_string(),
+                    }),
+                    record(SessionEvent::Output {
+                        data: output.to_string(),
+                    }),
+                    record(SessionEvent::CommandEnded {
+                        exit_code: Some(exit_code),
+                        wall_ms: None,
+                        cpu_ms: None,
+                    }),
+                ]
+            })
+            .collect()
+    }
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn align_follows_the_longest_common_subsequence() {
+        let (edits, exact) = align(&["a", "b", "c", "d"], &["a", "x", "c", "d", "e"]);
+        assert!(exact);
+        assert_eq!(
+            edits,
+            [
+                Edit::Same(0, 0),
+                Edit::Removed(1),
+                Edit::Added(1),
+                Edit::Same(2, 2),
+                Edit::Same(3, 3),
+                Edit::Added(4),
+            ]
+        );
+
+        let (edits, exact) = align(&[1, 2], &[1, 2]);
+        assert!(exact);
+        assert_eq!(edits, [Edit::Same(0, 0), Edit::Same(1, 1)]);
+    }
+
+    #[test]
+    fn align_gives_up_past_the_table_limit() {
+        // 中间部分 4100 x 4100，超过 MAX_TABLE
+        let old: Vec<i32> = [-1].into_iter().chain(0..4100).chain([-2]).collect();
+        let new: Vec<i32> = [-1].into_iter().chain(10_000..14_100).chain([-2]).collect();
+        let (edits, exact) = align(&old, &new);
+        assert!(!exact);
+        assert_eq!(edits.len(), 2 + 2 * 4100);
+        assert_eq!(edits[0], Edit::Same(0, 0));
+        assert_eq!(edits[1], Edit::Removed(1));
+        assert_eq!(edits[4101], Edit::Added(1));
+        assert_eq!(edits.last(), Some(&Edit::Same(4101, 4101)));
+    }
+
+    #[test]
+    fn hunks_are_unified_diffs() {
+        let mut out = String::new();
+        hunks(&lines(&["a", "b", "c"]), &lines(&["a", "B", "c"]), 1, &mut out);
+        assert_eq!(out, "    @@ -1,3 +1,3 @@\n     a\n    -b\n    +B\n     c\n");
+    }
+
+    #[test]
+    fn long_outputs_report_the_first_difference() {
+        let old: Vec<String> = (0..5000).map(|i| format!("a{}", i)).collect();
+        let new: Vec<String> = (0..5000)
+            .map(|i| format!("{}{}", if i < 2 { "a" } else { "b" }, i))
+            .collect();
+        let mut out = String::new();
+        hunks(&old, &new, 3, &mut out);
+        assert_eq!(
+            out,
+            "    输出不同 (旧 5000 行，新 5000 行，太长没有逐行比较)，从第 3 行开始:\n    -a2\n    +b2\n"
+        );
+    }
+
+    #[test]
+    fn timestamps_are_masked() {
+        assert_eq!(
+            mask_timestamps("2025-01-31T12:34:56.789Z build 12345 took 9:05"),
+            "<DATE>T<TIME>Z build 12345 took <TIME>"
+        );
+        assert_eq!(mask_timestamps("on 2025/01/31"), "on <DATE>");
+        // 分隔符不一致的不是日期，数字中间的也不是时间
+        assert_eq!(mask_timestamps("2025-01/31 v12:30"), "2025-01/31 v12:30");
+    }
+
+    #[test]
+    fn paths_are_masked() {
+        let line = "error at /srv/app/main.rs:12: see ~/build and C:\\work\\x, not https://x.org/a";
+        assert_eq!(
+            mask_paths(line),
+            "error at <PATH>:12: see <PATH> and <PATH>, not https://x.org/a"
+        );
+        assert_eq!(mask_paths("open('/tmp/x')"), "open('<PATH>')");
+        assert_eq!(mask_paths("a / b"), "a / b");
+    }
+
+    #[test]
+    fn report_lists_every_command() {
+        let old = to_steps(
+            session(
+                "/tmp",
+                &[("ls", "a\r\nb\r\n", 0), ("make", "", 0), ("rm -rf out", "", 0)],
+            ),
+            &args(false, false),
+        );
+        let new = to_steps(
+            session(
+                "/tmp",
+                &[("ls", "a\r\nc\r\n", 0), ("make", "", 2), ("echo hi", "", 0)],
+            ),
+            &args(false, false),
+        );
+
+        let (report, same) = report(&old, &new, "old", "new", 3);
+        assert!(!same);
+        assert_eq!(
+            report,
+            "--- old\n+++ new\n\
+             ! $ ls\n    @@ -1,2 +1,2 @@\n     a\n    -b\n    +c\n\
+             ! $ make\n    exit code: 0 -> 2\n\
+             - $ rm -rf out\n\
+             + $ echo hi\n\
+             \n0 条相同，2 条结果不同，1 条只在 old，1 条只在 new\n\
+             从 new 的第 1 条命令开始不同: ls\n"
+        );
+    }
+
+    #[test]
+    fn ignored_differences_compare_equal() {
+        let old = session("/tmp/a", &[("make", "built /tmp/a/out at 12:00:01\r\n", 0)]);
+        let new = session("/tmp/b", &[("make", "built /tmp/b/out at 12:00:07\r\n", 0)]);
+
+        let strict = args(false, false);
+        let (_, same) = report(
+            &to_steps(old.clone(), &strict),
+            &to_steps(new.clone(), &strict),
+            "old",
+            "new",
+            3,
+        );
+        assert!(!same);
+
+        let lenient = args(true, true);
+        let (report, same) = report(
+            &to_steps(old, &lenient),
+            &to_steps(new, &lenient),
+            "old",
+            "new",
+            3,
+        );
+        assert!(same, "{}", report);
+    }
+}
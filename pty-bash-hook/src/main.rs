@@ -1,10 +1,29 @@
 use anyhow::Result;
+use clap::{Parser, Subcommand};
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use session_events::{Recorder, SessionEvent};
+use shell_markers::{Marker, MarkerParser, Shell};
 use std::fs::OpenOptions;
 use std::io::{self, BufWriter, Read, Write};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
+use workspace_config::{Overrides, RecorderConfig};
+
+mod diff;
+mod ring;
+mod script;
+
+use ring::RingBuffer;
+use script::{Script, Watch};
+
+// 检查终端大小的间隔 (Windows 没有 SIGWINCH，统一轮询)
+const RESIZE_POLL: Duration = Duration::from_millis(250);
+
+// 把 PTY 调整为 (列, 行)
+type Resize = Box<dyn Fn(u16, u16) -> Result<()> + Send>;
 
 #[cfg(windows)]
 use winptyrs::PTY;
@@ -78,134 +97,269 @@ enum PtyBackend {
 
 struct CommandSession {
     command: String,
+    user: String,
+    host: String,
+    cwd: String,
     start_time: std::time::SystemTime,
-    output: Vec<u8>,
+    // 输出在命令文本之后才写入日志
+    logging: bool,
+    // 已直接写入日志的输出字节数
+    head: usize,
+    // 头尾之间没有记录的字节数
+    omitted: u64,
 }
 
 struct LogInterpreter {
     log_file: Arc<Mutex<BufWriter<std::fs::File>>>,
+    // 同一会话的结构化事件记录 (session-events 格式)，与调整大小的线程共用
+    events: Arc<Mutex<Recorder<BufWriter<std::fs::File>>>>,
     current_session: Option<CommandSession>,
+    // 每条命令的输出：前 head_limit 字节边收边写入日志，
+    // 最后的部分留在 tail 里，命令结束时写入
+    head_limit: usize,
+    tail: RingBuffer,
 }
 
 impl LogInterpreter {
-    fn new(log_file: Arc<Mutex<BufWriter<std::fs::File>>>) -> Self {
+    fn new(
+        log_file: Arc<Mutex<BufWriter<std::fs::File>>>,
+        events: Arc<Mutex<Recorder<BufWriter<std::fs::File>>>>,
+        output_limit: usize,
+    ) -> Self {
+        let head_limit = output_limit / 2;
         Self {
             log_file,
+            events,
             current_session: None,
+            head_limit,
+            tail: RingBuffer::with_capacity(output_limit - head_limit),
         }
     }
 
-    fn capture_output(&mut self, data: &[u8]) {
-        if let Some(session) = &mut self.current_session {
-            session.output.extend_from_slice(data);
+    fn record(&self, event: SessionEvent) {
+        if let Ok(mut events) = self.events.lock() {
+            let _ = events.record(event);
         }
     }
-}
 
-impl vte::Perform for LogInterpreter {
-    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
-        if params.is_empty() {
+    fn capture_output(&mut self, data: &[u8]) {
+        if let Ok(mut events) = self.events.lock() {
+            let _ = events.output(data);
+        }
+        let Some(session) = &mut self.current_session else {
+            return;
+        };
+        if !session.logging {
             return;
         }
 
-        if params[0] == b"666" {
-            if params.len() < 2 {
-                return;
+        let head = self.head_limit.saturating_sub(session.head).min(data.len());
+        if head > 0 {
+            if let Ok(mut log) = self.log_file.lock() {
+                let _ = log.write_all(&data[..head]);
             }
+            session.head += head;
+        }
+        session.omitted += self.tail.push(&data[head..]);
+    }
+}
 
-            let type_str = String::from_utf8_lossy(params[1]);
-
-            match type_str.as_ref() {
-                "CMD_START" => {
-                    // 命令开始执行
-                    if params.len() >= 3 {
-                        let command = String::from_utf8_lossy(params[2]).to_string();
-
-                        if let Ok(mut log) = self.log_file.lock() {
-                            let _ = writeln!(log, "\n=== Command Started ===");
-                            let _ = writeln!(log, "Command: {}", command);
-                            let _ = writeln!(log, "Time: {:?}", std::time::SystemTime::now());
-                            let _ = log.flush();
-                        }
-
-                        self.current_session = Some(CommandSession {
-                            command,
-                            start_time: std::time::SystemTime::now(),
-                            output: Vec::new(),
-                        });
+impl shell_markers::Perform for LogInterpreter {
+    fn marker(&mut self, marker: Marker) {
+        match marker {
+            Marker::Start { user, host, cwd } => {
+                // 命令开始执行，命令文本随后由 CMD 标记给出
+                self.current_session = Some(CommandSession {
+                    command: String::new(),
+                    user,
+                    host,
+                    cwd,
+                    start_time: std::time::SystemTime::now(),
+                    logging: false,
+                    head: 0,
+                    omitted: 0,
+                });
+                self.tail.clear();
+            }
+            Marker::Command(command) => {
+                if let Some(session) = &mut self.current_session {
+                    if let Ok(mut log) = self.log_file.lock() {
+                        let _ = writeln!(log, "\n=== Command Started ===");
+                        let _ = writeln!(log, "Command: {}", command);
+                        let _ = writeln!(log, "Time: {:?}", session.start_time);
+                        let _ = writeln!(log, "--- Output ---");
+                        let _ = log.flush();
                     }
+                    session.logging = true;
+                    let event = SessionEvent::CommandStarted {
+                        command: Some(command.clone()),
+                        user: session.user.clone(),
+                        host: session.host.clone(),
+                        cwd: session.cwd.clone(),
+                    };
+                    session.command = command;
+                    self.record(event);
                 }
-                "CMD_END" => {
-                    // 命令执行完成
-                    if let Some(session) = self.current_session.take() {
-                        let exit_code = if params.len() >= 3 {
-                            String::from_utf8_lossy(params[2]).to_string()
-                        } else {
-                            "unknown".to_string()
-                        };
-
-                        if let Ok(mut log) = self.log_file.lock() {
-                            let duration = std::time::SystemTime::now()
-                                .duration_since(session.start_time)
-                                .unwrap_or_default();
-
-                            let _ = writeln!(log, "--- Output ---");
-                            let output_str = String::from_utf8_lossy(&session.output);
-                            let _ = write!(log, "{}", output_str);
-                            let _ = writeln!(log, "\n--- End Output ---");
-                            let _ = writeln!(log, "Exit Code: {}", exit_code);
-                            let _ = writeln!(log, "Duration: {:?}", duration);
-                            let _ = writeln!(log, "=== Command Ended ===\n");
-                            let _ = log.flush();
+            }
+            Marker::End {
+                exit_code,
+                wall_ms,
+                cpu_ms,
+            } => {
+                // 命令执行完成
+                if let Some(session) = self.current_session.take() {
+                    self.record(SessionEvent::CommandEnded {
+                        exit_code,
+                        wall_ms,
+                        cpu_ms,
+                    });
+
+                    let exit_code = exit_code
+                        .map(|code| code.to_string())
+                        .unwrap_or_else(|| "unknown".to_string());
+
+                    if let Ok(mut log) = self.log_file.lock() {
+                        let duration = std::time::SystemTime::now()
+                            .duration_since(session.start_time)
+                            .unwrap_or_default();
+
+                        if session.omitted > 0 {
+                            let _ = write!(log, "\n[... {} bytes omitted ...]\n", session.omitted);
                         }
+                        let (first, second) = self.tail.as_slices();
+                        let _ = log.write_all(first);
+                        let _ = log.write_all(second);
+                        let _ = writeln!(log, "\n--- End Output ---");
+                        let _ = writeln!(log, "Exit Code: {}", exit_code);
+                        let _ = writeln!(log, "Duration: {:?}", duration);
+                        let _ = writeln!(log, "=== Command Ended ===\n");
+                        let _ = log.flush();
                     }
                 }
-                "PWD" => {
-                    // 可选：记录工作目录变化
-                    if params.len() >= 3 {
-                        let pwd = String::from_utf8_lossy(params[2]);
-                        if let Ok(mut log) = self.log_file.lock() {
-                            let _ = writeln!(log, "[PWD] {}", pwd);
-                            let _ = log.flush();
-                        }
-                    }
+            }
+            Marker::Cwd(pwd) => {
+                // 可选：记录工作目录变化
+                if let Ok(mut log) = self.log_file.lock() {
+                    let _ = writeln!(log, "[PWD] {}", pwd);
+                    let _ = log.flush();
                 }
-                _ => {}
             }
         }
     }
+
+    fn osc(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        // OSC 0/2: 窗口标题
+        if let [b"0" | b"2", title @ ..] = params {
+            let title = title
+                .iter()
+                .map(|p| String::from_utf8_lossy(p))
+                .collect::<Vec<_>>()
+                .join(";");
+            self.record(SessionEvent::TitleChanged { title });
+        }
+    }
+}
+
+fn resize_master(master: Box<dyn portable_pty::MasterPty + Send>) -> Resize {
+    Box::new(move |cols, rows| {
+        master.resize(PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+    })
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about = "在 PTY 中运行 shell，记录每条命令及其输出")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// 配置文件，读取其中的 [recorder] 部分 (默认 $WORKSPACE_CONFIG 或 ./workspace.toml)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// 命令日志文件 (默认 shell_commands.log)
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// 会话记录目录 (默认 recordings)
+    #[arg(long)]
+    recordings_dir: Option<PathBuf>,
+
+    /// 每条命令最多记录的输出字节数，超出时保留开头和结尾各一半 (默认 1048576)
+    #[arg(long)]
+    output_limit: Option<usize>,
+
+    /// 用脚本代替标准输入驱动 shell (每行一步: send、expect、exit、timeout)，
+    /// 照常记录，有一步没有满足时退出码为 1
+    #[arg(long)]
+    script: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// 比较两次记录的会话，找出新的一次从哪里开始不同。有差异时退出码为 1
+    Diff(diff::DiffArgs),
 }
 
 fn main() -> Result<()> {
+    // 配置: 默认值 -> 配置文件 -> BASH_PTY_RECORDER_* 环境变量 -> 命令行参数
+    let args = Args::parse();
+    if let Some(Command::Diff(diff)) = &args.command {
+        let same = diff::run(diff)?;
+        std::process::exit(if same { 0 } else { 1 });
+    }
+    let config: RecorderConfig = workspace_config::load(
+        args.config.as_deref(),
+        Overrides::new()
+            .set("log_file", args.log_file)
+            .set("recordings_dir", args.recordings_dir)
+            .set("output_limit", args.output_limit),
+    )?;
+    // 运行 shell 之前先检查脚本
+    let script = args.script.as_deref().map(Script::load).transpose()?;
+
     // 创建命令日志文件
     let log_file = OpenOptions::new()
         .create(true)
         .append(true)
-        .open("shell_commands.log")?;
+        .open(&config.log_file)?;
     let log_file = Arc::new(Mutex::new(BufWriter::new(log_file)));
 
-    let cwd = std::env::current_dir()?;
+    // 结构化事件记录，每次运行一个文件 (<recordings_dir>/<启动时间>-<pid>.jsonl)
+    std::fs::create_dir_all(&config.recordings_dir)?;
+    let events_file = std::fs::File::create(config.recordings_dir.join(format!(
+        "{}-{}.jsonl",
+        session_events::unix_now(),
+        std::process::id()
+    )))?;
+
+    // 把集成脚本写到新建的私有临时目录，供 shell 启动时加载
+    let script_dir = shell_markers::extract("bash-pty-recorder")?;
 
     #[cfg(windows)]
     let use_winpty = !is_windows_10_or_higher();
 
     #[cfg(windows)]
-    let script_path = cwd.join("powershell_recorder.ps1");
+    let script_path = script_dir.join(Shell::PowerShell.file_name());
 
     #[cfg(not(windows))]
-    let script_path = cwd.join("bash_recorder.sh");
+    let script_path = script_dir.join(Shell::Bash.file_name());
 
     // 根据平台和版本选择不同的 PTY 实现
     #[cfg(windows)]
-    let (mut reader, mut writer, _child) = if use_winpty {
+    let (mut reader, mut writer, _child, resize) = if use_winpty {
         // Windows 7/8: 使用 WinPTY
         eprintln!("Using WinPTY backend (Windows 7/8 detected)");
 
         use winptyrs::*;
 
         let mut pty = PTY::new(&PTYArgs {
-            cols: 80,
-            rows: 24,
+            cols: config.cols as i32,
+            rows: config.rows as i32,
             agent_config: AgentConfig::WINPTY_FLAG_COLOR_ESCAPES,
             ..Default::default()
         })
@@ -227,11 +381,20 @@ fn main() -> Result<()> {
         let writer = WinPtyWriter {
             pty: Arc::clone(&pty),
         };
+        let resize: Resize = Box::new(move |cols, rows| {
+            let pty = pty
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Failed to lock PTY"))?;
+            pty.set_size(cols as i32, rows as i32)
+                .map_err(|e| anyhow::anyhow!("Failed to resize PTY: {:?}", e))?;
+            Ok(())
+        });
 
         (
             Box::new(reader) as Box<dyn Read + Send>,
             Box::new(writer) as Box<dyn Write + Send>,
             None,
+            resize,
         )
     } else {
         // Windows 10+: 使用 ConPTY
@@ -239,8 +402,8 @@ fn main() -> Result<()> {
 
         let pty_system = native_pty_system();
         let pair = pty_system.openpty(PtySize {
-            rows: 24,
-            cols: 80,
+            rows: config.rows,
+            cols: config.cols,
             pixel_width: 0,
             pixel_height: 0,
         })?;
@@ -258,20 +421,22 @@ fn main() -> Result<()> {
 
         let reader = pair.master.try_clone_reader()?;
         let writer = pair.master.take_writer()?;
+        let resize = resize_master(pair.master);
 
         (
             Box::new(reader) as Box<dyn Read + Send>,
             Box::new(writer) as Box<dyn Write + Send>,
             Some(child),
+            resize,
         )
     };
 
     #[cfg(not(windows))]
-    let (mut reader, mut writer, _child) = {
+    let (mut reader, mut writer, _child, resize) = {
         let pty_system = native_pty_system();
         let pair = pty_system.openpty(PtySize {
-            rows: 24,
-            cols: 80,
+            rows: config.rows,
+            cols: config.cols,
             pixel_width: 0,
             pixel_height: 0,
         })?;
@@ -279,29 +444,89 @@ fn main() -> Result<()> {
         let mut cmd = CommandBuilder::new("bash");
         cmd.arg("--rcfile");
         cmd.arg(script_path);
+        // --rcfile 会跳过 ~/.bashrc，让脚本自己加载
+        cmd.env(shell_markers::LOAD_RC_ENV, "1");
 
         let child = pair.slave.spawn_command(cmd)?;
         drop(pair.slave);
 
         let reader = pair.master.try_clone_reader()?;
         let writer = pair.master.take_writer()?;
+        let resize = resize_master(pair.master);
 
         (
             Box::new(reader) as Box<dyn Read + Send>,
             Box::new(writer) as Box<dyn Write + Send>,
             child,
+            resize,
         )
     };
 
-    enable_raw_mode()?;
+    // 有脚本时由脚本输入，脚本执行完 (或失败) 后让 shell 退出
+    let script = match script {
+        Some(script) => {
+            let watch = Arc::new(Watch::default());
+            let runner = {
+                let watch = Arc::clone(&watch);
+                thread::spawn(move || {
+                    let result = script.run(&watch, &mut writer);
+                    if result.is_err() {
+                        // 先中断还在运行的命令
+                        let _ = writer.write_all(b"\x03");
+                    }
+                    let _ = writer.write_all(b"exit\r");
+                    let _ = writer.flush();
+                    result
+                })
+            };
+            Some((watch, runner))
+        }
+        None => {
+            enable_raw_mode()?;
+            thread::spawn(move || {
+                let mut stdin = io::stdin();
+                let _ = io::copy(&mut stdin, &mut writer);
+            });
+            None
+        }
+    };
 
-    thread::spawn(move || {
-        let mut stdin = io::stdin();
-        let _ = io::copy(&mut stdin, &mut writer);
-    });
+    let mut parser = MarkerParser::new();
+    #[cfg(windows)]
+    let shell = "powershell.exe";
+    #[cfg(not(windows))]
+    let shell = "bash";
+    let cwd = std::env::current_dir()?;
+    let events = Arc::new(Mutex::new(Recorder::new(
+        BufWriter::new(events_file),
+        config.cols,
+        config.rows,
+        Some(shell.to_string()),
+        Some(cwd.display().to_string()),
+    )?));
+
+    // 终端大小变化时同步给 PTY (SIGWINCH)，并记录 Resize 事件，回放时按当时的大小显示。
+    // 启动时终端与配置的大小不同，也在第一次检查时调整
+    {
+        let events = Arc::clone(&events);
+        let mut size = (config.cols, config.rows);
+        thread::spawn(move || loop {
+            if let Ok(current) = crossterm::terminal::size() {
+                if current != size {
+                    size = current;
+                    let (cols, rows) = current;
+                    if resize(cols, rows).is_ok() {
+                        if let Ok(mut events) = events.lock() {
+                            let _ = events.record(SessionEvent::Resize { cols, rows });
+                        }
+                    }
+                }
+            }
+            thread::sleep(RESIZE_POLL);
+        });
+    }
 
-    let mut parser = vte::Parser::new();
-    let mut interpreter = LogInterpreter::new(log_file);
+    let mut interpreter = LogInterpreter::new(log_file, events, config.output_limit);
     let mut stdout = io::stdout();
     let mut buf = [0u8; 4096];
 
@@ -318,17 +543,28 @@ fn main() -> Result<()> {
                 // 捕获命令输出（去除 ANSI 控制序列的原始数据）
                 interpreter.capture_output(data);
 
-                // 解析 OSC 序列
-                for byte in data {
-                    parser.advance(&mut interpreter, *byte);
+                // 解析 OSC 标记
+                parser.advance(&mut interpreter, data);
+
+                if let Some((watch, _)) = &script {
+                    watch.output(data);
                 }
             }
             Err(_) => break,
         }
     }
 
-    disable_raw_mode()?;
+    let Some((watch, runner)) = script else {
+        disable_raw_mode()?;
+        println!("Session ended.");
+        return Ok(());
+    };
+    watch.close();
     println!("Session ended.");
+    if let Err(e) = runner.join().expect("script thread panicked") {
+        eprintln!("Script failed: {:#}", e);
+        std::process::exit(1);
+    }
 
     Ok(())
 }
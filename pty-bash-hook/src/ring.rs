@@ -0,0 +1,52 @@
+/// 定长环形缓冲区：只保留最后写入的 `capacity` 字节，内存在创建时一次分配
+pub struct RingBuffer {
+    buf: Box<[u8]>,
+    // 最旧字节的位置
+    start: usize,
+    len: usize,
+}
+
+impl RingBuffer {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buf: vec![0; capacity].into_boxed_slice(),
+            start: 0,
+            len: 0,
+        }
+    }
+
+    /// 追加 `data`，返回因此被挤出 (或放不下) 的字节数
+    pub fn push(&mut self, data: &[u8]) -> u64 {
+        let capacity = self.buf.len();
+        // 比整个缓冲区还长时，只有最后 capacity 字节留得下
+        let skipped = data.len().saturating_sub(capacity);
+        let data = &data[skipped..];
+
+        let evicted = (self.len + data.len()).saturating_sub(capacity);
+        self.start = (self.start + evicted) % capacity.max(1);
+        self.len -= evicted;
+
+        // 写入位置之后到缓冲区末尾的部分放不下时，剩下的绕回开头
+        let end = (self.start + self.len) % capacity.max(1);
+        let first = data.len().min(capacity - end);
+        self.buf[end..end + first].copy_from_slice(&data[..first]);
+        self.buf[..data.len() - first].copy_from_slice(&data[first..]);
+        self.len += data.len();
+
+        (skipped + evicted) as u64
+    }
+
+    /// 按写入顺序排列的内容，绕回时分成两段
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        let first = self.len.min(self.buf.len() - self.start);
+        (
+            &self.buf[self.start..self.start + first],
+            &self.buf[..self.len - first],
+        )
+    }
+
+    pub fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+    }
+}
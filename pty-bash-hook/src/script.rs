@@ -0,0 +1,315 @@
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use shell_markers::{Marker, MarkerParser, Perform};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+// expect 和 exit 默认最多等待的时间
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// 没有被 expect 匹配的输出至少保留的字节数，超过两倍时丢掉较旧的部分
+const MAX_TEXT: usize = 1024 * 1024;
+
+/// 脚本中的一步。脚本每行一步，空行和 # 开头的行忽略：
+///
+/// ```text
+/// timeout 30
+/// send make deploy
+/// expect Deployed version \d+
+/// exit 0
+/// ```
+#[derive(Debug)]
+enum Step {
+    /// 发送一行输入
+    Send(String),
+    /// 等待输出 (去掉控制序列后) 匹配正则表达式
+    Expect(Regex),
+    /// 等待最近发送的命令结束，检查退出码
+    Exit(i32),
+    /// 之后的 expect 和 exit 最多等待的时间 (秒)
+    Timeout(Duration),
+}
+
+/// 代替标准输入驱动 shell 的脚本
+pub struct Script {
+    // (行号, 步骤)
+    steps: Vec<(usize, Step)>,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text, path)
+    }
+
+    /// 解析脚本内容，`path` 只用于错误信息
+    fn parse(text: &str, path: &Path) -> Result<Self> {
+        let mut steps = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keyword, arg) = line.split_once(' ').unwrap_or((line, ""));
+            let step = match keyword {
+                "send" => Ok(Step::Send(arg.to_string())),
+                "expect" => Regex::new(arg).map(Step::Expect).map_err(Into::into),
+                "exit" => arg.trim().parse().map(Step::Exit).map_err(Into::into),
+                "timeout" => arg
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|secs| Ok(Step::Timeout(Duration::try_from_secs_f64(secs)?))),
+                _ => Err(anyhow::anyhow!("unknown step `{}`", keyword)),
+            }
+            .with_context(|| format!("{}:{}", path.display(), i + 1))?;
+            steps.push((i + 1, step));
+        }
+
+        Ok(Self { steps })
+    }
+
+    /// 逐步执行，在第一个没有满足的 expect 或 exit 处失败
+    pub fn run(&self, watch: &Watch, writer: &mut dyn Write) -> Result<()> {
+        let mut timeout = DEFAULT_TIMEOUT;
+        for (line, step) in &self.steps {
+            match step {
+                Step::Send(input) => {
+                    // 之前的命令的退出码不再需要
+                    watch.state.lock().unwrap().seen.exits.clear();
+                    writer.write_all(input.as_bytes())?;
+                    writer.write_all(b"\r")?;
+                    writer.flush()?;
+                }
+                Step::Expect(pattern) => {
+                    let found = watch.wait(timeout, |seen| {
+                        let end = pattern.find(&seen.text)?.end();
+                        // 匹配到的输出不会再被之后的 expect 匹配
+                        seen.text.drain(..end);
+                        Some(())
+                    });
+                    if found.is_none() {
+                        bail!("line {}: expected /{}/ within {:?}", line, pattern, timeout);
+                    }
+                }
+                Step::Exit(expected) => {
+                    let Some(code) = watch.wait(timeout, |seen| seen.exits.pop_front()) else {
+                        bail!("line {}: no command finished within {:?}", line, timeout);
+                    };
+                    if code != Some(*expected) {
+                        let code = code.map_or_else(|| "unknown".to_string(), |c| c.to_string());
+                        bail!("line {}: exit code {}, expected {}", line, code, expected);
+                    }
+                }
+                Step::Timeout(duration) => timeout = *duration,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 脚本看到的 shell 输出，由读取 PTY 的线程写入
+#[derive(Default)]
+pub struct Watch {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    parser: MarkerParser,
+    seen: Seen,
+}
+
+#[derive(Default)]
+struct Seen {
+    // 还没有被 expect 匹配过的纯文本输出
+    text: String,
+    // 还没有被 exit 检查过的退出码
+    exits: VecDeque<Option<i32>>,
+    // shell 已经退出
+    closed: bool,
+}
+
+impl Perform for Seen {
+    fn marker(&mut self, marker: Marker) {
+        if let Marker::End { exit_code, .. } = marker {
+            self.exits.push_back(exit_code);
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        self.text.push(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        if byte == b'\n' || byte == b'\t' {
+            self.text.push(byte as char);
+        }
+    }
+}
+
+impl Seen {
+    /// 一直没有 expect 匹配时，只保留最近的输出
+    fn trim(&mut self) {
+        if self.text.len() <= 2 * MAX_TEXT {
+            return;
+        }
+        let mut cut = self.text.len() - MAX_TEXT;
+        while !self.text.is_char_boundary(cut) {
+            cut += 1;
+        }
+        self.text.drain(..cut);
+    }
+}
+
+impl Watch {
+    pub fn output(&self, data: &[u8]) {
+        let mut state = self.state.lock().unwrap();
+        let State { parser, seen } = &mut *state;
+        parser.advance(seen, data);
+        seen.trim();
+        self.changed.notify_all();
+    }
+
+    pub fn close(&self) {
+        self.state.lock().unwrap().seen.closed = true;
+        self.changed.notify_all();
+    }
+
+    /// 等到 `check` 在新的输出上返回结果，超时或 shell 退出时返回 None
+    fn wait<T>(
+        &self,
+        timeout: Duration,
+        mut check: impl FnMut(&mut Seen) -> Option<T>,
+    ) -> Option<T> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(found) = check(&mut state.seen) {
+                return Some(found);
+            }
+            let left = deadline.checked_duration_since(Instant::now())?;
+            if state.seen.closed {
+                return None;
+            }
+            state = self.changed.wait_timeout(state, left).unwrap().0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Script> {
+        Script::parse(text, Path::new("test.script"))
+    }
+
+    fn end(exit_code: i32) -> String {
+        Marker::End {
+            exit_code: Some(exit_code),
+            wall_ms: None,
+            cpu_ms: None,
+        }
+        .to_string()
+    }
+
+    /// 执行脚本，`output` 在脚本开始后由另一个线程写入
+    fn run(script: &str, output: &[&str]) -> Result<Vec<u8>> {
+        let script = parse(script)?;
+        let watch = Watch::default();
+        let mut input = Vec::new();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for data in output {
+                    std::thread::sleep(Duration::from_millis(20));
+                    watch.output(data.as_bytes());
+                }
+            });
+            script.run(&watch, &mut input)
+        })?;
+        Ok(input)
+    }
+
+    #[test]
+    fn steps_are_parsed_with_their_line_numbers() {
+        let script = parse(
+            "# deploy\n\ntimeout 2.5\nsend make deploy\n  expect Deployed version \\d+\nexit 0\n",
+        )
+        .unwrap();
+        let steps: Vec<_> = script.steps.iter().map(|(line, step)| (*line, step)).collect();
+        assert!(matches!(steps[0], (3, Step::Timeout(d)) if *d == Duration::from_millis(2500)));
+        assert!(matches!(steps[1], (4, Step::Send(s)) if s == "make deploy"));
+        assert!(matches!(steps[2], (5, Step::Expect(r)) if r.as_str() == "Deployed version \\d+"));
+        assert!(matches!(steps[3], (6, Step::Exit(0))));
+        assert_eq!(steps.len(), 4);
+    }
+
+    #[test]
+    fn bad_steps_name_their_line() {
+        for (text, message) in [
+            ("frobnicate\n", "unknown step `frobnicate`"),
+            ("send x\nexpect (\n", "test.script:2"),
+            ("exit zero\n", "test.script:1"),
+            ("timeout -1\n", "test.script:1"),
+        ] {
+            let e = format!("{:#}", parse(text).err().unwrap());
+            assert!(e.contains(message), "{:?}: {}", text, e);
+        }
+    }
+
+    #[test]
+    fn send_types_a_line() {
+        let input = run("send echo hi\nsend exit\n", &[]).unwrap();
+        assert_eq!(input, b"echo hi\rexit\r");
+    }
+
+    #[test]
+    fn expect_matches_output_without_escape_sequences() {
+        run("expect hello world", &["\x1b[1mhello\x1b[0m", " world\r\n"]).unwrap();
+        let e = run("timeout 0.2\nexpect 1m", &["\x1b[1mhello\x1b[0m\r\n"]).unwrap_err();
+        assert_eq!(e.to_string(), "line 2: expected /1m/ within 200ms");
+    }
+
+    #[test]
+    fn matched_output_is_not_matched_again() {
+        let e = run("timeout 0.2\nexpect ok\nexpect ok\n", &["ok\r\n"]).unwrap_err();
+        assert_eq!(e.to_string(), "line 3: expected /ok/ within 200ms");
+        run("expect ok\nexpect ok\n", &["ok ok\r\n"]).unwrap();
+    }
+
+    #[test]
+    fn exit_checks_the_finished_command() {
+        run("send true\nexit 0\n", &["$ ", &end(0)]).unwrap();
+        let e = run("send false\nexit 0\n", &[&end(1)]).unwrap_err();
+        assert_eq!(e.to_string(), "line 2: exit code 1, expected 0");
+    }
+
+    #[test]
+    fn waiting_stops_when_the_shell_exits() {
+        let script = parse("expect never\n").unwrap();
+        let watch = Watch::default();
+        watch.close();
+        let started = Instant::now();
+        assert!(script.run(&watch, &mut Vec::new()).is_err());
+        assert!(started.elapsed() < DEFAULT_TIMEOUT);
+    }
+
+    #[test]
+    fn unmatched_output_is_capped() {
+        let watch = Watch::default();
+        let chunk = "é".repeat(4096);
+        for _ in 0..(3 * MAX_TEXT / chunk.len()) {
+            watch.output(chunk.as_bytes());
+        }
+        let text = &watch.state.lock().unwrap().seen.text;
+        assert!(text.len() <= 2 * MAX_TEXT);
+        assert!(text.len() >= MAX_TEXT);
+        assert!(text.chars().all(|c| c == 'é'));
+    }
+}
@@ -0,0 +1,193 @@
+//! Agent mode: dial out to a hub and serve PTY sessions over that connection
+//!
+//! Used for machines behind NAT or firewalls that the hub can't reach directly.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use futures::{sink::SinkExt, stream::StreamExt};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{
+    config::Config,
+    proxy,
+    session::{PtySession, SessionOutput},
+    tunnel::{encode_output, TunnelFrame},
+};
+
+/// Delay before reconnecting after the tunnel drops
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// How the agent introduces itself to the hub
+pub struct Registration {
+    pub id: String,
+    pub token: Option<String>,
+    pub labels: HashMap<String, String>,
+}
+
+pub async fn run(hub: String, registration: Registration, config: Arc<Config>) {
+    loop {
+        match connect(&hub, &registration, &config).await {
+            Ok(()) => tracing::info!("Tunnel to {} closed", hub),
+            Err(e) => tracing::error!("Tunnel to {} failed: {}", hub, e),
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn connect(hub: &str, registration: &Registration, config: &Arc<Config>) -> anyhow::Result<()> {
+    let (ws, _) = tokio_tungstenite::connect_async(hub).await?;
+    let (mut sink, mut stream) = ws.split();
+    tracing::info!("Connected to hub {} as {}", hub, registration.id);
+
+    let register = TunnelFrame::Register {
+        id: registration.id.clone(),
+        token: registration.token.clone(),
+        labels: registration.labels.clone(),
+    };
+    sink.send(Message::Text(serde_json::to_string(&register)?))
+        .await?;
+
+    // Every session task writes into the tunnel through this channel
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+    let send_task = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if sink.send(msg).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut sessions: HashMap<u64, PtySession> = HashMap::new();
+    // Sessions whose shell exited by itself. The hub drops them on our Close without sending
+    // one back, so they're removed here.
+    let (ended_tx, mut ended_rx) = mpsc::unbounded_channel::<u64>();
+
+    loop {
+        let msg = tokio::select! {
+            msg = stream.next() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            Some(session) = ended_rx.recv() => {
+                // Dropping the session reaps the shell and closes the PTY
+                sessions.remove(&session);
+                continue;
+            }
+        };
+        let text = match msg? {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let frame = match serde_json::from_str::<TunnelFrame>(&text) {
+            Ok(frame) => frame,
+            Err(e) => {
+                tracing::warn!("Bad frame from hub: {}", e);
+                continue;
+            }
+        };
+
+        match frame {
+            TunnelFrame::Open {
+                session,
+                cwd,
+                profile,
+                namespace,
+                pod,
+                tmux,
+            } => match open_session(config, cwd, profile, namespace, pod, tmux) {
+                Ok((pty, mut output)) => {
+                    sessions.insert(session, pty);
+
+                    let tx = tx.clone();
+                    let ended = ended_tx.clone();
+                    tokio::spawn(async move {
+                        loop {
+                            tokio::select! {
+                                Some(data) = output.output.recv() => {
+                                    if tx.send(Message::Binary(encode_output(session, &data))).is_err() {
+                                        return;
+                                    }
+                                }
+                                Some(msg) = output.logs.recv() => {
+                                    let frame = TunnelFrame::Log { session, msg };
+                                    if let Ok(json) = serde_json::to_string(&frame) {
+                                        if tx.send(Message::Text(json)).is_err() {
+                                            return;
+                                        }
+                                    }
+                                }
+                                else => break,
+                            }
+                        }
+                        // The shell exited
+                        let _ = ended.send(session);
+                        if let Ok(json) = serde_json::to_string(&TunnelFrame::Close { session }) {
+                            let _ = tx.send(Message::Text(json));
+                        }
+                    });
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to open session {}: {}", session, e);
+                    if let Ok(json) = serde_json::to_string(&TunnelFrame::Close { session }) {
+                        let _ = tx.send(Message::Text(json));
+                    }
+                }
+            },
+            TunnelFrame::Client { session, msg } => {
+                if let Some(pty) = sessions.get(&session) {
+                    pty.handle_client_msg(msg);
+                }
+            }
+            TunnelFrame::Close { session } => {
+                // Dropping the session kills the shell
+                sessions.remove(&session);
+            }
+            TunnelFrame::ProxyRequest {
+                request,
+                port,
+                path,
+            } => {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let (response, error) = match proxy::fetch(port, &path).await {
+                        Ok(response) => (Some(response), None),
+                        Err(e) => (None, Some(e)),
+                    };
+                    let frame = TunnelFrame::ProxyResponse {
+                        request,
+                        response,
+                        error,
+                    };
+                    if let Ok(json) = serde_json::to_string(&frame) {
+                        let _ = tx.send(Message::Text(json));
+                    }
+                });
+            }
+            frame => tracing::warn!("Unexpected frame from hub: {:?}", frame),
+        }
+    }
+
+    send_task.abort();
+    Ok(())
+}
+
+fn open_session(
+    config: &Config,
+    cwd: Option<String>,
+    profile: Option<String>,
+    namespace: Option<String>,
+    pod: Option<String>,
+    tmux: Option<String>,
+) -> anyhow::Result<(PtySession, SessionOutput)> {
+    let cwd = config.resolve_cwd(cwd.as_deref()).map_err(anyhow::Error::msg)?;
+    let backend = config
+        .profile(profile.as_deref())
+        .and_then(|backend| backend.with_target(namespace.as_deref(), pod.as_deref()))
+        .and_then(|backend| backend.with_tmux_session(tmux.as_deref().unwrap_or("remote-shell")))
+        .map_err(anyhow::Error::msg)?;
+
+    PtySession::spawn(&backend, &cwd, config.log_limits, config.clipboard)
+}
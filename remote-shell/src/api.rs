@@ -1,317 +1,866 @@
 //! Web API
 
-use std::{
-    io::{Read, Write},
-    sync::{Arc, Mutex},
-    thread,
-};
+use std::{collections::HashMap, future::Future, path::Path, sync::Arc, time::Duration};
 
 use axum::{
-    extract::ws::{Message, WebSocket, WebSocketUpgrade},
-    response::{Html, IntoResponse},
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path as UrlPath, Query, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
 };
 use futures::{sink::SinkExt, stream::StreamExt};
-use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
-use tokio::sync::mpsc;
-
-
-use crate::{ClientMsg, ServerLogMsg};
-
-pub async fn index_handler() -> Html<&'static str> {
-    // Force recompilation when index.html changes by including bytes, though include_str matches too.
-    // We add a cache buster comment implicitly by changing main.rs
-    Html(include_str!("../static/index.html"))
-}
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tracing::Instrument;
+
+use crate::{
+    approval::{ApprovalStore, PendingCommand},
+    auth::{AuthUser, Role},
+    backend::Backend,
+    compress::{self, Deflate},
+    history::CommandInfo,
+    motd,
+    recording::Recording,
+    registry::{Control, Frame, Naming, SessionEntry, SessionFilter, SessionInfo},
+    security,
+    session::PtySession,
+    share::share_ended,
+    telemetry::CommandSpans,
+    tunnel::Agent,
+    AppState, ClientMsg, ExecStream, ServerLogMsg,
+};
 
-pub async fn ws_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
-    ws.on_upgrade(handle_socket)
+#[derive(Serialize)]
+pub struct HostInfo {
+    id: String,
+    labels: HashMap<String, String>,
+    sessions: usize,
 }
 
-async fn handle_socket(socket: WebSocket) {
-    tracing::info!("New WebSocket connection established");
-    let pty_system = NativePtySystem::default();
-
-    let pair = pty_system
-        .openpty(PtySize {
-            rows: 24,
-            cols: 80,
-            pixel_width: 0,
-            pixel_height: 0,
+/// Hosts (connected agents) sessions can be routed to with `/ws?host=<id>`
+pub async fn hosts_handler(_user: AuthUser, State(state): State<AppState>) -> Json<Vec<HostInfo>> {
+    let hosts = state
+        .agents
+        .list()
+        .into_iter()
+        .map(|agent| HostInfo {
+            id: agent.id.clone(),
+            labels: agent.labels.clone(),
+            sessions: agent.session_count(),
         })
-        .expect("Failed to create PTY");
+        .collect();
 
-    let shell = std::env::var("SHELL").unwrap_or_else(|_| "bash".to_string());
-    let is_bash = shell.ends_with("bash");
-    let is_zsh = shell.ends_with("zsh");
+    Json(hosts)
+}
 
-    let mut cmd = CommandBuilder::new(&shell);
+/// Live sessions, which can be observed with `/ws?session=<id>`. `?q=`, `?tag=` and `?host=`
+/// narrow them down.
+pub async fn sessions_handler(
+    _user: AuthUser,
+    State(state): State<AppState>,
+    Query(filter): Query<SessionFilter>,
+) -> Json<Vec<SessionInfo>> {
+    Json(state.sessions.list(&filter))
+}
 
-    if is_bash {
-        cmd.args(&["--rcfile", "static/shell-integration.bash"]);
+/// End a session (admins only)
+pub async fn close_session_handler(
+    user: AuthUser,
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+) -> StatusCode {
+    if !user.is_admin() {
+        return StatusCode::FORBIDDEN;
     }
 
-    cmd.cwd(std::env::current_dir().unwrap());
-    cmd.env("TERM", "xterm-256color");
-
-    let _child = pair
-        .slave
-        .spawn_command(cmd)
-        .expect("Failed to spawn shell");
-
-    let master = pair.master;
-    let mut reader = master.try_clone_reader().expect("Failed to clone reader");
-    let writer = master.take_writer().expect("Failed to take writer");
-
-    // We wrap writer in a Mutex to use it in the loop (which is technically blocking, but fast for buffer write)
-    // Using Arc<Mutex<...>> for thread safety if we were to share it, here we clone for the loop.
-    let writer = Arc::new(Mutex::new(writer));
-    let master = Arc::new(Mutex::new(master));
-
-    // Initialize Shell Integration for Zsh (since we can't use --rcfile)
-    if is_zsh {
-        if let Ok(mut w) = writer.lock() {
-            // Source the integration script
-            // We add a newline to ensure it executes
-            // To hide the command itself from history/view, usually we can't easily do it via injection
-            // without "space" prefix (if configured) or just accept it prints once.
-            let init_cmd = "source static/shell-integration.zsh\n";
-            let _ = w.write_all(init_cmd.as_bytes());
-            let _ = w.flush();
+    match state.sessions.get(&id) {
+        Some(entry) => {
+            tracing::info!(target: "audit", user = %user.name, session = %id, "Closing session");
+            entry.close();
+            StatusCode::NO_CONTENT
         }
+        None => StatusCode::NOT_FOUND,
     }
+}
 
-    let (tx_output, mut rx_output) = mpsc::channel::<Vec<u8>>(32);
-    let (tx_log, mut rx_log) = mpsc::channel::<ServerLogMsg>(32);
+/// Commands run in a session so far, without their output
+pub async fn commands_handler(
+    _user: AuthUser,
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+) -> Result<Json<Vec<CommandInfo>>, StatusCode> {
+    let entry = state.sessions.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Json(entry.history.list()))
+}
 
-    // Spawn blocking thread for reading PTY
-    thread::spawn(move || {
-        let mut buf = [0u8; 2048];
-        let mut parser = vte::Parser::new();
-        let mut interpreter = LogInterpreter::new(tx_log);
+#[derive(Deserialize, Debug)]
+pub struct ExecRequest {
+    command: String,
+    /// Give up waiting after this long, 60 seconds by default
+    #[serde(default = "default_exec_timeout")]
+    timeout_secs: u64,
+}
+
+fn default_exec_timeout() -> u64 {
+    60
+}
 
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecResponse {
+    stdout: String,
+    stderr: String,
+    /// Unset if the command didn't finish in time
+    exit_code: Option<i32>,
+}
+
+/// Run a command next to a session's shell (same backend and start directory) with piped
+/// stdout/stderr, for automation
+pub async fn exec_handler(
+    user: AuthUser,
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+    Json(request): Json<ExecRequest>,
+) -> Result<Json<ExecResponse>, (StatusCode, &'static str)> {
+    let entry = state
+        .sessions
+        .get(&id)
+        .ok_or((StatusCode::NOT_FOUND, "Unknown session"))?;
+    if !user.can_interact(&entry) {
+        return Err((StatusCode::FORBIDDEN, "Not allowed to run commands in this session"));
+    }
+    if state.approvals.required(&entry, &user) {
+        return Err((StatusCode::FORBIDDEN, "Commands on this host need approval"));
+    }
+    let mut frames = entry
+        .subscribe()
+        .ok_or((StatusCode::NOT_FOUND, "Session has ended"))?;
+
+    let exec_id = format!("rest-{:016x}", rand::random::<u64>());
+    tracing::info!(
+        target: "audit",
+        user = %user.name,
+        session = %entry.id,
+        command = %request.command,
+        "Exec"
+    );
+    entry.send(ClientMsg::Run {
+        data: request.command,
+        id: exec_id.clone(),
+        piped: Some(true),
+    });
+
+    let mut response = ExecResponse::default();
+    let collect = async {
         loop {
-            match reader.read(&mut buf) {
-                Ok(n) if n > 0 => {
-                    let data = buf[..n].to_vec();
-                    // Send RAW output to frontend terminal
-                    if tx_output.blocking_send(data.clone()).is_err() {
-                        break;
+            match frames.recv().await {
+                Ok(Frame::Log(ServerLogMsg::ExecOutput { id, stream, data })) if id == exec_id => {
+                    match stream {
+                        ExecStream::Stdout => response.stdout.push_str(&data),
+                        ExecStream::Stderr => response.stderr.push_str(&data),
                     }
-
-                    // Feed data to VTE parser for log extraction
-                    parser.advance(&mut interpreter, &data);
-                    
-                    // Flush any pending text after processing a chunk (optional but good for responsiveness)
-
-                    // interpreter.flush(); // Actually let's not flush too eagerly to avoid tiny chunks, 
-                    // or maybe flushing on newlines is better. 
-                    // But our flush logic is checking buffer emptiness. 
-                    // Let's do a flush if we have significant data or just trust the next chunk.
-                    // Implementation choice: flush every chunk processing for real-time logs?
-                    // Yes, logs-container updates are better in real-time.
-                    interpreter.flush(); 
                 }
-                Ok(_) => {
-                    tracing::info!("PTY EOF");
+                Ok(Frame::Log(ServerLogMsg::ExecEnd { id, exit_code })) if id == exec_id => {
+                    response.exit_code = Some(exit_code);
                     break;
                 }
-                Err(e) => {
-                    tracing::error!("PTY Read Error: {}", e);
-                    break;
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!("Exec {} fell behind, dropped {} frames", exec_id, n);
                 }
+                Err(broadcast::error::RecvError::Closed) => break,
             }
         }
-        tracing::info!("PTY read thread exited");
-    });
+    };
+    let _ = tokio::time::timeout(Duration::from_secs(request.timeout_secs), collect).await;
 
-    let (mut sender, mut receiver) = socket.split();
+    Ok(Json(response))
+}
 
-    let send_task = tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                Some(data) = rx_output.recv() => {
-                    if sender.send(Message::Binary(data)).await.is_err() {
-                        break;
-                    }
-                }
-                Some(log_msg) = rx_log.recv() => {
-                    if let Ok(json) = serde_json::to_string(&log_msg) {
-                         if sender.send(Message::Text(json)).await.is_err() {
-                            break;
-                         }
-                    }
-                }
-                else => break,
-            }
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    /// The cleaned output as a plain text download
+    #[default]
+    Text,
+    /// The output plus the command's metadata
+    Json,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TranscriptParams {
+    #[serde(default)]
+    pub format: TranscriptFormat,
+}
+
+/// The cleaned output of one command, `?format=json` for metadata too
+pub async fn transcript_handler(
+    _user: AuthUser,
+    State(state): State<AppState>,
+    UrlPath((id, seq)): UrlPath<(String, u64)>,
+    Query(params): Query<TranscriptParams>,
+) -> Response {
+    let Some(record) = state
+        .sessions
+        .get(&id)
+        .and_then(|entry| entry.history.get(seq))
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    match params.format {
+        TranscriptFormat::Json => Json(record).into_response(),
+        TranscriptFormat::Text => (
+            [
+                (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"session-{}-command-{}.txt\"", id, seq),
+                ),
+            ],
+            record.output,
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShareRequest {
+    /// Link lifetime, one hour by default
+    #[serde(default = "default_share_ttl")]
+    ttl_secs: u64,
+    /// Let the guest type into the session, not just watch
+    #[serde(default)]
+    interactive: bool,
+}
+
+fn default_share_ttl() -> u64 {
+    3600
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareResponse {
+    id: String,
+    url: String,
+    expires_at: u64,
+}
+
+/// Mint a share link granting access to one session without an account
+pub async fn create_share_handler(
+    user: AuthUser,
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+    Json(request): Json<ShareRequest>,
+) -> Result<Json<ShareResponse>, StatusCode> {
+    let entry = state.sessions.get(&id).ok_or(StatusCode::NOT_FOUND)?;
+
+    // Can't hand out more access than you have
+    if request.interactive && !user.can_interact(&entry) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let (grant, token) = state.shares.create(
+        &user.name,
+        &entry.id,
+        Duration::from_secs(request.ttl_secs),
+        request.interactive,
+    );
+    tracing::info!(
+        target: "audit",
+        user = %user.name,
+        session = %entry.id,
+        share = %grant.id,
+        interactive = grant.interactive,
+        "Created share link"
+    );
+
+    Ok(Json(ShareResponse {
+        id: grant.id,
+        url: format!("/share/{}", token),
+        expires_at: grant.expires_at,
+    }))
+}
+
+pub async fn revoke_share_handler(
+    user: AuthUser,
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+) -> StatusCode {
+    match state.shares.revoke(&id, &user) {
+        Ok(()) => {
+            tracing::info!(target: "audit", user = %user.name, share = %id, "Revoked share link");
+            StatusCode::NO_CONTENT
         }
+        Err(status) => status,
+    }
+}
+
+/// Commands waiting for approval (admins only)
+pub async fn approvals_handler(
+    user: AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<PendingCommand>>, StatusCode> {
+    if !user.is_admin() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let mut pending = state.approvals.list();
+    pending.retain(|p| {
+        let live = state.sessions.get(&p.session).is_some();
+        if !live {
+            state.approvals.discard_session(&p.session);
+        }
+        live
     });
 
-struct LogInterpreter {
-    tx_log: mpsc::Sender<ServerLogMsg>,
-    capturing: bool,
-    buffer: String,
+    Ok(Json(pending))
 }
 
-impl LogInterpreter {
-    fn new(tx_log: mpsc::Sender<ServerLogMsg>) -> Self {
-        Self {
-            tx_log,
-            capturing: false,
-            buffer: String::new(),
-        }
+/// Write a held command to its session (admins only)
+pub async fn approve_handler(
+    user: AuthUser,
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+) -> StatusCode {
+    if !user.is_admin() {
+        return StatusCode::FORBIDDEN;
+    }
+
+    let Some(pending) = state.approvals.take(&id) else {
+        return StatusCode::NOT_FOUND;
+    };
+    let Some(entry) = state.sessions.get(&pending.session) else {
+        return StatusCode::GONE;
+    };
+
+    tracing::info!(
+        target: "audit",
+        user = %user.name,
+        session = %entry.id,
+        approval = %pending.id,
+        proposed_by = %pending.user,
+        command = %pending.command,
+        "Approved Run"
+    );
+    entry.send(ClientMsg::Run {
+        data: pending.command,
+        id: format!("approval-{}", pending.id),
+        piped: Some(pending.piped),
+    });
+    StatusCode::NO_CONTENT
+}
+
+/// Drop a held command (admins only)
+pub async fn deny_handler(
+    user: AuthUser,
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+) -> StatusCode {
+    if !user.is_admin() {
+        return StatusCode::FORBIDDEN;
     }
 
-    fn flush(&mut self) {
-        if !self.buffer.is_empty() {
-            let _ = self.tx_log.blocking_send(ServerLogMsg::LogOutput {
-                data: std::mem::take(&mut self.buffer),
-            });
+    match state.approvals.take(&id) {
+        Some(pending) => {
+            tracing::info!(
+                target: "audit",
+                user = %user.name,
+                session = %pending.session,
+                approval = %pending.id,
+                proposed_by = %pending.user,
+                command = %pending.command,
+                "Denied Run"
+            );
+            StatusCode::NO_CONTENT
         }
+        None => StatusCode::NOT_FOUND,
     }
 }
 
-impl vte::Perform for LogInterpreter {
-    fn print(&mut self, c: char) {
-        if self.capturing {
-            self.buffer.push(c);
+#[derive(Deserialize, Debug)]
+pub struct WsParams {
+    /// Directory the shell should start in, must be inside one of the allowed roots
+    cwd: Option<String>,
+    /// Named profile from the config selecting the session backend
+    profile: Option<String>,
+    /// Kubernetes namespace for kubernetes profiles, checked against the profile's allowlist
+    namespace: Option<String>,
+    /// Kubernetes pod for kubernetes profiles, checked against the profile's allowlist
+    pod: Option<String>,
+    /// Run the session on a connected agent instead of this machine
+    host: Option<String>,
+    /// Attach to an existing session instead of starting a new one
+    session: Option<String>,
+    /// Share link token, used instead of an account
+    share: Option<String>,
+    /// tmux session to create or attach to with tmux profiles (`web-<user>` by default)
+    tmux: Option<String>,
+    /// Name for a new session, to find it by in `GET /api/sessions`
+    name: Option<String>,
+    /// Comma separated tags for a new session
+    tags: Option<String>,
+}
+
+impl WsParams {
+    fn naming(&self) -> Naming {
+        Naming {
+            name: self.name.clone().filter(|name| !name.trim().is_empty()),
+            tags: self
+                .tags
+                .as_deref()
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect(),
         }
     }
+}
 
-    fn execute(&mut self, byte: u8) {
-        if self.capturing {
-            // Handle basic control chars that are useful in logs: \n, \t, \r
-            if byte == b'\n' {
-                self.buffer.push('\n');
-            } else if byte == b'\t' {
-                self.buffer.push('\t');
-            } else if byte == b'\r' {
-                 // Ignore CR or handle it? Usually \r\n is processed.
-                 // For logs, simple \n is usually enough. 
-                 // If we push \r, it might mess up some simple log viewers, but let's keep it safe or ignore?
-                 // Let's ignore it to keep logs clean text.
+/// tmux session name used when the client doesn't pick one. User names are escaped into
+/// what tmux allows one to one (`_` and anything but letters, digits and `-` as `_<hex byte>`),
+/// so different users never share a session.
+fn default_tmux_session(user: &AuthUser) -> String {
+    let mut name = String::from("web-");
+    for c in user.name.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' {
+            name.push(c);
+        } else {
+            let mut utf8 = [0; 4];
+            for byte in c.encode_utf8(&mut utf8).bytes() {
+                name.push_str(&format!("_{:02x}", byte));
             }
         }
     }
+    name
+}
+
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    user: Option<AuthUser>,
+    Query(mut params): Query<WsParams>,
+) -> Response {
+    // Clients that can inflate output frames offer the subprotocol, serve_client sees whether
+    // it was picked
+    let ws = ws.protocols([compress::PROTOCOL]);
+    if !security::origin_allowed(&headers, &state.config.http) {
+        tracing::warn!("Rejected WebSocket from origin {:?}", headers.get(header::ORIGIN));
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+
+    if let Some(token) = params.share.as_deref() {
+        let (grant, revoked) = match state.shares.verify(token) {
+            Ok(share) => share,
+            Err(e) => return (StatusCode::FORBIDDEN, e).into_response(),
+        };
+        let Some((entry, frames)) = state
+            .sessions
+            .get(&grant.session)
+            .and_then(|entry| entry.subscribe().map(|frames| (entry, frames)))
+        else {
+            return (StatusCode::NOT_FOUND, "Shared session has ended").into_response();
+        };
+
+        let guest = AuthUser::new(format!("share:{}", grant.id), Role::Viewer, Vec::new(), Vec::new());
+        let interactive = grant.interactive;
+        tracing::info!(target: "audit", user = %guest.name, session = %entry.id, interactive, "Attaching via share link");
+        let approvals = state.approvals.clone();
+        return ws.on_upgrade(move |socket| {
+            let ends = share_ended(grant, revoked);
+            serve_client(socket, entry, frames, interactive, guest, approvals, ends)
+        });
+    }
 
-    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
-        if params.is_empty() {
-            return;
+    let Some(user) = user else {
+        return (StatusCode::UNAUTHORIZED, "Missing or invalid token").into_response();
+    };
+
+    if let Some(id) = params.session.as_deref() {
+        let Some((entry, frames)) = state
+            .sessions
+            .get(id)
+            .and_then(|entry| entry.subscribe().map(|frames| (entry, frames)))
+        else {
+            return (StatusCode::NOT_FOUND, format!("Unknown session {}", id)).into_response();
+        };
+        let interactive = user.can_interact(&entry);
+        tracing::info!(
+            target: "audit",
+            user = %user.name,
+            session = %entry.id,
+            interactive,
+            "Attaching to session"
+        );
+        let approvals = state.approvals.clone();
+        return ws.on_upgrade(move |socket| {
+            serve_client(socket, entry, frames, interactive, user, approvals, std::future::pending())
+        });
+    }
+
+    if !user.can_open(params.host.as_deref(), params.profile.as_deref()) {
+        tracing::warn!("User {} may not open this session", user.name);
+        return (StatusCode::FORBIDDEN, "Not allowed to open this session").into_response();
+    }
+
+    params.tmux = Some(params.tmux.take().unwrap_or_else(|| default_tmux_session(&user)));
+
+    // Remote sessions are resolved by the agent against its own config
+    if let Some(host) = params.host.clone() {
+        let Some(agent) = state.agents.get(&host) else {
+            return (StatusCode::NOT_FOUND, format!("Unknown host {}", host)).into_response();
+        };
+        tracing::info!(target: "audit", user = %user.name, host = %host, "Opening remote session");
+        return ws.on_upgrade(move |socket| async move {
+            let (entry, frames) = start_remote(&state, &user, agent, params);
+            let approvals = state.approvals.clone();
+            serve_client(socket, entry.clone(), frames, true, user, approvals, std::future::pending())
+                .await;
+            entry.close();
+        });
+    }
+
+    // Resolve before upgrading so a bad cwd is reported as a plain HTTP error
+    let cwd = match state.config.resolve_cwd(params.cwd.as_deref()) {
+        Ok(cwd) => cwd,
+        Err(e) => {
+            tracing::warn!("Rejected WebSocket connection: {}", e);
+            return (StatusCode::FORBIDDEN, e).into_response();
         }
+    };
 
-        // Check if code is 6973
-        // params[0] like "6973"
-        let code = params[0];
-        if code == b"6973" {
-             // Handle simple command parameter structure (params[1])
-             // Cases: 
-             // 1. 6973;START;USER;HOST;CWD...
-             // 2. 6973;END;0
-            if params.len() > 1 {
-                let cmd = params[1];
-                
-                if cmd == b"START" {
-                    self.capturing = true;
-                    self.buffer.clear(); 
-                    
-                    // Parse Context: params[2]=USER, params[3]=HOST, params[4..]=CWD
-                    let mut user = String::new();
-                    let mut host = String::new();
-                    let mut cwd = String::new();
-
-                    if params.len() > 2 {
-                        user = String::from_utf8_lossy(params[2]).to_string();
-                    }
-                    if params.len() > 3 {
-                        host = String::from_utf8_lossy(params[3]).to_string();
-                    }
-                    if params.len() > 4 {
-                        // Join remaining parts with ; in case CWD contained semicolons
-                        let parts: Vec<String> = params[4..].iter()
-                            .map(|&p| String::from_utf8_lossy(p).to_string())
-                            .collect();
-                        cwd = parts.join(";");
-                    }
+    let backend = match state.config.profile(params.profile.as_deref()) {
+        Ok(backend) => backend,
+        Err(e) => {
+            tracing::warn!("Rejected WebSocket connection: {}", e);
+            return (StatusCode::NOT_FOUND, e).into_response();
+        }
+    };
+
+    let backend = match backend
+        .with_target(params.namespace.as_deref(), params.pod.as_deref())
+        .and_then(|backend| backend.with_tmux_session(params.tmux.as_deref().unwrap_or_default()))
+    {
+        Ok(backend) => backend,
+        Err(e) => {
+            tracing::warn!("Rejected WebSocket connection: {}", e);
+            return (StatusCode::FORBIDDEN, e).into_response();
+        }
+    };
+
+    // Several web clients can share a tmux session: join the one already running it
+    if let Some((entry, frames)) = backend
+        .tmux_session()
+        .and_then(|name| state.sessions.find_tmux(params.profile.as_deref(), name))
+        .and_then(|entry| entry.subscribe().map(|frames| (entry, frames)))
+    {
+        let interactive = user.can_interact(&entry);
+        tracing::info!(
+            target: "audit",
+            user = %user.name,
+            session = %entry.id,
+            tmux = entry.tmux.as_deref().unwrap_or_default(),
+            interactive,
+            "Joining tmux session"
+        );
+        let approvals = state.approvals.clone();
+        return ws.on_upgrade(move |socket| {
+            serve_client(socket, entry, frames, interactive, user, approvals, std::future::pending())
+        });
+    }
 
-                    let _ = self.tx_log.blocking_send(ServerLogMsg::LogStart {
-                        user,
-                        host,
-                        cwd,
-                    });
-
-                } else if cmd.starts_with(b"END") {
-                     // Flush pending buffer first
-                    self.flush();
-
-                    let mut exit_code = 0;
-                    
-                    // Try to extract exit code
-                    // Case A: 6973;END;123 (Standard vte split) -> params[1]="END", params[2]="123"
-                    if params.len() > 2 {
-                        if let Ok(s) = std::str::from_utf8(params[2]) {
-                            if let Ok(n) = s.parse::<i32>() {
-                                exit_code = n;
-                            }
+    if let Backend::Kubernetes {
+        target: Some(target),
+        ..
+    } = &backend
+    {
+        tracing::info!(
+            target: "audit",
+            user = %user.name,
+            profile = params.profile.as_deref().unwrap_or_default(),
+            namespace = %target.namespace,
+            pod = %target.pod,
+            "Opening kubernetes exec session"
+        );
+    } else {
+        tracing::info!(target: "audit", user = %user.name, cwd = %cwd.display(), "Opening local session");
+    }
+
+    ws.on_upgrade(move |socket| async move {
+        let naming = params.naming();
+        let (entry, frames) = match start_local(&state, &user, params.profile, naming, &cwd, &backend) {
+            Ok(started) => started,
+            Err(e) => {
+                tracing::error!("Failed to start session: {}", e);
+                return;
+            }
+        };
+        let approvals = state.approvals.clone();
+        serve_client(socket, entry.clone(), frames, true, user, approvals, std::future::pending())
+            .await;
+        // The session lives as long as the client that opened it, except for tmux sessions which
+        // serve_client closes once nobody is attached
+        if entry.tmux.is_none() {
+            entry.close();
+        }
+    })
+}
+
+/// A started session plus the opener's subscription, taken before any output is published
+type Started = (Arc<SessionEntry>, broadcast::Receiver<Frame>);
+
+/// Spawn a shell on this machine and drive it until it exits or is closed
+fn start_local(
+    state: &AppState,
+    user: &AuthUser,
+    profile: Option<String>,
+    naming: Naming,
+    cwd: &Path,
+    backend: &Backend,
+) -> anyhow::Result<Started> {
+    let (pty, mut output) =
+        PtySession::spawn(backend, cwd, state.config.log_limits, state.config.clipboard)?;
+    let tmux = backend.tmux_session().map(str::to_string);
+    let mut driver = state.sessions.create(&user.name, None, profile, tmux, naming);
+    let entry = driver.entry.clone();
+    let frames = driver.output.subscribe();
+    let span = tracing::info_span!("session", session.id = %entry.id, user = %user.name, host = "local");
+    let mut recording = Recording::start(state.config.record_dir.as_deref(), &entry);
+    if let Some(frame) = motd_frame(state, &crate::hostname()) {
+        recording.frame(&frame);
+        let _ = driver.output.send(frame);
+    }
+
+    tokio::spawn(
+        async move {
+            let mut commands = CommandSpans::default();
+            loop {
+                tokio::select! {
+                    data = output.output.recv() => match data {
+                        Some(data) => {
+                            let frame = Frame::Output(data);
+                            recording.frame(&frame);
+                            let _ = driver.output.send(frame);
                         }
-                    } 
-                    // Case B: 6973;END;123 (If vte didn't split on second semi-col for some reason, rare)
-                    // Or if script sent it weirdly.
-                    else if cmd.len() > 4 && cmd[3] == b';' {
-                         if let Ok(s) = std::str::from_utf8(&cmd[4..]) {
-                            if let Ok(n) = s.parse::<i32>() {
-                                exit_code = n;
+                        None => {
+                            // The shell exited; pass on whatever the log parser produced last
+                            while let Ok(log_msg) = output.logs.try_recv() {
+                                commands.observe(&log_msg);
+                                driver.entry.history.observe(&log_msg);
+                                let frame = Frame::Log(log_msg);
+                                recording.frame(&frame);
+                                let _ = driver.output.send(frame);
                             }
-                         }
+                            break;
+                        }
+                    },
+                    Some(log_msg) = output.logs.recv() => {
+                        commands.observe(&log_msg);
+                        driver.entry.history.observe(&log_msg);
+                        let frame = Frame::Log(log_msg);
+                        recording.frame(&frame);
+                        let _ = driver.output.send(frame);
                     }
-
-                    let _ = self
-                        .tx_log
-                        .blocking_send(ServerLogMsg::LogEnd { exit_code });
-                    self.capturing = false;
+                    Some(control) = driver.control.recv() => match control {
+                        Control::Client(msg) => {
+                            recording.client_msg(&msg);
+                            pty.handle_client_msg(msg);
+                        }
+                        Control::Close => break,
+                    },
                 }
             }
+            // Dropping the PTY kills the shell, dropping the driver unregisters the session
         }
+        .instrument(span),
+    );
+
+    Ok((entry, frames))
+}
+
+/// Ask an agent to start a session and relay it until either side closes it
+fn start_remote(
+    state: &AppState,
+    user: &AuthUser,
+    agent: Arc<Agent>,
+    params: WsParams,
+) -> Started {
+    let naming = params.naming();
+    let (session, mut agent_frames) = agent.open_session(
+        params.cwd,
+        params.profile.clone(),
+        params.namespace,
+        params.pod,
+        params.tmux,
+    );
+    let mut driver = state
+        .sessions
+        .create(&user.name, Some(agent.id.clone()), params.profile, None, naming);
+    let entry = driver.entry.clone();
+    let frames = driver.output.subscribe();
+    let span = tracing::info_span!("session", session.id = %entry.id, user = %user.name, host = %agent.id);
+    let mut recording = Recording::start(state.config.record_dir.as_deref(), &entry);
+    if let Some(frame) = motd_frame(state, &agent.id) {
+        recording.frame(&frame);
+        let _ = driver.output.send(frame);
     }
+
+    tokio::spawn(
+        async move {
+            let mut commands = CommandSpans::default();
+            loop {
+                tokio::select! {
+                    frame = agent_frames.recv() => match frame {
+                        Some(frame) => {
+                            if let Frame::Log(log_msg) = &frame {
+                                commands.observe(log_msg);
+                                driver.entry.history.observe(log_msg);
+                            }
+                            recording.frame(&frame);
+                            let _ = driver.output.send(frame);
+                        }
+                        None => break,
+                    },
+                    Some(control) = driver.control.recv() => match control {
+                        Control::Client(msg) => {
+                            recording.client_msg(&msg);
+                            agent.client_msg(session, msg);
+                        }
+                        Control::Close => break,
+                    },
+                }
+            }
+            agent.close_session(session);
+        }
+        .instrument(span),
+    );
+
+    (entry, frames)
 }
 
+/// The configured MOTD banner for a new session on `host`. It goes out before the driver
+/// starts, so the opener's subscription sees it ahead of anything the shell prints.
+fn motd_frame(state: &AppState, host: &str) -> Option<Frame> {
+    let motd = state.config.motd.as_ref()?;
+    motd::render(motd, host).map(Frame::Output)
+}
 
-    let writer_clone = writer.clone();
-    let master_clone = master.clone();
+/// Relay a session to one browser until it disconnects or `ends` resolves.
+/// Non-interactive clients only watch.
+async fn serve_client(
+    socket: WebSocket,
+    entry: Arc<SessionEntry>,
+    mut frames: broadcast::Receiver<Frame>,
+    interactive: bool,
+    user: AuthUser,
+    approvals: Arc<ApprovalStore>,
+    ends: impl Future<Output = ()> + Send,
+) {
+    tracing::info!("New WebSocket connection for session {}", entry.id);
+    let gated = interactive && approvals.required(&entry, &user);
+    let mut deflate = socket
+        .protocol()
+        .is_some_and(|protocol| protocol == compress::PROTOCOL)
+        .then(Deflate::new);
+
+    let (mut sender, mut receiver) = socket.split();
+
+    let send_task = tokio::spawn(async move {
+        loop {
+            let msg = match frames.recv().await {
+                Ok(Frame::Output(data)) => match &mut deflate {
+                    Some(deflate) => Message::Binary(deflate.frame(&data)),
+                    None => Message::Binary(data),
+                },
+                Ok(Frame::Log(log_msg)) => match serde_json::to_string(&log_msg) {
+                    Ok(json) => Message::Text(json),
+                    Err(_) => continue,
+                },
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    tracing::warn!("Client fell behind, dropped {} frames", n);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            if sender.send(msg).await.is_err() {
+                return;
+            }
+        }
+        // The session ended
+        let _ = sender.send(Message::Close(None)).await;
+    });
+
+    tokio::pin!(ends);
 
     // Handle incoming WebSocket messages
-    while let Some(Ok(msg)) = receiver.next().await {
+    loop {
+        let msg = tokio::select! {
+            msg = receiver.next() => match msg {
+                Some(Ok(msg)) => msg,
+                _ => break,
+            },
+            _ = &mut ends => {
+                tracing::info!("Access for {} to session {} ended", user.name, entry.id);
+                break;
+            }
+        };
+
         match msg {
             Message::Text(text) => {
+                if !interactive {
+                    continue;
+                }
                 if let Ok(parsed) = serde_json::from_str::<ClientMsg>(&text) {
-                    match parsed {
-                        ClientMsg::Input { data } => {
-                            if let Ok(mut w) = writer_clone.lock() {
-                                let _ = w.write_all(data.as_bytes());
-                                let _ = w.flush();
-                            }
-                            tracing::info!("Received input: {}", data);
-                        }
-                        ClientMsg::Run { data, id: _ } => {
-                            if let Ok(mut w) = writer_clone.lock() {
-                                // Just send the raw command. The shell integration (trap) will handle markers.
-                                // We add a newline to ensure execution.
-                                let cmd_str = format!("{}\n", data);
-                                let _ = w.write_all(cmd_str.as_bytes());
-                                let _ = w.flush();
-                            }
-                            tracing::info!("Executed command: {}", data);
-                        }
-                        ClientMsg::Resize { cols, rows } => {
-                            if let Ok(m) = master_clone.lock() {
-                                let _ = m.resize(PtySize {
-                                    rows,
-                                    cols,
-                                    pixel_width: 0,
-                                    pixel_height: 0,
-                                });
-                            }
-                            tracing::info!("Resized PTY to {} cols and {} rows", cols, rows);
+                    let kind = match &parsed {
+                        ClientMsg::Input { .. } => "input",
+                        ClientMsg::Run { .. } => "run",
+                        ClientMsg::Resize { .. } => "resize",
+                    };
+                    let _span = tracing::debug_span!(
+                        "ws_message",
+                        session.id = %entry.id,
+                        user = %user.name,
+                        kind
+                    )
+                    .entered();
+
+                    // Typing straight into the terminal would bypass approval, so only Run
+                    // (held) and Resize get through
+                    if gated && !matches!(parsed, ClientMsg::Resize { .. }) {
+                        if let ClientMsg::Run { data, piped, .. } = parsed {
+                            let pending = approvals.submit(
+                                &entry.id,
+                                &user.name,
+                                data,
+                                piped.unwrap_or_default(),
+                            );
+                            tracing::info!(
+                                target: "audit",
+                                user = %user.name,
+                                session = %entry.id,
+                                approval = %pending.id,
+                                command = %pending.command,
+                                "Run held for approval"
+                            );
                         }
+                        continue;
                     }
+                    if let ClientMsg::Run { data, .. } = &parsed {
+                        tracing::info!(
+                            target: "audit",
+                            user = %user.name,
+                            session = %entry.id,
+                            command = %data,
+                            "Run"
+                        );
+                    }
+                    entry.send(parsed);
                 }
             }
             Message::Close(_) => break,
@@ -320,4 +869,43 @@ impl vte::Perform for LogInterpreter {
     }
 
     send_task.abort();
+    // Wait for the task to go away so its subscription no longer counts
+    let _ = send_task.await;
+
+    // tmux keeps the shell itself alive, this only drops our attached tmux client
+    if entry.tmux.is_some() && entry.client_count() == 0 {
+        entry.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{auth::Role, backend::Backend};
+
+    fn tmux_name(user: &str) -> String {
+        default_tmux_session(&AuthUser::new(user.to_string(), Role::Operator, Vec::new(), Vec::new()))
+    }
+
+    #[test]
+    fn default_tmux_sessions_differ_per_user() {
+        let names = ["a.b", "a-b", "a_b", "a b", "a_2eb", "ä"].map(tmux_name);
+        for (i, name) in names.iter().enumerate() {
+            assert!(!names[i + 1..].contains(name), "{} is used twice", name);
+        }
+        assert_eq!(tmux_name("alice-1"), "web-alice-1");
+        assert_eq!(tmux_name("a.b"), "web-a_2eb");
+    }
+
+    #[test]
+    fn default_tmux_sessions_are_valid_names() {
+        let tmux = Backend::Tmux {
+            shell: None,
+            session: None,
+        };
+        for user in ["alice", "a.b@example.com", "ä:ö"] {
+            assert!(tmux.clone().with_tmux_session(&tmux_name(user)).is_ok(), "{}", user);
+        }
+    }
 }
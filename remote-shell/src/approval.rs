@@ -0,0 +1,93 @@
+//! Commands from non-admins on sensitive hosts, held until an admin approves them
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{
+    auth::{AuthUser, LOCAL_HOST},
+    registry::SessionEntry,
+};
+
+/// What `GET /api/approvals` reports about a held command
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingCommand {
+    pub id: String,
+    pub session: String,
+    /// Who proposed the command
+    pub user: String,
+    pub command: String,
+    /// Run outside the terminal with separate stdout/stderr
+    pub piped: bool,
+    /// Unix timestamp in seconds
+    pub created_at: u64,
+}
+
+pub struct ApprovalStore {
+    /// Hosts where commands need approval ("local" for this server)
+    hosts: Vec<String>,
+    pending: Mutex<HashMap<String, PendingCommand>>,
+    next_id: AtomicU64,
+}
+
+impl ApprovalStore {
+    pub fn new(hosts: Vec<String>) -> Self {
+        Self {
+            hosts,
+            pending: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether `user`'s commands in `session` have to be approved first
+    pub fn required(&self, session: &SessionEntry, user: &AuthUser) -> bool {
+        let host = session.host.as_deref().unwrap_or(LOCAL_HOST);
+        !user.is_admin() && self.hosts.iter().any(|h| h == host)
+    }
+
+    /// Hold a command until an admin decides on it
+    pub fn submit(&self, session: &str, user: &str, command: String, piped: bool) -> PendingCommand {
+        let pending = PendingCommand {
+            id: (self.next_id.fetch_add(1, Ordering::Relaxed) + 1).to_string(),
+            session: session.to_string(),
+            user: user.to_string(),
+            command,
+            piped,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        };
+
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(pending.id.clone(), pending.clone());
+        pending
+    }
+
+    /// Held commands, oldest first
+    pub fn list(&self) -> Vec<PendingCommand> {
+        let mut pending: Vec<_> = self.pending.lock().unwrap().values().cloned().collect();
+        pending.sort_by_key(|p| (p.created_at, p.id.parse::<u64>().unwrap_or(0)));
+        pending
+    }
+
+    /// Remove a held command to approve or deny it
+    pub fn take(&self, id: &str) -> Option<PendingCommand> {
+        self.pending.lock().unwrap().remove(id)
+    }
+
+    /// Drop everything held for a session that has ended
+    pub fn discard_session(&self, session: &str) {
+        self.pending.lock().unwrap().retain(|_, p| p.session != session);
+    }
+}
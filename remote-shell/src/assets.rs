@@ -0,0 +1,103 @@
+//! Static assets: embedded in the binary, or served from disk with `--static-dir`
+
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use rust_embed::RustEmbed;
+
+use crate::{security, AppState};
+
+#[derive(RustEmbed)]
+#[folder = "static/"]
+struct Embedded;
+
+/// `--static-dir`, set once at startup
+static STATIC_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Where embedded integration scripts were extracted to
+static EXTRACTED_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Serve assets from `dir` instead of the embedded copies (for frontend development)
+pub fn init(dir: Option<PathBuf>) {
+    let dir = dir.map(|dir| dir.canonicalize().unwrap_or(dir));
+    if let Some(dir) = &dir {
+        tracing::info!("Serving static assets from {}", dir.display());
+    }
+    STATIC_DIR
+        .set(dir)
+        .expect("assets::init is called once at startup");
+}
+
+pub fn static_dir() -> Option<&'static Path> {
+    STATIC_DIR.get().and_then(|dir| dir.as_deref())
+}
+
+/// Read an asset from the static dir if one is set, else from the binary
+pub fn get(path: &str) -> Option<Vec<u8>> {
+    match static_dir() {
+        Some(dir) => std::fs::read(disk_path(dir, path)?).ok(),
+        None => Embedded::get(path).map(|file| file.data.into_owned()),
+    }
+}
+
+/// `path` under `dir`, if it stays there. Embedded lookups can't escape the folder, but a `..`
+/// or an absolute path (`/static//etc/passwd`) would take a disk lookup out of it.
+fn disk_path(dir: &Path, path: &str) -> Option<PathBuf> {
+    let plain = Path::new(path)
+        .components()
+        .all(|part| matches!(part, Component::Normal(_)));
+    plain.then(|| dir.join(path))
+}
+
+/// Directory holding the shell integration scripts.
+///
+/// Shells need them as real files (`--rcfile`, `source`), so the copies built into
+/// `shell-markers` are written out to a private temporary directory the first time they're
+/// needed.
+pub fn integration_dir() -> PathBuf {
+    EXTRACTED_DIR
+        .get_or_init(|| {
+            shell_markers::extract("remote-shell").expect("Failed to write integration scripts")
+        })
+        .clone()
+}
+
+pub async fn index_handler(State(state): State<AppState>) -> Response {
+    match get("index.html") {
+        Some(html) => (security::html_headers(&state.config.http), Html(html)).into_response(),
+        None => (StatusCode::NOT_FOUND, "index.html not found").into_response(),
+    }
+}
+
+pub async fn static_handler(UrlPath(path): UrlPath<String>) -> Response {
+    match get(&path) {
+        Some(data) => {
+            let mime = mime_guess::from_path(&path).first_or_octet_stream();
+            ([(header::CONTENT_TYPE, mime.as_ref().to_string())], data).into_response()
+        }
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_lookups_stay_in_the_static_dir() {
+        let dir = Path::new("/srv/static");
+        assert_eq!(disk_path(dir, "app.js"), Some(dir.join("app.js")));
+        assert_eq!(disk_path(dir, "css/site.css"), Some(dir.join("css/site.css")));
+
+        for path in ["/etc/passwd", "../secret", "css/../../secret"] {
+            assert_eq!(disk_path(dir, path), None, "{} was allowed", path);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Users, roles and request authentication
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{header, request::Parts, StatusCode},
+};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use crate::{registry::SessionEntry, AppState};
+
+/// Ordered from most to least privileged
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Can manage every session
+    Admin,
+    /// Can open and drive sessions on assigned hosts/profiles
+    Operator,
+    /// Can only observe existing sessions
+    Viewer,
+}
+
+/// A user from the `[[users]]` config section
+#[derive(Deserialize, Debug, Clone)]
+pub struct UserConfig {
+    pub name: String,
+    /// Bearer token identifying the user
+    pub token: String,
+    pub role: Role,
+    /// Hosts an operator may use (agent ids, or "local" for this machine). Empty means any.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    /// Profiles an operator may use ("default" for the default profile). Empty means any.
+    #[serde(default)]
+    pub profiles: Vec<String>,
+}
+
+/// Host id used for sessions running on the server itself
+pub const LOCAL_HOST: &str = "local";
+
+/// The identity behind a request
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub name: String,
+    pub role: Role,
+    hosts: Vec<String>,
+    profiles: Vec<String>,
+}
+
+impl AuthUser {
+    /// Used when no users are configured, which keeps the single-user behaviour
+    fn anonymous() -> Self {
+        Self {
+            name: "anonymous".to_string(),
+            role: Role::Admin,
+            hosts: Vec::new(),
+            profiles: Vec::new(),
+        }
+    }
+
+    pub fn new(name: String, role: Role, hosts: Vec<String>, profiles: Vec<String>) -> Self {
+        Self {
+            name,
+            role,
+            hosts,
+            profiles,
+        }
+    }
+
+    pub fn from_config(user: &UserConfig) -> Self {
+        Self::new(
+            user.name.clone(),
+            user.role,
+            user.hosts.clone(),
+            user.profiles.clone(),
+        )
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    /// Whether the user may start a new session on `host` with `profile`
+    pub fn can_open(&self, host: Option<&str>, profile: Option<&str>) -> bool {
+        match self.role {
+            Role::Admin => true,
+            Role::Viewer => false,
+            Role::Operator => {
+                let host = host.unwrap_or(LOCAL_HOST);
+                let profile = profile.unwrap_or("default");
+                (self.hosts.is_empty() || self.hosts.iter().any(|h| h == host))
+                    && (self.profiles.is_empty() || self.profiles.iter().any(|p| p == profile))
+            }
+        }
+    }
+
+    /// Whether the user may send input to an existing session (everyone else may only watch)
+    pub fn can_interact(&self, session: &SessionEntry) -> bool {
+        match self.role {
+            Role::Admin => true,
+            Role::Viewer => false,
+            Role::Operator => {
+                session.owner == self.name
+                    || self.can_open(session.host.as_deref(), session.profile.as_deref())
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenParam {
+    token: Option<String>,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        if state.config.users.is_empty() && state.jwt.is_none() {
+            return Ok(AuthUser::anonymous());
+        }
+
+        // Browsers can't set headers on a WebSocket upgrade, so also accept ?token=
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_string)
+            .or_else(|| {
+                Query::<TokenParam>::try_from_uri(&parts.uri)
+                    .ok()
+                    .and_then(|Query(param)| param.token)
+            })
+            .ok_or((StatusCode::UNAUTHORIZED, "Missing token"))?;
+
+        // Static tokens are opaque strings, anything shaped like header.payload.signature is a JWT
+        if let Some(jwt) = &state.jwt {
+            if token.split('.').count() == 3 {
+                return jwt.authenticate(&token).await.map_err(|e| {
+                    tracing::warn!("Rejected JWT: {}", e);
+                    (StatusCode::UNAUTHORIZED, "Invalid token")
+                });
+            }
+        }
+
+        state
+            .config
+            .users
+            .iter()
+            .find(|user| user.token.as_bytes().ct_eq(token.as_bytes()).into())
+            .map(AuthUser::from_config)
+            .ok_or((StatusCode::UNAUTHORIZED, "Invalid token"))
+    }
+}
@@ -0,0 +1,436 @@
+//! Session backends: where the shell behind a PTY actually runs
+
+use std::path::Path;
+
+use portable_pty::CommandBuilder;
+use serde::Deserialize;
+use shell_markers::Shell;
+
+/// A backend selected per session through a named profile (`/ws?profile=...`).
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum Backend {
+    /// A shell on the server itself (`$SHELL`, falling back to bash)
+    Local {
+        #[serde(default)]
+        shell: Option<String>,
+    },
+    /// A shell inside a tmux session on the server (`tmux new-session -A`). The tmux session
+    /// outlives the web session and can also be attached to over SSH.
+    Tmux {
+        #[serde(default)]
+        shell: Option<String>,
+        /// tmux session name, filled in by [`Backend::with_tmux_session`]
+        #[serde(skip)]
+        session: Option<String>,
+    },
+    /// `docker exec -it <container> <shell>` into a container running on the host
+    Docker {
+        container: String,
+        #[serde(default)]
+        shell: Option<String>,
+        /// Passed to `docker exec --user`
+        #[serde(default)]
+        user: Option<String>,
+        /// Passed to `docker exec --workdir`; the host-side cwd means nothing inside the container
+        #[serde(default)]
+        workdir: Option<String>,
+    },
+    /// `kubectl exec -it <pod> -- <shell>` into a pod picked from an allowlist
+    Kubernetes {
+        /// Namespaces sessions may exec into; the first one is used when the client doesn't pick one
+        namespaces: Vec<String>,
+        /// Pods sessions may exec into; a trailing `*` matches any suffix (e.g. `api-*`)
+        pods: Vec<String>,
+        #[serde(default)]
+        container: Option<String>,
+        /// kubeconfig context, defaults to kubectl's current context
+        #[serde(default)]
+        context: Option<String>,
+        #[serde(default)]
+        shell: Option<String>,
+        /// Pod chosen for this session, filled in by [`Backend::with_target`]
+        #[serde(skip)]
+        target: Option<PodTarget>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct PodTarget {
+    pub namespace: String,
+    pub pod: String,
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Backend::Local { shell: None }
+    }
+}
+
+impl Backend {
+    fn shell(&self) -> String {
+        match self {
+            Backend::Local { shell } | Backend::Tmux { shell, .. } => shell
+                .clone()
+                .unwrap_or_else(|| std::env::var("SHELL").unwrap_or_else(|_| "bash".to_string())),
+            Backend::Docker { shell, .. } | Backend::Kubernetes { shell, .. } => {
+                shell.clone().unwrap_or_else(|| "bash".to_string())
+            }
+        }
+    }
+
+    /// Bind the session-specific target (`/ws?namespace=...&pod=...`), checking it against the
+    /// profile's allowlist. Backends without a selectable target ignore it.
+    pub fn with_target(mut self, namespace: Option<&str>, pod: Option<&str>) -> Result<Self, String> {
+        if let Backend::Kubernetes {
+            namespaces,
+            pods,
+            target,
+            ..
+        } = &mut self
+        {
+            // Checked before the allowlist: a bare `*` would otherwise let through a pod named
+            // like a kubectl flag
+            if let Some(ns) = namespace.filter(|ns| !dns_name(ns, 63, false)) {
+                return Err(format!("Invalid namespace {}", ns));
+            }
+            if let Some(pod) = pod.filter(|pod| !dns_name(pod, 253, true)) {
+                return Err(format!("Invalid pod name {}", pod));
+            }
+
+            let namespace = match namespace {
+                Some(ns) if namespaces.iter().any(|allowed| allowed == ns) => ns.to_string(),
+                Some(ns) => return Err(format!("Namespace {} is not allowed", ns)),
+                None => namespaces
+                    .first()
+                    .cloned()
+                    .ok_or_else(|| "Profile has no namespaces configured".to_string())?,
+            };
+
+            let pod = pod.ok_or_else(|| "A pod must be chosen for this profile".to_string())?;
+            let allowed = pods.iter().any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => pod.starts_with(prefix),
+                None => pattern == pod,
+            });
+            if !allowed {
+                return Err(format!("Pod {} is not allowed", pod));
+            }
+
+            *target = Some(PodTarget {
+                namespace,
+                pod: pod.to_string(),
+            });
+        }
+
+        Ok(self)
+    }
+
+    /// Bind the tmux session name for tmux backends; other backends ignore it
+    pub fn with_tmux_session(mut self, name: &str) -> Result<Self, String> {
+        if let Backend::Tmux { session, .. } = &mut self {
+            // tmux reserves '.' and ':' for targets, keep names plain
+            let valid = !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return Err(format!("Invalid tmux session name {}", name));
+            }
+            *session = Some(name.to_string());
+        }
+
+        Ok(self)
+    }
+
+    /// The tmux session this backend attaches to, if it is a tmux backend
+    pub fn tmux_session(&self) -> Option<&str> {
+        match self {
+            Backend::Tmux { session, .. } => session.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The shell, if it's one with an integration script
+    pub fn shell_kind(&self) -> Option<Shell> {
+        Shell::detect(&self.shell())
+    }
+
+    /// Arguments loading the integration script when the shell starts, for shells that can
+    fn integration_args(&self, cmd: &mut CommandBuilder, script_dir: &Path) {
+        match self.shell_kind() {
+            Some(Shell::Bash) => {
+                cmd.arg("--rcfile");
+                cmd.arg(script_dir.join(Shell::Bash.file_name()));
+            }
+            Some(Shell::Fish) => {
+                cmd.arg("--init-command");
+                cmd.arg(format!("source {}", script_dir.join(Shell::Fish.file_name()).display()));
+            }
+            _ => {}
+        }
+    }
+
+    /// Build the command to spawn inside the PTY
+    pub fn command(&self, cwd: &Path, script_dir: &Path) -> CommandBuilder {
+        let shell = self.shell();
+
+        match self {
+            Backend::Local { .. } => {
+                let mut cmd = CommandBuilder::new(&shell);
+                self.integration_args(&mut cmd, script_dir);
+                cmd.cwd(cwd);
+                cmd.env("TERM", "xterm-256color");
+                cmd
+            }
+            Backend::Tmux { session, .. } => {
+                let session = session
+                    .as_deref()
+                    .expect("tmux session is bound by with_tmux_session before spawning");
+
+                let mut cmd = CommandBuilder::new("tmux");
+                // -A attaches when the session already exists; the shell and -c only apply to a
+                // new one
+                cmd.args(["new-session", "-A", "-s", session, "-c"]);
+                cmd.arg(cwd);
+                cmd.arg(&shell);
+                self.integration_args(&mut cmd, script_dir);
+                // tmux drops OSC sequences it doesn't know; the integration scripts wrap theirs in
+                // a passthrough sequence, which has to be allowed
+                cmd.args([";", "set-option", "-p", "allow-passthrough", "on"]);
+                cmd.cwd(cwd);
+                cmd.env("TERM", "xterm-256color");
+                // Otherwise tmux refuses to nest when the server itself runs inside tmux
+                cmd.env_remove("TMUX");
+                cmd
+            }
+            Backend::Docker {
+                container,
+                user,
+                workdir,
+                ..
+            } => {
+                let mut cmd = CommandBuilder::new("docker");
+                cmd.args(["exec", "-it", "-e", "TERM=xterm-256color"]);
+                if let Some(user) = user {
+                    cmd.arg("--user");
+                    cmd.arg(user);
+                }
+                if let Some(workdir) = workdir {
+                    cmd.arg("--workdir");
+                    cmd.arg(workdir);
+                }
+                cmd.arg(container);
+                cmd.arg(&shell);
+                cmd
+            }
+            Backend::Kubernetes {
+                container,
+                context,
+                target,
+                ..
+            } => {
+                let target = target
+                    .as_ref()
+                    .expect("Kubernetes target is bound by with_target before spawning");
+
+                let mut cmd = CommandBuilder::new("kubectl");
+                if let Some(context) = context {
+                    cmd.arg("--context");
+                    cmd.arg(context);
+                }
+                cmd.args(["exec", "-it", "-n", target.namespace.as_str(), target.pod.as_str()]);
+                if let Some(container) = container {
+                    cmd.arg("-c");
+                    cmd.arg(container);
+                }
+                // kubectl exec has no -e, so set TERM through env(1) inside the pod
+                cmd.args(["--", "env", "TERM=xterm-256color", shell.as_str()]);
+                cmd
+            }
+        }
+    }
+
+    /// Build a non-interactive `<shell> -c <command>` running where this backend's shells run,
+    /// for piped execution outside the PTY
+    pub fn exec_command(&self, cwd: &Path, command: &str) -> tokio::process::Command {
+        let shell = self.shell();
+
+        match self {
+            Backend::Local { .. } | Backend::Tmux { .. } => {
+                let mut cmd = tokio::process::Command::new(&shell);
+                cmd.arg("-c").arg(command).current_dir(cwd);
+                cmd
+            }
+            Backend::Docker {
+                container,
+                user,
+                workdir,
+                ..
+            } => {
+                // No -t: a TTY would merge stderr into stdout
+                let mut cmd = tokio::process::Command::new("docker");
+                cmd.arg("exec");
+                if let Some(user) = user {
+                    cmd.arg("--user").arg(user);
+                }
+                if let Some(workdir) = workdir {
+                    cmd.arg("--workdir").arg(workdir);
+                }
+                cmd.arg(container).arg(&shell).arg("-c").arg(command);
+                cmd
+            }
+            Backend::Kubernetes {
+                container,
+                context,
+                target,
+                ..
+            } => {
+                let target = target
+                    .as_ref()
+                    .expect("Kubernetes target is bound by with_target before spawning");
+
+                let mut cmd = tokio::process::Command::new("kubectl");
+                if let Some(context) = context {
+                    cmd.arg("--context").arg(context);
+                }
+                cmd.args(["exec", "-n", target.namespace.as_str(), target.pod.as_str()]);
+                if let Some(container) = container {
+                    cmd.arg("-c").arg(container);
+                }
+                cmd.arg("--").arg(&shell).arg("-c").arg(command);
+                cmd
+            }
+        }
+    }
+
+    /// Input to type into a freshly spawned shell to load the shell integration, if the
+    /// command line couldn't do it.
+    pub fn init_input(&self, script_dir: &Path) -> Option<String> {
+        let shell = self.shell_kind()?;
+
+        match self {
+            // Bash and fish got it on the command line; zsh has no equivalent, so source the
+            // script by path
+            Backend::Local { .. } => match shell {
+                Shell::Zsh => Some(format!(
+                    "source {}\n",
+                    script_dir.join(shell.file_name()).display()
+                )),
+                _ => None,
+            },
+            // Typing would land in whatever is running in an existing tmux session, so only
+            // shells that take it on the command line get the integration there
+            Backend::Tmux { .. } => None,
+            // The script isn't on the container's filesystem, so feed its contents through stdin
+            _ => match shell {
+                Shell::Bash | Shell::Zsh => Some(format!(
+                    "source /dev/stdin <<'__RS_INTEGRATION__'\n{}\n__RS_INTEGRATION__\n",
+                    shell.script().trim_end()
+                )),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Whether `name` is a DNS-1123 name as Kubernetes requires: lowercase alphanumerics and `-`,
+/// dot separated if `dots`, starting and ending alphanumeric
+fn dns_name(name: &str, max_len: usize, dots: bool) -> bool {
+    let alphanumeric = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+    let part = |part: &str| {
+        part.starts_with(alphanumeric)
+            && part.ends_with(alphanumeric)
+            && part.chars().all(|c| alphanumeric(c) || c == '-')
+    };
+    let valid = if dots {
+        name.split('.').all(part)
+    } else {
+        part(name)
+    };
+    valid && name.len() <= max_len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tmux() -> Backend {
+        Backend::Tmux {
+            shell: None,
+            session: None,
+        }
+    }
+
+    #[test]
+    fn plain_tmux_names_are_accepted() {
+        for name in ["remote-shell", "web-alice", "build_2", "A"] {
+            let backend = tmux().with_tmux_session(name).unwrap();
+            assert_eq!(backend.tmux_session(), Some(name));
+        }
+    }
+
+    #[test]
+    fn tmux_target_syntax_is_rejected() {
+        for name in ["", "a.b", "a:1", "two words", "%1", "résumé"] {
+            assert!(tmux().with_tmux_session(name).is_err(), "{:?} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn other_backends_ignore_the_name() {
+        let backend = Backend::Local { shell: None }.with_tmux_session("a:b").unwrap();
+        assert_eq!(backend.tmux_session(), None);
+    }
+
+    fn kubernetes(pods: &[&str]) -> Backend {
+        Backend::Kubernetes {
+            namespaces: vec!["prod".to_string(), "staging".to_string()],
+            pods: pods.iter().map(|pod| pod.to_string()).collect(),
+            container: None,
+            context: None,
+            shell: None,
+            target: None,
+        }
+    }
+
+    fn target(backend: &Backend) -> (&str, &str) {
+        match backend {
+            Backend::Kubernetes {
+                target: Some(target),
+                ..
+            } => (&target.namespace, &target.pod),
+            _ => panic!("no target bound"),
+        }
+    }
+
+    #[test]
+    fn allowed_pods_are_bound() {
+        let backend = kubernetes(&["api-*"]).with_target(None, Some("api-7d9f")).unwrap();
+        assert_eq!(target(&backend), ("prod", "api-7d9f"));
+        let backend = kubernetes(&["*"]).with_target(Some("staging"), Some("db-0.db")).unwrap();
+        assert_eq!(target(&backend), ("staging", "db-0.db"));
+    }
+
+    #[test]
+    fn pods_outside_the_allowlist_are_rejected() {
+        let backend = kubernetes(&["api-*", "worker"]);
+        assert!(backend.clone().with_target(None, Some("db-0")).is_err());
+        assert!(backend.clone().with_target(None, Some("worker-2")).is_err());
+        assert!(backend.clone().with_target(Some("kube-system"), Some("api-1")).is_err());
+        assert!(backend.with_target(None, None).is_err());
+    }
+
+    #[test]
+    fn names_that_are_not_dns_names_are_rejected() {
+        let backend = kubernetes(&["*"]);
+        for pod in ["--kubeconfig=/tmp/x", "-it", "API-1", "api_1", "api-", ".api", "a..b", ""] {
+            let err = backend.clone().with_target(None, Some(pod)).unwrap_err();
+            assert!(err.starts_with("Invalid pod name"), "{:?}: {}", pod, err);
+        }
+        assert!(backend.clone().with_target(None, Some(&"a".repeat(254))).is_err());
+        for ns in ["-n", "prod.x", "Prod", ""] {
+            let err = backend.clone().with_target(Some(ns), Some("api")).unwrap_err();
+            assert!(err.starts_with("Invalid namespace"), "{:?}: {}", ns, err);
+        }
+    }
+}
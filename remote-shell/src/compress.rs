@@ -0,0 +1,36 @@
+//! Compressed terminal output for clients that ask for it with the [`PROTOCOL`] WebSocket
+//! subprotocol. A connection's output frames are one zlib stream, each binary message a piece
+//! of it flushed so it inflates to exactly that frame's output.
+
+use flate2::{Compress, Compression, FlushCompress};
+
+/// Subprotocol a client offers when it can inflate output frames
+pub const PROTOCOL: &str = "remote-shell.deflate";
+
+/// One connection's output stream
+pub struct Deflate(Compress);
+
+impl Deflate {
+    pub fn new() -> Self {
+        // Terminal output compresses well even at the fastest level
+        Deflate(Compress::new(Compression::fast(), true))
+    }
+
+    /// `data` compressed, continuing the stream from the frames before it
+    pub fn frame(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() / 2 + 64);
+        let mut input = data;
+        loop {
+            out.reserve(input.len().max(64));
+            let before = self.0.total_in();
+            self.0
+                .compress_vec(input, &mut out, FlushCompress::Sync)
+                .expect("the stream is never finished, so compressing can't fail");
+            input = &input[(self.0.total_in() - before) as usize..];
+            // Done when everything went in and the flush didn't run out of room
+            if input.is_empty() && out.len() < out.capacity() {
+                return out;
+            }
+        }
+    }
+}
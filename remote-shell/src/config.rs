@@ -0,0 +1,194 @@
+//! Server configuration
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use workspace_config::{BannerConfig, Overrides, Section, ServerConfig};
+
+use crate::{auth::UserConfig, backend::Backend, jwt::JwtConfig, security::HttpConfig};
+
+/// Config file of remote-shell's own, looked up in the working directory when
+/// `REMOTE_SHELL_CONFIG` is not set. It holds just the `[server]` section's keys.
+const DEFAULT_CONFIG_FILE: &str = "remote-shell.toml";
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Config {
+    /// Listen address and other process settings shared with the command line
+    #[serde(flatten)]
+    pub server: ServerConfig,
+
+    /// Directories a session is allowed to start in (`/ws?cwd=...`).
+    /// When empty, only the server's own working directory (and below) is allowed.
+    pub allowed_roots: Vec<PathBuf>,
+
+    /// Named session backends, selected with `/ws?profile=<name>`
+    pub profiles: HashMap<String, Backend>,
+
+    /// Profile used when the client doesn't ask for one (a local shell if unset)
+    pub default_profile: Option<String>,
+
+    /// Agents allowed to register with this hub, by id
+    pub hosts: HashMap<String, HostConfig>,
+
+    /// Accept agents that aren't listed in `hosts` (no token check, no labels)
+    pub allow_unregistered_agents: bool,
+
+    /// Accounts allowed to use the server. When empty, authentication is off and every
+    /// request acts as an admin.
+    pub users: Vec<UserConfig>,
+
+    /// Accept bearer JWTs from an OIDC provider, in addition to `users`
+    pub jwt: Option<JwtConfig>,
+
+    /// Hosts ("local" for this server) where `Run` commands from non-admins wait for an admin's
+    /// approval. Typing into the terminal directly is disabled for them there.
+    pub require_approval: Vec<String>,
+
+    /// Caps on the cleaned command output relayed as logs
+    pub log_limits: LogLimits,
+
+    /// Record every session to `<dir>/<created>-<id>.jsonl` in the shared session event format
+    pub record_dir: Option<PathBuf>,
+
+    /// Directory of pty-bash-hook recordings served under `/logs`
+    pub logs_dir: Option<PathBuf>,
+
+    /// What to do with OSC 52 clipboard writes from programs in the session
+    pub clipboard: ClipboardPolicy,
+
+    /// Cross-origin and security header policy
+    pub http: HttpConfig,
+
+    /// Banner shown in a new session's terminal before the first prompt, with the options of
+    /// text-ui's `[banner]`. Off unless set.
+    pub motd: Option<BannerConfig>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct HostConfig {
+    /// Shared secret the agent must present when registering. Without one the host is treated
+    /// like an unlisted agent, accepted only with `allow_unregistered_agents`.
+    pub token: Option<String>,
+    /// Labels shown in `GET /api/hosts`, merged over the agent's own labels
+    pub labels: HashMap<String, String>,
+}
+
+/// The `[log_limits]` config section, in bytes. Output past a limit is still shown in the
+/// terminal, it just isn't logged.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct LogLimits {
+    pub per_command: u64,
+    pub per_session: u64,
+}
+
+impl Default for LogLimits {
+    fn default() -> Self {
+        Self {
+            per_command: 1024 * 1024,
+            per_session: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Handling of OSC 52 (set clipboard) sequences
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardPolicy {
+    /// Leave them in the terminal stream for the terminal emulator to deal with
+    #[default]
+    Passthrough,
+    /// Also send the decoded text as a `Clipboard` message the frontend can honor
+    Forward,
+    /// Strip them from the terminal stream
+    Block,
+}
+
+impl Section for Config {
+    const NAME: &'static str = ServerConfig::NAME;
+    const ENV_PREFIX: &'static str = ServerConfig::ENV_PREFIX;
+
+    fn own_file() -> Option<PathBuf> {
+        match std::env::var_os("REMOTE_SHELL_CONFIG") {
+            Some(path) => Some(path.into()),
+            None => Path::new(DEFAULT_CONFIG_FILE)
+                .exists()
+                .then(|| PathBuf::from(DEFAULT_CONFIG_FILE)),
+        }
+    }
+}
+
+impl Config {
+    /// Load the `[server]` section: defaults, then `path` (or `$WORKSPACE_CONFIG`,
+    /// `$REMOTE_SHELL_CONFIG`, `remote-shell.toml` or `workspace.toml`), then `REMOTE_SHELL_*`
+    /// variables (`REMOTE_SHELL_ALLOWED_ROOTS=/srv/projects:/home`), then `overrides`.
+    pub fn load(path: Option<&Path>, overrides: Overrides) -> Self {
+        workspace_config::load(path, overrides)
+            .unwrap_or_else(|e| panic!("Failed to load config: {}", e))
+    }
+
+    /// Resolve the working directory requested by a client against the allowed roots.
+    ///
+    /// Returns the canonical directory the session should start in, or a message
+    /// suitable for sending back to the client.
+    pub fn resolve_cwd(&self, requested: Option<&str>) -> Result<PathBuf, String> {
+        let server_cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+
+        let roots: Vec<PathBuf> = if self.allowed_roots.is_empty() {
+            vec![server_cwd.clone()]
+        } else {
+            // Roots that don't exist are skipped rather than failing every request
+            self.allowed_roots
+                .iter()
+                .filter_map(|root| root.canonicalize().ok())
+                .collect()
+        };
+
+        let requested = match requested {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            // No explicit cwd: keep the old behaviour when it's allowed, else use the first root
+            _ => {
+                let cwd = server_cwd.canonicalize().unwrap_or(server_cwd);
+                if roots.iter().any(|root| cwd.starts_with(root)) {
+                    return Ok(cwd);
+                }
+                return roots
+                    .first()
+                    .cloned()
+                    .ok_or_else(|| "No usable allowed root configured".to_string());
+            }
+        };
+
+        // Canonicalize so `..` and symlinks can't escape the allowed roots
+        let resolved = requested
+            .canonicalize()
+            .map_err(|e| format!("Invalid cwd {}: {}", requested.display(), e))?;
+
+        if !resolved.is_dir() {
+            return Err(format!("cwd {} is not a directory", resolved.display()));
+        }
+
+        if roots.iter().any(|root| resolved.starts_with(root)) {
+            Ok(resolved)
+        } else {
+            Err(format!("cwd {} is outside the allowed roots", resolved.display()))
+        }
+    }
+
+    /// Look up the backend for a session's profile
+    pub fn profile(&self, name: Option<&str>) -> Result<Backend, String> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("Unknown profile {}", name)),
+            None => Ok(Backend::default()),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! `remote-shell gen-types`: TypeScript definitions and JSON Schemas for the WebSocket protocol,
+//! derived from the Rust enums so the frontend can't drift from them
+
+use std::{fs, io, path::Path};
+
+use ts_rs::TS;
+
+use crate::{ClientMsg, ExecStream, ServerLogMsg};
+
+pub fn write(out: &Path) -> io::Result<()> {
+    fs::create_dir_all(out)?;
+
+    let header = "// Generated by `remote-shell gen-types`, do not edit.\n";
+    let typescript = format!(
+        "{}\n/** Sent by the browser */\nexport {}\n\n/** Sent by the server as JSON text frames */\nexport {}\n\nexport {}\n",
+        header,
+        ClientMsg::decl(),
+        ServerLogMsg::decl(),
+        ExecStream::decl()
+    );
+    fs::write(out.join("protocol.d.ts"), typescript)?;
+
+    let schemas = [
+        ("client-msg.schema.json", schemars::schema_for!(ClientMsg)),
+        ("server-log-msg.schema.json", schemars::schema_for!(ServerLogMsg)),
+    ];
+    for (name, schema) in schemas {
+        let json = serde_json::to_string_pretty(&schema).map_err(io::Error::other)?;
+        fs::write(out.join(name), json + "\n")?;
+    }
+
+    tracing::info!("Wrote protocol types to {}", out.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The `type` tag serde puts on the wire
+    fn tag<T: serde::Serialize>(msg: &T) -> String {
+        serde_json::to_value(msg).unwrap()["type"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn typescript_tags_match_the_wire_format() {
+        let decl = ServerLogMsg::decl();
+        let messages = [
+            ServerLogMsg::LogStart {
+                user: String::new(),
+                host: String::new(),
+                cwd: String::new(),
+            },
+            ServerLogMsg::LogOutput {
+                data: String::new(),
+            },
+            ServerLogMsg::LogEnd {
+                exit_code: 0,
+                wall_ms: None,
+                cpu_ms: None,
+                max_rss_kb: None,
+            },
+            ServerLogMsg::Truncated { dropped_bytes: 0 },
+            ServerLogMsg::ExecOutput {
+                id: String::new(),
+                stream: ExecStream::Stdout,
+                data: String::new(),
+            },
+            ServerLogMsg::ExecEnd {
+                id: String::new(),
+                exit_code: 0,
+            },
+            ServerLogMsg::Clipboard {
+                data: String::new(),
+            },
+            ServerLogMsg::Title {
+                text: String::new(),
+            },
+        ];
+        for msg in &messages {
+            let tag = tag(msg);
+            assert!(decl.contains(&format!("\"{}\"", tag)), "{} missing from {}", tag, decl);
+        }
+
+        let run = ClientMsg::Run {
+            data: String::new(),
+            id: String::new(),
+            piped: None,
+        };
+        assert!(ClientMsg::decl().contains(&format!("\"{}\"", tag(&run))));
+    }
+
+    #[test]
+    fn piped_is_optional() {
+        assert!(ClientMsg::decl().contains("piped?: boolean"));
+    }
+}
@@ -0,0 +1,80 @@
+//! Liveness, readiness and build info for load balancers and orchestrators
+
+use axum::{extract::State, http::StatusCode, Json};
+use portable_pty::{NativePtySystem, PtySize, PtySystem};
+use serde::Serialize;
+
+use crate::{assets, auth::AuthUser, AppState};
+
+/// The process is up and serving requests
+pub async fn healthz_handler() -> &'static str {
+    "ok"
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Readiness {
+    ready: bool,
+    /// A PTY could be allocated
+    pty: bool,
+    /// The UI can be served
+    assets: bool,
+}
+
+/// The server can actually host sessions: PTYs can be opened and the UI is present
+pub async fn readyz_handler() -> (StatusCode, Json<Readiness>) {
+    let pty = tokio::task::spawn_blocking(|| {
+        NativePtySystem::default()
+            .openpty(PtySize {
+                rows: 24,
+                cols: 80,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| tracing::warn!("Readiness check could not open a PTY: {}", e))
+            .is_ok()
+    })
+    .await
+    .unwrap_or(false);
+    let assets = assets::get("index.html").is_some();
+
+    let ready = pty && assets;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(Readiness { ready, pty, assets }))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionInfo {
+    version: &'static str,
+    git_hash: &'static str,
+    /// Optional features switched on in this instance's config
+    features: Vec<&'static str>,
+}
+
+pub async fn version_handler(_user: AuthUser, State(state): State<AppState>) -> Json<VersionInfo> {
+    let config = &state.config;
+    let enabled = [
+        ("users", !config.users.is_empty()),
+        ("jwt", config.jwt.is_some()),
+        ("agents", !config.hosts.is_empty() || config.allow_unregistered_agents),
+        ("approval", !config.require_approval.is_empty()),
+        ("cors", !config.http.allowed_origins.is_empty()),
+        ("static-dir", assets::static_dir().is_some()),
+    ];
+
+    Json(VersionInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_hash: env!("REMOTE_SHELL_GIT_HASH"),
+        features: enabled
+            .into_iter()
+            .filter(|(_, on)| *on)
+            .map(|(name, _)| name)
+            .collect(),
+    })
+}
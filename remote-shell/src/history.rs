@@ -0,0 +1,134 @@
+//! Per-session history of executed commands and their cleaned output, built from the log stream
+
+use std::{
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::ServerLogMsg;
+
+/// What is known about a command without its output
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandInfo {
+    /// Position in the session, starting at 1
+    pub seq: u64,
+    pub user: String,
+    pub host: String,
+    pub cwd: String,
+    /// Unix timestamps in seconds
+    pub started_at: u64,
+    pub ended_at: Option<u64>,
+    pub exit_code: Option<i32>,
+    pub wall_ms: Option<u64>,
+    pub cpu_ms: Option<u64>,
+    pub max_rss_kb: Option<u64>,
+    /// Output left out because of the log limits
+    pub dropped_bytes: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandRecord {
+    #[serde(flatten)]
+    pub info: CommandInfo,
+    pub output: String,
+}
+
+/// Commands seen in one session. Output is bounded by the session's log limits.
+#[derive(Default)]
+pub struct History {
+    commands: Mutex<Vec<CommandRecord>>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl History {
+    pub fn observe(&self, msg: &ServerLogMsg) {
+        let mut commands = self.commands.lock().unwrap();
+        match msg {
+            ServerLogMsg::LogStart { user, host, cwd } => {
+                let seq = commands.len() as u64 + 1;
+                commands.push(CommandRecord {
+                    info: CommandInfo {
+                        seq,
+                        user: user.clone(),
+                        host: host.clone(),
+                        cwd: cwd.clone(),
+                        started_at: now(),
+                        ended_at: None,
+                        exit_code: None,
+                        wall_ms: None,
+                        cpu_ms: None,
+                        max_rss_kb: None,
+                        dropped_bytes: 0,
+                    },
+                    output: String::new(),
+                });
+            }
+            ServerLogMsg::LogOutput { data } => {
+                if let Some(current) = commands.last_mut().filter(|c| c.info.ended_at.is_none()) {
+                    current.output.push_str(data);
+                }
+            }
+            ServerLogMsg::Truncated { dropped_bytes } => {
+                if let Some(current) = commands.last_mut().filter(|c| c.info.ended_at.is_none()) {
+                    current.info.dropped_bytes += dropped_bytes;
+                }
+            }
+            ServerLogMsg::LogEnd {
+                exit_code,
+                wall_ms,
+                cpu_ms,
+                max_rss_kb,
+            } => {
+                if let Some(current) = commands.last_mut().filter(|c| c.info.ended_at.is_none()) {
+                    current.info.ended_at = Some(now());
+                    current.info.exit_code = Some(*exit_code);
+                    current.info.wall_ms = *wall_ms;
+                    current.info.cpu_ms = *cpu_ms;
+                    current.info.max_rss_kb = *max_rss_kb;
+                }
+            }
+            // Piped runs happen outside the shell's command stream
+            ServerLogMsg::ExecOutput { .. } | ServerLogMsg::ExecEnd { .. } => {}
+            ServerLogMsg::Clipboard { .. } | ServerLogMsg::Title { .. } => {}
+        }
+    }
+
+    pub fn list(&self) -> Vec<CommandInfo> {
+        self.commands
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|c| c.info.clone())
+            .collect()
+    }
+
+    /// `seq` of the commands whose directory or output contains `needle`, which must be in
+    /// lower case
+    pub fn search(&self, needle: &str) -> Vec<u64> {
+        self.commands
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|c| {
+                c.info.cwd.to_lowercase().contains(needle) || c.output.to_lowercase().contains(needle)
+            })
+            .map(|c| c.info.seq)
+            .collect()
+    }
+
+    /// A command by its `seq`, with the output captured so far if it's still running
+    pub fn get(&self, seq: u64) -> Option<CommandRecord> {
+        let index = usize::try_from(seq.checked_sub(1)?).ok()?;
+        self.commands.lock().unwrap().get(index).cloned()
+    }
+}
@@ -0,0 +1,325 @@
+//! Bearer JWT validation against an OIDC provider's JWKS
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm},
+    Algorithm, DecodingKey, Validation,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::auth::{AuthUser, Role};
+
+/// How long fetched signing keys are trusted before being refreshed
+const JWKS_TTL: Duration = Duration::from_secs(600);
+
+/// Least time between JWKS fetches, so tokens with made-up key ids can't have every request
+/// call the provider
+const JWKS_MIN_REFETCH: Duration = Duration::from_secs(30);
+
+/// Bounds on a JWKS fetch. Requests needing keys wait for it, so a hung provider must not hold
+/// them forever.
+const JWKS_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const JWKS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The `[jwt]` config section
+#[derive(Deserialize, Debug, Clone)]
+pub struct JwtConfig {
+    /// Expected `iss` claim
+    pub issuer: String,
+    /// Expected `aud` claim
+    pub audience: String,
+    /// Where the provider publishes its signing keys
+    pub jwks_url: String,
+    /// Claim holding the user name
+    #[serde(default = "default_username_claim")]
+    pub username_claim: String,
+    /// Claim holding the user's groups/roles, as a string or a list of strings
+    #[serde(default = "default_roles_claim")]
+    pub roles_claim: String,
+    /// Maps values of `roles_claim` to roles; the most privileged match wins
+    #[serde(default)]
+    pub roles: HashMap<String, Role>,
+    /// Role for valid tokens that match nothing in `roles`. Such tokens are rejected if unset.
+    #[serde(default)]
+    pub default_role: Option<Role>,
+    /// Claim listing the hosts an operator may use (any if absent)
+    #[serde(default)]
+    pub hosts_claim: Option<String>,
+    /// Claim listing the profiles an operator may use (any if absent)
+    #[serde(default)]
+    pub profiles_claim: Option<String>,
+}
+
+fn default_username_claim() -> String {
+    "sub".to_string()
+}
+
+fn default_roles_claim() -> String {
+    "groups".to_string()
+}
+
+pub struct JwtValidator {
+    config: JwtConfig,
+    http: reqwest::Client,
+    keys: Mutex<KeyCache>,
+}
+
+#[derive(Default)]
+struct KeyCache {
+    keys: Option<JwkSet>,
+    /// When `keys` were fetched
+    fetched: Option<Instant>,
+    /// Last fetch, whether it worked or not
+    attempted: Option<Instant>,
+}
+
+impl JwtValidator {
+    pub fn new(config: JwtConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::builder()
+                .connect_timeout(JWKS_CONNECT_TIMEOUT)
+                .timeout(JWKS_TIMEOUT)
+                .build()
+                .expect("HTTP client builds"),
+            keys: Mutex::default(),
+        }
+    }
+
+    /// Validate a token and map its claims to a user
+    pub async fn authenticate(&self, token: &str) -> Result<AuthUser, String> {
+        let header = decode_header(token).map_err(|e| e.to_string())?;
+        let kid = header.kid.ok_or("Token has no kid")?;
+
+        let (key, algorithm) = self.key(&kid).await?;
+
+        // The header is the token's to choose, so the key decides how it must be signed
+        if header.alg != algorithm {
+            return Err(format!(
+                "Token is signed with {:?}, key {} is for {:?}",
+                header.alg, kid, algorithm
+            ));
+        }
+
+        let mut validation = Validation::new(algorithm);
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.audience]);
+
+        let claims = decode::<HashMap<String, Value>>(token, &key, &validation)
+            .map_err(|e| e.to_string())?
+            .claims;
+
+        let name = claims
+            .get(&self.config.username_claim)
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("Token has no {} claim", self.config.username_claim))?
+            .to_string();
+
+        let role = string_list(claims.get(&self.config.roles_claim))
+            .iter()
+            .filter_map(|value| self.config.roles.get(value).copied())
+            .min()
+            .or(self.config.default_role)
+            .ok_or_else(|| format!("No role mapped for {}", name))?;
+
+        let claim_list = |claim: &Option<String>| {
+            claim
+                .as_ref()
+                .map(|claim| string_list(claims.get(claim)))
+                .unwrap_or_default()
+        };
+
+        Ok(AuthUser::new(
+            name,
+            role,
+            claim_list(&self.config.hosts_claim),
+            claim_list(&self.config.profiles_claim),
+        ))
+    }
+
+    /// Find the signing key for `kid` and the algorithm it's for, refetching the JWKS when it's
+    /// stale or the key is unknown (the provider may have rotated keys), at most once per
+    /// [`JWKS_MIN_REFETCH`]
+    async fn key(&self, kid: &str) -> Result<(DecodingKey, Algorithm), String> {
+        // Held across the fetch so concurrent requests wait for it instead of fetching too
+        let mut cache = self.keys.lock().await;
+
+        let current = |cache: &KeyCache| cache.fetched.is_some_and(|at| at.elapsed() < JWKS_TTL);
+        let known = cache
+            .keys
+            .as_ref()
+            .is_some_and(|keys| keys.find(kid).is_some());
+        let may_fetch = cache
+            .attempted
+            .is_none_or(|at| at.elapsed() >= JWKS_MIN_REFETCH);
+        if (!current(&cache) || !known) && may_fetch {
+            cache.attempted = Some(Instant::now());
+            cache.keys = Some(self.fetch().await?);
+            cache.fetched = Some(Instant::now());
+        }
+        if !current(&cache) {
+            return Err("Signing keys are out of date".to_string());
+        }
+
+        let jwk = cache
+            .keys
+            .as_ref()
+            .and_then(|keys| keys.find(kid))
+            .ok_or_else(|| format!("Unknown signing key {}", kid))?;
+        let algorithm =
+            algorithm(jwk).ok_or_else(|| format!("Signing key {} has no usable algorithm", kid))?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|e| e.to_string())?;
+        Ok((key, algorithm))
+    }
+
+    async fn fetch(&self) -> Result<JwkSet, String> {
+        self.http
+            .get(&self.config.jwks_url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Failed to fetch JWKS: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid JWKS: {}", e))
+    }
+}
+
+/// The signature algorithm a key is for: its `alg`, else the usual one for its key type.
+/// Symmetric keys have no business in a provider's public JWKS and get none.
+fn algorithm(jwk: &Jwk) -> Option<Algorithm> {
+    if let Some(alg) = &jwk.common.key_algorithm {
+        return match alg {
+            KeyAlgorithm::RS256 => Some(Algorithm::RS256),
+            KeyAlgorithm::RS384 => Some(Algorithm::RS384),
+            KeyAlgorithm::RS512 => Some(Algorithm::RS512),
+            KeyAlgorithm::PS256 => Some(Algorithm::PS256),
+            KeyAlgorithm::PS384 => Some(Algorithm::PS384),
+            KeyAlgorithm::PS512 => Some(Algorithm::PS512),
+            KeyAlgorithm::ES256 => Some(Algorithm::ES256),
+            KeyAlgorithm::ES384 => Some(Algorithm::ES384),
+            KeyAlgorithm::EdDSA => Some(Algorithm::EdDSA),
+            // HMAC, and key encryption algorithms
+            _ => None,
+        };
+    }
+
+    match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => Some(Algorithm::RS256),
+        AlgorithmParameters::EllipticCurve(ec) => match ec.curve {
+            EllipticCurve::P256 => Some(Algorithm::ES256),
+            EllipticCurve::P384 => Some(Algorithm::ES384),
+            _ => None,
+        },
+        AlgorithmParameters::OctetKeyPair(okp) => {
+            (okp.curve == EllipticCurve::Ed25519).then_some(Algorithm::EdDSA)
+        }
+        AlgorithmParameters::OctetKey(_) => None,
+    }
+}
+
+/// A claim that may be a single string or a list of strings
+fn string_list(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::String(s)) => vec![s.clone()],
+        Some(Value::Array(values)) => values
+            .iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    fn jwk(json: Value) -> Jwk {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn rsa_key(kid: &str) -> Value {
+        serde_json::json!({ "kty": "RSA", "kid": kid, "n": "AQAB", "e": "AQAB" })
+    }
+
+    /// A validator that already has `keys`, so nothing is fetched
+    fn validator(keys: Value) -> JwtValidator {
+        let validator = JwtValidator::new(JwtConfig {
+            issuer: "https://idp.example".to_string(),
+            audience: "remote-shell".to_string(),
+            jwks_url: "http://127.0.0.1:9/jwks".to_string(),
+            username_claim: default_username_claim(),
+            roles_claim: default_roles_claim(),
+            roles: HashMap::new(),
+            default_role: None,
+            hosts_claim: None,
+            profiles_claim: None,
+        });
+        let now = Instant::now();
+        *validator.keys.try_lock().unwrap() = KeyCache {
+            keys: Some(serde_json::from_value(keys).unwrap()),
+            fetched: Some(now),
+            attempted: Some(now),
+        };
+        validator
+    }
+
+    #[test]
+    fn algorithm_comes_from_the_key() {
+        assert_eq!(algorithm(&jwk(rsa_key("k"))), Some(Algorithm::RS256));
+
+        let mut pss = rsa_key("k");
+        pss["alg"] = "PS256".into();
+        assert_eq!(algorithm(&jwk(pss)), Some(Algorithm::PS256));
+
+        let ec = serde_json::json!({ "kty": "EC", "crv": "P-384", "x": "AQAB", "y": "AQAB" });
+        assert_eq!(algorithm(&jwk(ec)), Some(Algorithm::ES384));
+    }
+
+    #[test]
+    fn symmetric_keys_are_not_used() {
+        let oct = serde_json::json!({ "kty": "oct", "k": "c2VjcmV0" });
+        assert_eq!(algorithm(&jwk(oct)), None);
+
+        let mut hs = rsa_key("k");
+        hs["alg"] = "HS256".into();
+        assert_eq!(algorithm(&jwk(hs)), None);
+    }
+
+    #[tokio::test]
+    async fn tokens_signed_with_another_algorithm_are_rejected() {
+        let validator = validator(serde_json::json!({ "keys": [rsa_key("k1")] }));
+
+        // HS256 keyed with the public key's bytes, the classic algorithm confusion attack
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("k1".to_string());
+        let claims = serde_json::json!({
+            "sub": "mallory",
+            "iss": "https://idp.example",
+            "aud": "remote-shell",
+            "exp": u32::MAX,
+        });
+        let token = encode(&header, &claims, &EncodingKey::from_secret(b"AQAB")).unwrap();
+
+        let error = validator.authenticate(&token).await.unwrap_err();
+        assert!(error.contains("is for RS256"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn unknown_key_ids_do_not_refetch_within_the_limit() {
+        let validator = validator(serde_json::json!({ "keys": [rsa_key("k1")] }));
+
+        // The JWKS URL is unreachable, so a fetch would fail with a different error
+        let error = validator.key("made-up").await.err().unwrap();
+        assert_eq!(error, "Unknown signing key made-up");
+    }
+}
@@ -0,0 +1,131 @@
+//! Listening sockets: TCP, Unix domain sockets, and sockets inherited from systemd
+
+use std::io;
+
+use axum::Router;
+
+pub enum Listener {
+    Tcp(tokio::net::TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+/// Use a socket passed by systemd socket activation if there is one, else bind `spec`
+/// (`host:port` or `unix:/path/to.sock`).
+pub async fn open(spec: &str) -> io::Result<Listener> {
+    #[cfg(unix)]
+    if let Some(listener) = from_systemd()? {
+        return Ok(listener);
+    }
+
+    match spec.strip_prefix("unix:") {
+        #[cfg(unix)]
+        Some(path) => {
+            // A socket file left over from a previous run would make bind fail. Anything else
+            // at the path isn't ours to delete.
+            if let Ok(metadata) = std::fs::symlink_metadata(path) {
+                use std::os::unix::fs::FileTypeExt;
+
+                if !metadata.file_type().is_socket() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} exists and is not a socket", path),
+                    ));
+                }
+                std::fs::remove_file(path)?;
+            }
+            tracing::info!("Listening on unix:{}", path);
+            Ok(Listener::Unix(tokio::net::UnixListener::bind(path)?))
+        }
+        #[cfg(not(unix))]
+        Some(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Unix sockets are not supported on this platform",
+        )),
+        None => {
+            tracing::info!("Listening on http://{}", spec);
+            Ok(Listener::Tcp(tokio::net::TcpListener::bind(spec).await?))
+        }
+    }
+}
+
+/// Take over the first socket from `$LISTEN_FDS` (see sd_listen_fds(3))
+#[cfg(unix)]
+fn from_systemd() -> io::Result<Option<Listener>> {
+    use std::os::fd::FromRawFd;
+
+    /// systemd passes sockets starting at this fd
+    const SD_LISTEN_FDS_START: i32 = 3;
+
+    let for_us = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok())
+        .is_some_and(|pid| pid == std::process::id());
+    let count = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse::<i32>().ok())
+        .unwrap_or(0);
+    if !for_us || count < 1 {
+        return Ok(None);
+    }
+
+    // Children (our shells) must not think the sockets are theirs
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    // SAFETY: systemd hands us ownership of the fds from SD_LISTEN_FDS_START on
+    let socket = unsafe { socket2::Socket::from_raw_fd(SD_LISTEN_FDS_START) };
+    socket.set_nonblocking(true)?;
+
+    let listener = if socket.local_addr()?.as_socket().is_some() {
+        let listener = std::net::TcpListener::from(socket);
+        tracing::info!("Listening on inherited socket {}", listener.local_addr()?);
+        Listener::Tcp(tokio::net::TcpListener::from_std(listener)?)
+    } else {
+        tracing::info!("Listening on inherited unix socket");
+        Listener::Unix(tokio::net::UnixListener::from_std(socket.into())?)
+    };
+
+    Ok(Some(listener))
+}
+
+pub async fn serve(listener: Listener, app: Router) -> io::Result<()> {
+    match listener {
+        Listener::Tcp(listener) => axum::serve(listener, app).await,
+        #[cfg(unix)]
+        Listener::Unix(listener) => serve_unix(listener, app).await,
+    }
+}
+
+/// `axum::serve` only takes TCP listeners, so drive hyper directly
+#[cfg(unix)]
+async fn serve_unix(listener: tokio::net::UnixListener, app: Router) -> io::Result<()> {
+    use hyper::body::Incoming;
+    use hyper_util::{
+        rt::{TokioExecutor, TokioIo},
+        server::conn::auto::Builder,
+    };
+    use tower::Service;
+
+    loop {
+        let (socket, _) = listener.accept().await?;
+        let tower_service = app.clone();
+
+        tokio::spawn(async move {
+            let socket = TokioIo::new(socket);
+            let hyper_service =
+                hyper::service::service_fn(move |request: hyper::Request<Incoming>| {
+                    tower_service.clone().call(request)
+                });
+
+            // Upgrades are needed for the WebSocket endpoints
+            if let Err(e) = Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(socket, hyper_service)
+                .await
+            {
+                tracing::warn!("Failed to serve connection: {}", e);
+            }
+        });
+    }
+}
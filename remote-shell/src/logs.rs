@@ -0,0 +1,267 @@
+//! `/logs`: browse sessions pty-bash-hook recorded on this machine (`logs_dir`), in the shared
+//! session event format
+
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use session_events::{plain_text, Record, SessionEvent};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+
+use crate::{
+    api::{TranscriptFormat, TranscriptParams},
+    auth::AuthUser,
+    AppState,
+};
+
+/// What `GET /logs` reports about a recording
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingInfo {
+    /// File name without `.jsonl`
+    pub id: String,
+    /// Unix timestamp in seconds
+    pub started_at: u64,
+    pub shell: Option<String>,
+    pub cwd: Option<String>,
+    /// File size in bytes
+    pub size: u64,
+}
+
+/// Longest first line read for a listing; the `sessionStarted` header is far shorter
+const MAX_HEADER: u64 = 64 * 1024;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedCommandInfo {
+    /// Position in the recording, starting at 1
+    pub seq: u64,
+    pub command: Option<String>,
+    pub user: String,
+    pub host: String,
+    pub cwd: String,
+    /// Unix timestamps in seconds
+    pub started_at: u64,
+    pub ended_at: Option<u64>,
+    pub exit_code: Option<i32>,
+    pub wall_ms: Option<u64>,
+    pub cpu_ms: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordedCommand {
+    #[serde(flatten)]
+    pub info: RecordedCommandInfo,
+    /// Output with escape sequences removed
+    pub output: String,
+}
+
+/// Recordings are listed from a directory, so ids must not name anything outside it
+fn recording_path(dir: &Path, id: &str) -> Option<PathBuf> {
+    let valid = !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then(|| dir.join(format!("{}.jsonl", id)))
+}
+
+async fn load(path: &Path) -> Option<Vec<Record>> {
+    let text = tokio::fs::read_to_string(path).await.ok()?;
+    session_events::read(text.as_bytes())
+        .map_err(|e| tracing::warn!("Skipping unreadable recording {}: {}", path.display(), e))
+        .ok()
+}
+
+/// A recording's size and its first record, without reading the rest
+async fn header(path: &Path) -> Option<(u64, Record)> {
+    let file = tokio::fs::File::open(path).await.ok()?;
+    let size = file.metadata().await.ok()?.len();
+    let mut line = String::new();
+    BufReader::new(file.take(MAX_HEADER))
+        .read_line(&mut line)
+        .await
+        .ok()?;
+    let record = serde_json::from_str(&line)
+        .map_err(|e| tracing::warn!("Skipping unreadable recording {}: {}", path.display(), e))
+        .ok()?;
+    Some((size, record))
+}
+
+/// Commands in a recording with their raw output
+fn commands(records: &[Record]) -> Vec<(RecordedCommandInfo, String)> {
+    let started_at = match records.first().map(|r| &r.event) {
+        Some(SessionEvent::SessionStarted { started_at, .. }) => *started_at,
+        _ => 0,
+    };
+    let at = |time: f64| started_at + time as u64;
+
+    let mut commands: Vec<(RecordedCommandInfo, String)> = Vec::new();
+    for record in records {
+        let current = commands.last_mut().filter(|(info, _)| info.ended_at.is_none());
+        match &record.event {
+            SessionEvent::CommandStarted {
+                command,
+                user,
+                host,
+                cwd,
+            } => {
+                let seq = commands.len() as u64 + 1;
+                commands.push((
+                    RecordedCommandInfo {
+                        seq,
+                        command: command.clone(),
+                        user: user.clone(),
+                        host: host.clone(),
+                        cwd: cwd.clone(),
+                        started_at: at(record.time),
+                        ended_at: None,
+                        exit_code: None,
+                        wall_ms: None,
+                        cpu_ms: None,
+                    },
+                    String::new(),
+                ));
+            }
+            SessionEvent::Output { data } => {
+                if let Some((_, output)) = current {
+                    output.push_str(data);
+                }
+            }
+            SessionEvent::CommandEnded {
+                exit_code,
+                wall_ms,
+                cpu_ms,
+            } => {
+                if let Some((info, _)) = current {
+                    info.ended_at = Some(at(record.time));
+                    info.exit_code = *exit_code;
+                    info.wall_ms = *wall_ms;
+                    info.cpu_ms = *cpu_ms;
+                }
+            }
+            SessionEvent::SessionStarted { .. }
+            | SessionEvent::Resize { .. }
+            | SessionEvent::TitleChanged { .. } => {}
+        }
+    }
+    commands
+}
+
+/// The recordings directory, for admins only: recordings hold whatever was typed and shown
+fn logs_dir(user: &AuthUser, state: &AppState) -> Result<PathBuf, StatusCode> {
+    if !user.is_admin() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    state.config.logs_dir.clone().ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Recordings, newest first
+pub async fn recordings_handler(
+    user: AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<RecordingInfo>>, StatusCode> {
+    let dir = logs_dir(&user, &state)?;
+    let mut entries = tokio::fs::read_dir(&dir).await.map_err(|e| {
+        tracing::warn!("Cannot list recordings in {}: {}", dir.display(), e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let mut recordings = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension() != Some(OsStr::new("jsonl")) {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|s| s.to_str()).map(str::to_string) else {
+            continue;
+        };
+        // Listing reads only the header; commands are parsed when a recording is opened
+        let Some((size, first)) = header(&path).await else {
+            continue;
+        };
+
+        let (started_at, shell, cwd) = match first.event {
+            SessionEvent::SessionStarted {
+                started_at,
+                shell,
+                cwd,
+                ..
+            } => (started_at, shell, cwd),
+            _ => (0, None, None),
+        };
+        recordings.push(RecordingInfo {
+            id,
+            started_at,
+            shell,
+            cwd,
+            size,
+        });
+    }
+
+    recordings.sort_by(|a, b| b.started_at.cmp(&a.started_at).then_with(|| b.id.cmp(&a.id)));
+    Ok(Json(recordings))
+}
+
+/// Commands in a recording, without their output
+pub async fn recording_commands_handler(
+    user: AuthUser,
+    State(state): State<AppState>,
+    UrlPath(id): UrlPath<String>,
+) -> Result<Json<Vec<RecordedCommandInfo>>, StatusCode> {
+    let dir = logs_dir(&user, &state)?;
+    let path = recording_path(&dir, &id).ok_or(StatusCode::NOT_FOUND)?;
+    let records = load(&path).await.ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(commands(&records).into_iter().map(|(info, _)| info).collect()))
+}
+
+/// One command's output as text, `?format=json` for metadata too
+pub async fn recording_output_handler(
+    user: AuthUser,
+    State(state): State<AppState>,
+    UrlPath((id, seq)): UrlPath<(String, u64)>,
+    Query(params): Query<TranscriptParams>,
+) -> Response {
+    let dir = match logs_dir(&user, &state) {
+        Ok(dir) => dir,
+        Err(status) => return status.into_response(),
+    };
+    let Some(path) = recording_path(&dir, &id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(records) = load(&path).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some((info, output)) = usize::try_from(seq)
+        .ok()
+        .and_then(|seq| seq.checked_sub(1))
+        .and_then(|index| commands(&records).into_iter().nth(index))
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let output = plain_text(&output);
+    match params.format {
+        TranscriptFormat::Json => Json(RecordedCommand { info, output }).into_response(),
+        TranscriptFormat::Text => (
+            [
+                (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}-command-{}.txt\"", id, seq),
+                ),
+            ],
+            output,
+        )
+            .into_response(),
+    }
+}
@@ -1,31 +1,203 @@
-use axum::{routing::get, Router};
+use std::{path::PathBuf, sync::Arc};
+
+use axum::{
+    routing::{delete, get, post},
+    Router,
+};
+use clap::{Parser, Subcommand};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tower_http::services::ServeDir;
+use ts_rs::TS;
+use workspace_config::Overrides;
 
-use crate::api::{index_handler, ws_handler};
+use crate::{
+    api::{
+        approvals_handler, approve_handler, close_session_handler, commands_handler,
+        create_share_handler, deny_handler, exec_handler, hosts_handler, revoke_share_handler,
+        sessions_handler, transcript_handler, ws_handler,
+    },
+    approval::ApprovalStore,
+    assets::{index_handler, static_handler},
+    config::Config,
+    health::{healthz_handler, readyz_handler, version_handler},
+    jwt::JwtValidator,
+    logs::{recording_commands_handler, recording_output_handler, recordings_handler},
+    proxy::{proxy_handler, proxy_root_handler},
+    registry::SessionRegistry,
+    share::ShareStore,
+    tunnel::{agent_handler, AgentRegistry},
+};
 
+mod agent;
 mod api;
+mod approval;
+mod assets;
+mod auth;
+mod backend;
+mod compress;
+mod config;
+mod gen_types;
+mod health;
+mod history;
+mod jwt;
+mod listen;
+mod logs;
+mod motd;
+mod proxy;
+mod recording;
+mod registry;
+mod security;
+mod session;
+mod share;
+mod telemetry;
+mod tunnel;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Config file with a [server] section (default: $WORKSPACE_CONFIG, else
+    /// remote-shell.toml or workspace.toml in the working directory)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Serve static assets from this directory instead of the copies embedded in the binary
+    /// (for development)
+    #[arg(long, global = true)]
+    static_dir: Option<PathBuf>,
+
+    /// Address to serve on: host:port or unix:/path/to.sock (default 0.0.0.0:3000). Ignored
+    /// when systemd passes a socket through $LISTEN_FDS.
+    #[arg(long)]
+    listen: Option<String>,
+
+    /// OTLP (gRPC) collector to export traces to, e.g. http://localhost:4317. Needs the `otel`
+    /// build feature.
+    #[arg(long, global = true, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    otlp_endpoint: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Serve the web terminal (the default)
+    Serve,
+    /// Write TypeScript definitions and JSON Schemas for the WebSocket protocol
+    GenTypes {
+        /// Directory to write protocol.d.ts and the *.schema.json files to
+        #[arg(long, default_value = "static/generated")]
+        out: PathBuf,
+    },
+    /// Dial out to a hub and serve sessions over that connection, for machines behind NAT
+    Agent {
+        /// Hub tunnel endpoint, e.g. wss://hub.example/agent
+        #[arg(long)]
+        hub: String,
 
-#[derive(Serialize)]
+        /// Id the hub lists this machine under (defaults to the hostname)
+        #[arg(long)]
+        id: Option<String>,
+
+        /// Token matching this host's entry in the hub config
+        #[arg(long, env = "REMOTE_SHELL_AGENT_TOKEN")]
+        token: Option<String>,
+
+        /// Label shown in the hub's host list, as key=value (repeatable)
+        #[arg(long = "label", value_parser = parse_label)]
+        labels: Vec<(String, String)>,
+    },
+}
+
+/// Shared state handed to every handler
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    /// Agents connected to this instance acting as a hub
+    pub agents: Arc<AgentRegistry>,
+    /// Live sessions, local and remote
+    pub sessions: Arc<SessionRegistry>,
+    /// Set when JWT authentication is configured
+    pub jwt: Option<Arc<JwtValidator>>,
+    /// Share links handed out for sessions
+    pub shares: Arc<ShareStore>,
+    /// Commands waiting for an admin's approval
+    pub approvals: Arc<ApprovalStore>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema, TS)]
 #[serde(tag = "type", rename_all = "camelCase")]
-enum ServerLogMsg {
+// ts-rs doesn't follow serde's rename_all for variants, and its own camelCase lowercases them
+// whole, so variants of more than one word are named explicitly
+#[ts(rename_all = "camelCase")]
+pub enum ServerLogMsg {
+    #[ts(rename = "logStart")]
     LogStart {
         user: String,
         host: String,
         cwd: String,
     },
+    #[ts(rename = "logOutput")]
     LogOutput {
         data: String,
     },
+    #[ts(rename = "logEnd")]
     LogEnd {
         #[serde(rename = "exitCode")]
         exit_code: i32,
+        /// Wall-clock duration in milliseconds
+        #[serde(rename = "wallMs", default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        wall_ms: Option<u64>,
+        /// User + system CPU time in milliseconds
+        #[serde(rename = "cpuMs", default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        cpu_ms: Option<u64>,
+        /// Peak resident set size in KiB, only known for local shells
+        #[serde(rename = "maxRssKb", default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional, type = "number")]
+        max_rss_kb: Option<u64>,
+    },
+    /// Log output of the current command went over a limit; sent before its `LogEnd`
+    Truncated {
+        #[serde(rename = "droppedBytes")]
+        #[ts(type = "number")]
+        dropped_bytes: u64,
+    },
+    /// Output of a piped `Run`, one stream at a time
+    #[ts(rename = "execOutput")]
+    ExecOutput {
+        id: String,
+        stream: ExecStream,
+        data: String,
+    },
+    /// A piped `Run` finished
+    #[ts(rename = "execEnd")]
+    ExecEnd {
+        id: String,
+        #[serde(rename = "exitCode")]
+        exit_code: i32,
     },
+    /// A program set the clipboard (OSC 52), with `clipboard = "forward"`
+    Clipboard {
+        data: String,
+    },
+    /// A program set the window title (OSC 0/2), usually to what's running or the directory
+    Title {
+        text: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema, TS)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecStream {
+    Stdout,
+    Stderr,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, JsonSchema, TS)]
 #[serde(tag = "type", rename_all = "lowercase")]
-enum ClientMsg {
+pub enum ClientMsg {
     Input {
         data: String,
     },
@@ -33,8 +205,13 @@ enum ClientMsg {
     Run {
         data: String,
 
-        #[allow(unused)]
         id: String,
+
+        /// Run as a plain child process with separate stdout/stderr instead of typing it into
+        /// the terminal. Results come back as `ExecOutput`/`ExecEnd` with the same id.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[ts(optional)]
+        piped: Option<bool>,
     },
     Resize {
         cols: u16,
@@ -44,15 +221,104 @@ enum ClientMsg {
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    let cli = Cli::parse();
+
+    if let Some(Command::GenTypes { out }) = &cli.command {
+        gen_types::write(out)
+            .unwrap_or_else(|e| panic!("Failed to write types to {}: {}", out.display(), e));
+        return;
+    }
+
+    let config = Arc::new(Config::load(
+        cli.config.as_deref(),
+        Overrides::new()
+            .set("listen", cli.listen)
+            .set("static_dir", cli.static_dir)
+            .set("otlp_endpoint", cli.otlp_endpoint),
+    ));
+    telemetry::init(config.server.otlp_endpoint.as_deref());
+    assets::init(config.server.static_dir.clone());
+
+    if let Some(Command::Agent {
+        hub,
+        id,
+        token,
+        labels,
+    }) = cli.command
+    {
+        let registration = agent::Registration {
+            id: id.unwrap_or_else(hostname),
+            token,
+            labels: labels.into_iter().collect(),
+        };
+        agent::run(hub, registration, config).await;
+        return;
+    }
+
+    for (id, host) in &config.hosts {
+        if host.token.is_none() && config.allow_unregistered_agents {
+            tracing::warn!("Host {} has no token, any agent can register as it", id);
+        } else if host.token.is_none() {
+            tracing::warn!("Host {} has no token, its agent will be turned away", id);
+        }
+    }
 
+    let state = AppState {
+        jwt: config.jwt.clone().map(|jwt| Arc::new(JwtValidator::new(jwt))),
+        shares: Arc::new(ShareStore::default()),
+        approvals: Arc::new(ApprovalStore::new(config.require_approval.clone())),
+        config,
+        agents: Arc::new(AgentRegistry::default()),
+        sessions: Arc::new(SessionRegistry::default()),
+    };
+
+    let address = state.config.server.listen.clone();
+    let cors = security::cors_layer(&state.config.http);
     let app = Router::new()
         .route("/", get(index_handler))
         .route("/ws", get(ws_handler))
-        .nest_service("/static", ServeDir::new("static"));
+        .route("/agent", get(agent_handler))
+        .route("/healthz", get(healthz_handler))
+        .route("/readyz", get(readyz_handler))
+        .route("/api/version", get(version_handler))
+        .route("/api/hosts", get(hosts_handler))
+        .route("/api/sessions", get(sessions_handler))
+        .route("/api/sessions/:id", delete(close_session_handler))
+        .route("/api/sessions/:id/shares", post(create_share_handler))
+        .route("/api/sessions/:id/exec", post(exec_handler))
+        .route("/api/sessions/:id/commands", get(commands_handler))
+        .route("/api/sessions/:id/commands/:seq/transcript", get(transcript_handler))
+        .route("/api/shares/:id", delete(revoke_share_handler))
+        .route("/api/approvals", get(approvals_handler))
+        .route("/api/approvals/:id/approve", post(approve_handler))
+        .route("/api/approvals/:id/deny", post(deny_handler))
+        .route("/logs", get(recordings_handler))
+        .route("/logs/:id/commands", get(recording_commands_handler))
+        .route("/logs/:id/commands/:seq/output", get(recording_output_handler))
+        .route("/share/:token", get(index_handler))
+        .route("/proxy/:session/:port/", get(proxy_root_handler))
+        .route("/proxy/:session/:port/*path", get(proxy_handler))
+        .route("/static/*path", get(static_handler))
+        .layer(cors)
+        .with_state(state);
+
+    let listener = listen::open(&address)
+        .await
+        .unwrap_or_else(|e| panic!("Failed to listen on {}: {}", address, e));
+    listen::serve(listener, app).await.unwrap();
+}
+
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "agent".to_string())
+}
 
-    let addr = "0.0.0.0:3000";
-    tracing::info!("Listening on http://{}", addr);
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+fn parse_label(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("Expected key=value, got {}", s))
 }
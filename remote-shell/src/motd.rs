@@ -0,0 +1,32 @@
+//! Banner written to a new session's terminal before the shell's first prompt, rendered with
+//! text-ui-core from the `[motd]` config section
+
+use text_ui_core::{color::Depth, BannerBuilder, BannerConfig, Output};
+
+/// `config` rendered for a session on `host`, as terminal output. `{host}` and `{version}` in
+/// the text, title and info are filled in. `None` (logged) if the banner can't be rendered, the
+/// session opens without one then.
+pub fn render(config: &BannerConfig, host: &str) -> Option<Vec<u8>> {
+    let fill = |s: &str| {
+        s.replace("{host}", host)
+            .replace("{version}", env!("CARGO_PKG_VERSION"))
+    };
+    let mut config = config.clone();
+    config.text = fill(&config.text);
+    config.title = config.title.as_deref().map(fill);
+    config.info = config.info.as_deref().map(fill);
+
+    // The terminal's width isn't known until the client's first resize, so the banner is
+    // neither fitted nor aligned. xterm.js shows truecolor.
+    match BannerBuilder::from(config)
+        .output(Output::Ansi)
+        .depth(Depth::TrueColor)
+        .render()
+    {
+        Ok(banner) => Some(banner.replace('\n', "\r\n").into_bytes()),
+        Err(e) => {
+            tracing::warn!("Failed to render the MOTD banner: {}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! `GET /proxy/:session/:port/*path`: peek at HTTP services listening on localhost of the machine
+//! hosting a session (this server, or the agent for remote sessions).
+//!
+//! Container and pod sessions are proxied to the machine running docker/kubectl, not into the
+//! container.
+
+use std::{sync::OnceLock, time::Duration};
+
+use axum::{
+    extract::{Path as UrlPath, RawQuery, State},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{auth::AuthUser, AppState};
+
+/// Upstream responses larger than this are refused
+const MAX_BODY: usize = 16 * 1024 * 1024;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A proxied response, also carried through the agent tunnel
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProxyResponse {
+    pub status: u16,
+    pub content_type: Option<String>,
+    /// Redirect target as the upstream sent it; [`proxy`] rewrites it under the proxy path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_disposition: Option<String>,
+    /// Cookies as the upstream set them; [`proxy`] scopes them to the proxy path
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub set_cookie: Vec<String>,
+    #[serde(with = "base64_body")]
+    pub body: Vec<u8>,
+}
+
+/// Bodies are base64 in JSON tunnel frames
+mod base64_body {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(body: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(body))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            // A redirect could point anywhere, let the browser see it instead
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("HTTP client builds")
+    })
+}
+
+/// GET `path` (with its query string) from `localhost:<port>` on this machine
+pub async fn fetch(port: u16, path: &str) -> Result<ProxyResponse, String> {
+    let url = format!("http://127.0.0.1:{}/{}", port, path.trim_start_matches('/'));
+    let mut response = client().get(&url).send().await.map_err(|e| e.to_string())?;
+
+    let status = response.status().as_u16();
+    let headers = response.headers();
+    let single = |name: header::HeaderName| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let content_type = single(header::CONTENT_TYPE);
+    let location = single(header::LOCATION);
+    let cache_control = single(header::CACHE_CONTROL);
+    let content_disposition = single(header::CONTENT_DISPOSITION);
+    let set_cookie = headers
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(str::to_string)
+        .collect();
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        if body.len() + chunk.len() > MAX_BODY {
+            return Err(format!("Response is larger than {} bytes", MAX_BODY));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(ProxyResponse {
+        status,
+        content_type,
+        location,
+        cache_control,
+        content_disposition,
+        set_cookie,
+        body,
+    })
+}
+
+/// Where a redirect should send the browser: targets on the proxied machine's localhost (and
+/// absolute paths, which are relative to it) go back through the proxy, anything else is left
+/// alone
+fn rewrite_location(location: &str, session: &str, port: u16) -> String {
+    let prefix = format!("/proxy/{}/{}", session, port);
+    if location.starts_with('/') && !location.starts_with("//") {
+        return format!("{}{}", prefix, location);
+    }
+
+    let absolute = if location.starts_with("//") {
+        reqwest::Url::parse(&format!("http:{}", location))
+    } else {
+        reqwest::Url::parse(location)
+    };
+    let Ok(url) = absolute else {
+        // Relative to the current page, which is already under the proxy
+        return location.to_string();
+    };
+    let local = matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]"));
+    let Some(target) = url.port_or_known_default().filter(|_| local) else {
+        return location.to_string();
+    };
+
+    let mut path = format!("/proxy/{}/{}{}", session, target, url.path());
+    if let Some(query) = url.query() {
+        path = format!("{}?{}", path, query);
+    }
+    if let Some(fragment) = url.fragment() {
+        path = format!("{}#{}", path, fragment);
+    }
+    path
+}
+
+/// `cookie` with its `Path` moved under the proxy path and its `Domain` dropped, so it's only
+/// sent back to the service that set it
+fn scope_cookie(cookie: &str, session: &str, port: u16) -> String {
+    let mut parts = cookie.split(';').map(str::trim);
+    let mut scoped = vec![parts.next().unwrap_or_default().to_string()];
+    let mut path = "/";
+    for attribute in parts {
+        let (name, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        if name.eq_ignore_ascii_case("path") {
+            if value.starts_with('/') {
+                path = value;
+            }
+        } else if !name.eq_ignore_ascii_case("domain") {
+            scoped.push(attribute.to_string());
+        }
+    }
+    scoped.push(format!("Path=/proxy/{}/{}{}", session, port, path));
+    scoped.join("; ")
+}
+
+/// `query` without the `token` parameter. Browsers can only authenticate a navigation with
+/// `?token=`, and the user's token is no business of the proxied service (or the audit log).
+fn strip_token(query: &str) -> Option<String> {
+    let params: Vec<&str> = query
+        .split('&')
+        .filter(|param| param.split('=').next() != Some("token"))
+        .collect();
+    (!params.is_empty()).then(|| params.join("&"))
+}
+
+pub async fn proxy_handler(
+    user: AuthUser,
+    State(state): State<AppState>,
+    UrlPath((session, port, path)): UrlPath<(String, u16, String)>,
+    RawQuery(query): RawQuery,
+) -> Response {
+    proxy(user, state, session, port, path, query).await
+}
+
+/// `/proxy/:session/:port/`, which the wildcard route doesn't match
+pub async fn proxy_root_handler(
+    user: AuthUser,
+    State(state): State<AppState>,
+    UrlPath((session, port)): UrlPath<(String, u16)>,
+    RawQuery(query): RawQuery,
+) -> Response {
+    proxy(user, state, session, port, String::new(), query).await
+}
+
+async fn proxy(
+    user: AuthUser,
+    state: AppState,
+    session: String,
+    port: u16,
+    path: String,
+    query: Option<String>,
+) -> Response {
+    let Some(entry) = state.sessions.get(&session) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    // Reaching the host's local services is as good as having its shell
+    if !user.can_interact(&entry) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let path = match query.as_deref().and_then(strip_token) {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+    tracing::info!(
+        target: "audit",
+        user = %user.name,
+        session = %entry.id,
+        port,
+        path = %path,
+        "Proxying request"
+    );
+
+    let result = match &entry.host {
+        None => fetch(port, &path).await,
+        Some(host) => match state.agents.get(host) {
+            Some(agent) => agent.proxy(port, path).await,
+            None => Err(format!("Host {} is not connected", host)),
+        },
+    };
+
+    match result {
+        Ok(upstream) => {
+            let status = StatusCode::from_u16(upstream.status).unwrap_or(StatusCode::BAD_GATEWAY);
+            let mut response = (status, upstream.body).into_response();
+            let location = upstream
+                .location
+                .map(|location| rewrite_location(&location, &entry.id, port));
+            let single = [
+                (header::CONTENT_TYPE, upstream.content_type),
+                (header::LOCATION, location),
+                (header::CACHE_CONTROL, upstream.cache_control),
+                (header::CONTENT_DISPOSITION, upstream.content_disposition),
+            ];
+            for (name, value) in single {
+                if let Some(value) = value.and_then(|value| HeaderValue::from_str(&value).ok()) {
+                    response.headers_mut().insert(name, value);
+                }
+            }
+            for cookie in &upstream.set_cookie {
+                let cookie = scope_cookie(cookie, &entry.id, port);
+                if let Ok(value) = HeaderValue::from_str(&cookie) {
+                    response.headers_mut().append(header::SET_COOKIE, value);
+                }
+            }
+            // Proxied pages are served from our origin; sandboxing gives them an opaque one so
+            // their scripts can't act as the user against the API
+            response.headers_mut().insert(
+                header::CONTENT_SECURITY_POLICY,
+                HeaderValue::from_static("sandbox allow-scripts allow-forms allow-popups"),
+            );
+            response
+        }
+        Err(e) => {
+            tracing::warn!("Proxy to port {} for session {} failed: {}", port, entry.id, e);
+            (StatusCode::BAD_GATEWAY, e).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirects_on_the_proxied_machine_stay_in_the_proxy() {
+        assert_eq!(rewrite_location("/login?next=/", "7", 8080), "/proxy/7/8080/login?next=/");
+        assert_eq!(
+            rewrite_location("http://localhost:8080/app/#top", "7", 8080),
+            "/proxy/7/8080/app/#top"
+        );
+        assert_eq!(rewrite_location("http://127.0.0.1:3000/", "7", 8080), "/proxy/7/3000/");
+        assert_eq!(rewrite_location("//localhost/x", "7", 8080), "/proxy/7/80/x");
+    }
+
+    #[test]
+    fn other_redirects_are_left_alone() {
+        assert_eq!(rewrite_location("next", "7", 8080), "next");
+        assert_eq!(
+            rewrite_location("https://idp.example/auth", "7", 8080),
+            "https://idp.example/auth"
+        );
+    }
+
+    #[test]
+    fn cookies_are_scoped_to_the_proxy_path() {
+        assert_eq!(
+            scope_cookie("sid=abc; Path=/app; Domain=localhost; HttpOnly", "7", 8080),
+            "sid=abc; HttpOnly; Path=/proxy/7/8080/app"
+        );
+        assert_eq!(scope_cookie("theme=dark", "7", 8080), "theme=dark; Path=/proxy/7/8080/");
+    }
+
+    #[test]
+    fn the_users_token_is_not_passed_upstream() {
+        assert_eq!(strip_token("token=secret"), None);
+        assert_eq!(strip_token("a=1&token=secret&b=2").as_deref(), Some("a=1&b=2"));
+        assert_eq!(strip_token("tokens=1&token").as_deref(), Some("tokens=1"));
+        assert_eq!(strip_token("q=token%3Dx").as_deref(), Some("q=token%3Dx"));
+    }
+}
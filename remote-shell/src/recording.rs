@@ -0,0 +1,89 @@
+//! Session recordings in the workspace's shared event format (`record_dir`), one JSON lines file
+//! per session
+
+use std::{fs::File, io::BufWriter, path::Path};
+
+use session_events::{Recorder, SessionEvent};
+
+use crate::{
+    registry::{Frame, SessionEntry},
+    ClientMsg, ServerLogMsg,
+};
+
+/// Records a session's frames, or does nothing when recording is off or the file couldn't be
+/// created
+pub struct Recording(Option<Recorder<BufWriter<File>>>);
+
+impl Recording {
+    pub fn start(dir: Option<&Path>, entry: &SessionEntry) -> Self {
+        let Some(dir) = dir else {
+            return Recording(None);
+        };
+
+        // Session ids restart with the process, the creation time keeps file names apart
+        let path = dir.join(format!("{}-{}.jsonl", entry.created_at, entry.id));
+        let recorder = File::create(&path).and_then(|file| {
+            // PTYs start at 80x24 until the first client resizes them
+            Recorder::new(BufWriter::new(file), 80, 24, None, None)
+        });
+        match recorder {
+            Ok(recorder) => Recording(Some(recorder)),
+            Err(e) => {
+                tracing::warn!("Not recording session {} to {}: {}", entry.id, path.display(), e);
+                Recording(None)
+            }
+        }
+    }
+
+    pub fn frame(&mut self, frame: &Frame) {
+        let Some(recorder) = &mut self.0 else {
+            return;
+        };
+
+        let result = match frame {
+            Frame::Output(data) => recorder.output(data),
+            // The shell reports the command line only to the client that typed it
+            Frame::Log(ServerLogMsg::LogStart { user, host, cwd }) => {
+                recorder.record(SessionEvent::CommandStarted {
+                    command: None,
+                    user: user.clone(),
+                    host: host.clone(),
+                    cwd: cwd.clone(),
+                })
+            }
+            Frame::Log(ServerLogMsg::LogEnd {
+                exit_code,
+                wall_ms,
+                cpu_ms,
+                ..
+            }) => recorder.record(SessionEvent::CommandEnded {
+                exit_code: Some(*exit_code),
+                wall_ms: *wall_ms,
+                cpu_ms: *cpu_ms,
+            }),
+            Frame::Log(ServerLogMsg::Title { text }) => {
+                recorder.record(SessionEvent::TitleChanged { title: text.clone() })
+            }
+            Frame::Log(_) => Ok(()),
+        };
+        self.check(result);
+    }
+
+    pub fn client_msg(&mut self, msg: &ClientMsg) {
+        if let (Some(recorder), ClientMsg::Resize { cols, rows }) = (&mut self.0, msg) {
+            let result = recorder.record(SessionEvent::Resize {
+                cols: *cols,
+                rows: *rows,
+            });
+            self.check(result);
+        }
+    }
+
+    /// Stop recording after a write error instead of failing on every frame
+    fn check(&mut self, result: std::io::Result<()>) {
+        if let Err(e) = result {
+            tracing::warn!("Stopped recording session: {}", e);
+            self.0 = None;
+        }
+    }
+}
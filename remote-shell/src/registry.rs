@@ -0,0 +1,251 @@
+//! Registry of live sessions that several clients can attach to
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::{auth::LOCAL_HOST, history::History, ClientMsg, ServerLogMsg};
+
+/// Something a session produced, fanned out to every attached client
+#[derive(Clone, Debug)]
+pub enum Frame {
+    Output(Vec<u8>),
+    Log(ServerLogMsg),
+}
+
+/// Name and tags given to a session when it's opened, to find it again by
+#[derive(Debug, Clone, Default)]
+pub struct Naming {
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// What `GET /api/sessions` narrows the list down by. Every one given must match.
+#[derive(Deserialize, Debug, Default)]
+pub struct SessionFilter {
+    /// Text looked for, ignoring case, in the session's name, tags, owner and host, and in the
+    /// directories and output of its commands
+    pub q: Option<String>,
+    /// A tag the session has
+    pub tag: Option<String>,
+    /// Agent id, or "local" for sessions on this machine
+    pub host: Option<String>,
+}
+
+/// Instructions for the task driving a session
+#[derive(Debug)]
+pub enum Control {
+    Client(ClientMsg),
+    Close,
+}
+
+pub struct SessionEntry {
+    pub id: String,
+    /// Name of the user who opened the session
+    pub owner: String,
+    /// Agent id, `None` for a session on this machine
+    pub host: Option<String>,
+    pub profile: Option<String>,
+    /// tmux session the shell runs in, for tmux profiles
+    pub tmux: Option<String>,
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+    /// Unix timestamp in seconds
+    pub created_at: u64,
+    /// Commands run so far, fed by the driving task
+    pub history: History,
+    /// Cleared once the session ends so attached clients see the channel close
+    output: Mutex<Option<broadcast::Sender<Frame>>>,
+    control: mpsc::UnboundedSender<Control>,
+}
+
+impl SessionEntry {
+    /// Start receiving the session's output, `None` if it has already ended
+    pub fn subscribe(&self) -> Option<broadcast::Receiver<Frame>> {
+        self.output.lock().unwrap().as_ref().map(|tx| tx.subscribe())
+    }
+
+    /// Number of clients currently subscribed to the output
+    pub fn client_count(&self) -> usize {
+        self.output
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(0, |tx| tx.receiver_count())
+    }
+
+    pub fn send(&self, msg: ClientMsg) {
+        let _ = self.control.send(Control::Client(msg));
+    }
+
+    /// Ask the driving task to end the session
+    pub fn close(&self) {
+        let _ = self.control.send(Control::Close);
+    }
+
+    pub fn info(&self) -> SessionInfo {
+        SessionInfo {
+            id: self.id.clone(),
+            owner: self.owner.clone(),
+            host: self.host.clone(),
+            profile: self.profile.clone(),
+            tmux: self.tmux.clone(),
+            name: self.name.clone(),
+            tags: self.tags.clone(),
+            created_at: self.created_at,
+            matching_commands: Vec::new(),
+        }
+    }
+
+    /// The session's info if it passes `filter`, with the commands `q` was found in
+    pub fn search(&self, filter: &SessionFilter) -> Option<SessionInfo> {
+        if filter.tag.as_ref().is_some_and(|tag| !self.tags.contains(tag)) {
+            return None;
+        }
+        let host = self.host.as_deref().unwrap_or(LOCAL_HOST);
+        if filter.host.as_deref().is_some_and(|wanted| wanted != host) {
+            return None;
+        }
+
+        let mut info = self.info();
+        if let Some(q) = filter.q.as_deref().map(str::to_lowercase) {
+            let found = |text: &str| text.to_lowercase().contains(&q);
+            let own = self.name.as_deref().is_some_and(found)
+                || self.tags.iter().any(|tag| found(tag))
+                || found(&self.owner)
+                || found(host);
+            info.matching_commands = self.history.search(&q);
+            if !own && info.matching_commands.is_empty() {
+                return None;
+            }
+        }
+        Some(info)
+    }
+}
+
+/// What `GET /api/sessions` reports about a session
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub id: String,
+    pub owner: String,
+    pub host: Option<String>,
+    pub profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tmux: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    pub created_at: u64,
+    /// `seq` of the commands a search found its text in
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub matching_commands: Vec<u64>,
+}
+
+/// Handed to the task driving a session: publish output on `output` (having no receivers is
+/// fine), receive client messages on `control`
+pub struct SessionDriver {
+    pub entry: Arc<SessionEntry>,
+    pub output: broadcast::Sender<Frame>,
+    pub control: mpsc::UnboundedReceiver<Control>,
+    registry: Arc<SessionRegistry>,
+}
+
+impl Drop for SessionDriver {
+    fn drop(&mut self) {
+        self.entry.output.lock().unwrap().take();
+        self.registry.remove(&self.entry.id);
+        tracing::info!("Session {} ended", self.entry.id);
+    }
+}
+
+#[derive(Default)]
+pub struct SessionRegistry {
+    sessions: Mutex<HashMap<String, Arc<SessionEntry>>>,
+    next_id: AtomicU64,
+}
+
+impl SessionRegistry {
+    /// Register a new session. The caller drives it with the returned [`SessionDriver`];
+    /// dropping the driver unregisters the session.
+    pub fn create(
+        self: &Arc<Self>,
+        owner: &str,
+        host: Option<String>,
+        profile: Option<String>,
+        tmux: Option<String>,
+        naming: Naming,
+    ) -> SessionDriver {
+        let id = (self.next_id.fetch_add(1, Ordering::Relaxed) + 1).to_string();
+        let (output, _) = broadcast::channel(256);
+        let (control_tx, control_rx) = mpsc::unbounded_channel();
+
+        let entry = Arc::new(SessionEntry {
+            id: id.clone(),
+            owner: owner.to_string(),
+            host,
+            profile,
+            tmux,
+            name: naming.name,
+            tags: naming.tags,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            history: History::default(),
+            output: Mutex::new(Some(output.clone())),
+            control: control_tx,
+        });
+
+        self.sessions.lock().unwrap().insert(id, entry.clone());
+
+        SessionDriver {
+            entry,
+            output,
+            control: control_rx,
+            registry: self.clone(),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<SessionEntry>> {
+        self.sessions.lock().unwrap().get(id).cloned()
+    }
+
+    /// The live session running in tmux session `name` of `profile`, if any. The same name
+    /// under another profile is another backend's session.
+    pub fn find_tmux(&self, profile: Option<&str>, name: &str) -> Option<Arc<SessionEntry>> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .values()
+            .find(|entry| entry.profile.as_deref() == profile && entry.tmux.as_deref() == Some(name))
+            .cloned()
+    }
+
+    /// Live sessions passing `filter`, oldest first
+    pub fn list(&self, filter: &SessionFilter) -> Vec<SessionInfo> {
+        let mut sessions: Vec<_> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|entry| entry.search(filter))
+            .collect();
+        // Ids count up, ordering sessions created within the same second
+        sessions.sort_by_key(|info| (info.created_at, info.id.parse::<u64>().unwrap_or(u64::MAX)));
+        sessions
+    }
+
+    fn remove(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+}
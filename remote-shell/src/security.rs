@@ -0,0 +1,84 @@
+//! Cross-origin policy and security headers
+
+use axum::http::{header, HeaderMap, HeaderValue, Method};
+use serde::Deserialize;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// The `[http]` config section. Defaults are strict: same-origin only, no framing.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Origins besides the server's own allowed to call the API and open WebSockets,
+    /// e.g. "https://dashboard.internal"
+    pub allowed_origins: Vec<String>,
+    /// Origins allowed to embed the UI in a frame; framing is denied when empty
+    pub frame_ancestors: Vec<String>,
+    /// Replaces the default Content-Security-Policy entirely
+    pub content_security_policy: Option<String>,
+}
+
+pub fn cors_layer(config: &HttpConfig) -> CorsLayer {
+    let origins: Vec<HeaderValue> = config
+        .allowed_origins
+        .iter()
+        .filter_map(|origin| match HeaderValue::from_str(origin) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                tracing::warn!("Ignoring invalid allowed origin {}", origin);
+                None
+            }
+        })
+        .collect();
+
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+}
+
+/// Browsers don't apply CORS to WebSockets, so the upgrade has to check `Origin` itself.
+/// Requests without an `Origin` (agents, CLI tools) aren't browser requests and pass.
+pub fn origin_allowed(headers: &HeaderMap, config: &HttpConfig) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN).and_then(|v| v.to_str().ok()) else {
+        return true;
+    };
+
+    if config.allowed_origins.iter().any(|allowed| allowed == origin) {
+        return true;
+    }
+
+    // Same origin: the origin's authority matches the Host we were reached on
+    let authority = origin.split_once("://").map(|(_, rest)| rest);
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    authority.is_some() && authority == host
+}
+
+/// Headers for the HTML pages
+pub fn html_headers(config: &HttpConfig) -> HeaderMap {
+    let frame_ancestors = if config.frame_ancestors.is_empty() {
+        "'none'".to_string()
+    } else {
+        config.frame_ancestors.join(" ")
+    };
+
+    // index.html uses inline scripts and styles
+    let csp = config.content_security_policy.clone().unwrap_or_else(|| {
+        format!(
+            "default-src 'self'; script-src 'self' 'unsafe-inline'; style-src 'self' 'unsafe-inline'; \
+             connect-src 'self'; frame-ancestors {}",
+            frame_ancestors
+        )
+    });
+
+    let mut headers = HeaderMap::new();
+    if let Ok(csp) = HeaderValue::from_str(&csp) {
+        headers.insert(header::CONTENT_SECURITY_POLICY, csp);
+    }
+    // X-Frame-Options can't express a list of origins; CSP frame-ancestors covers that case
+    if config.frame_ancestors.is_empty() {
+        headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    }
+    headers.insert(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    headers.insert(header::REFERRER_POLICY, HeaderValue::from_static("no-referrer"));
+    headers
+}
@@ -0,0 +1,568 @@
+//! PTY sessions, shared by the browser WebSocket handler and agent mode
+
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use portable_pty::{Child, MasterPty, NativePtySystem, PtySize, PtySystem};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    sync::mpsc,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use shell_markers::{Marker, MarkerParser};
+
+use crate::{
+    assets,
+    backend::Backend,
+    config::{ClipboardPolicy, LogLimits},
+    ClientMsg, ExecStream, ServerLogMsg,
+};
+
+/// A shell running behind a PTY.
+///
+/// Output is delivered through the receivers returned by [`PtySession::spawn`]; dropping the
+/// session kills the child.
+pub struct PtySession {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    master: Arc<Mutex<Box<dyn MasterPty + Send>>>,
+    child: Box<dyn Child + Send + Sync>,
+    /// Where piped `Run`s execute
+    backend: Backend,
+    cwd: PathBuf,
+    /// Piped `Run` results go out with the logs. Weak so the channel still closes when the
+    /// shell exits.
+    tx_log: mpsc::WeakSender<ServerLogMsg>,
+}
+
+/// Receiving ends of a session: raw terminal bytes and parsed command logs
+pub struct SessionOutput {
+    pub output: mpsc::Receiver<Vec<u8>>,
+    pub logs: mpsc::Receiver<ServerLogMsg>,
+}
+
+impl PtySession {
+    pub fn spawn(
+        backend: &Backend,
+        cwd: &Path,
+        limits: LogLimits,
+        clipboard: ClipboardPolicy,
+    ) -> anyhow::Result<(Self, SessionOutput)> {
+        let pty_system = NativePtySystem::default();
+
+        let pair = pty_system.openpty(PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
+
+        let script_dir = assets::integration_dir();
+
+        tracing::info!("Starting {:?} session in {}", backend, cwd.display());
+        let cmd = backend.command(cwd, &script_dir);
+
+        let child = pair.slave.spawn_command(cmd)?;
+
+        let master = pair.master;
+        let mut reader = master.try_clone_reader()?;
+        let writer = master.take_writer()?;
+
+        // We wrap writer in a Mutex to use it in the loop (which is technically blocking, but fast for buffer write)
+        let writer = Arc::new(Mutex::new(writer));
+        let master = Arc::new(Mutex::new(master));
+
+        // Initialize Shell Integration where the command line couldn't (zsh has no --rcfile, containers
+        // don't have our scripts on disk)
+        if let Some(init_cmd) = backend.init_input(&script_dir) {
+            if let Ok(mut w) = writer.lock() {
+                // To hide the command itself from history/view, usually we can't easily do it via injection
+                // without "space" prefix (if configured) or just accept it prints once.
+                let _ = w.write_all(init_cmd.as_bytes());
+                let _ = w.flush();
+            }
+        }
+
+        let (tx_output, rx_output) = mpsc::channel::<Vec<u8>>(32);
+        let (tx_log, rx_log) = mpsc::channel::<ServerLogMsg>(32);
+        let weak_tx_log = tx_log.downgrade();
+
+        // Only local shells run their commands where we can see them in /proc
+        let max_rss = Arc::new(AtomicU64::new(0));
+        #[cfg(target_os = "linux")]
+        if matches!(backend, Backend::Local { .. }) {
+            let master = Arc::downgrade(&master);
+            let shell_pid = child.process_id();
+            let max_rss = max_rss.clone();
+            thread::spawn(move || sample_rss(master, shell_pid, max_rss));
+        }
+
+        // Spawn blocking thread for reading PTY
+        thread::spawn(move || {
+            let mut buf = [0u8; 2048];
+            let mut parser = MarkerParser::new();
+            let mut interpreter = LogInterpreter::new(tx_log, max_rss, limits, clipboard);
+            let mut osc52_filter = (clipboard == ClipboardPolicy::Block).then(Osc52Filter::default);
+
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(n) if n > 0 => {
+                        let data = buf[..n].to_vec();
+                        // Send RAW output to frontend terminal
+                        let raw = match &mut osc52_filter {
+                            Some(filter) => filter.filter(&data),
+                            None => data.clone(),
+                        };
+                        if !raw.is_empty() && tx_output.blocking_send(raw).is_err() {
+                            break;
+                        }
+
+                        // Feed data to the marker parser for log extraction
+                        parser.advance(&mut interpreter, &data);
+
+                        // Flush every chunk so the logs container updates in real time
+                        interpreter.flush();
+                    }
+                    Ok(_) => {
+                        tracing::info!("PTY EOF");
+                        break;
+                    }
+                    Err(e) => {
+                        tracing::error!("PTY Read Error: {}", e);
+                        break;
+                    }
+                }
+            }
+            tracing::info!("PTY read thread exited");
+        });
+
+        let session = Self {
+            writer,
+            master,
+            child,
+            backend: backend.clone(),
+            cwd: cwd.to_path_buf(),
+            tx_log: weak_tx_log,
+        };
+        let output = SessionOutput {
+            output: rx_output,
+            logs: rx_log,
+        };
+
+        Ok((session, output))
+    }
+
+    /// Apply a message from the client to the PTY
+    pub fn handle_client_msg(&self, msg: ClientMsg) {
+        match msg {
+            ClientMsg::Input { data } => {
+                self.write(data.as_bytes());
+                tracing::info!("Received input: {}", data);
+            }
+            ClientMsg::Run {
+                data,
+                id,
+                piped: Some(true),
+            } => {
+                tracing::info!("Executing piped command {}: {}", id, data);
+                self.exec_piped(id, data);
+            }
+            ClientMsg::Run { data, .. } => {
+                // Just send the raw command. The shell integration (trap) will handle markers.
+                // We add a newline to ensure execution.
+                self.write(format!("{}\n", data).as_bytes());
+                tracing::info!("Executed command: {}", data);
+            }
+            ClientMsg::Resize { cols, rows } => {
+                self.resize(cols, rows);
+                tracing::info!("Resized PTY to {} cols and {} rows", cols, rows);
+            }
+        }
+    }
+
+    /// Run `command` as a plain child process, reporting its stdout and stderr separately
+    /// through the log channel
+    fn exec_piped(&self, id: String, command: String) {
+        let Some(tx_log) = self.tx_log.upgrade() else {
+            return;
+        };
+
+        let mut cmd = self.backend.exec_command(&self.cwd, &command);
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        tokio::spawn(async move {
+            let exit_code = match run_piped(cmd, &id, &tx_log).await {
+                Ok(status) => status.code().unwrap_or(-1),
+                Err(e) => {
+                    let _ = tx_log
+                        .send(ServerLogMsg::ExecOutput {
+                            id: id.clone(),
+                            stream: ExecStream::Stderr,
+                            data: format!("Failed to run command: {}\n", e),
+                        })
+                        .await;
+                    127
+                }
+            };
+            let _ = tx_log.send(ServerLogMsg::ExecEnd { id, exit_code }).await;
+        });
+    }
+
+    pub fn write(&self, data: &[u8]) {
+        if let Ok(mut w) = self.writer.lock() {
+            let _ = w.write_all(data);
+            let _ = w.flush();
+        }
+    }
+
+    pub fn resize(&self, cols: u16, rows: u16) {
+        if let Ok(m) = self.master.lock() {
+            let _ = m.resize(PtySize {
+                rows,
+                cols,
+                pixel_width: 0,
+                pixel_height: 0,
+            });
+        }
+    }
+}
+
+impl Drop for PtySession {
+    fn drop(&mut self) {
+        // The shell would otherwise outlive the client (it keeps the PTY open)
+        let _ = self.child.kill();
+    }
+}
+
+struct LogInterpreter {
+    tx_log: mpsc::Sender<ServerLogMsg>,
+    capturing: bool,
+    buffer: String,
+    /// Peak RSS in KiB seen by [`sample_rss`] since the command started, 0 if unknown
+    max_rss: Arc<AtomicU64>,
+    limits: LogLimits,
+    /// Bytes logged for the current command and the whole session
+    command_bytes: u64,
+    session_bytes: u64,
+    /// Bytes of the current command's output left out of the log
+    dropped: u64,
+    clipboard: ClipboardPolicy,
+    /// Last title sent, as prompts tend to set it again before every command
+    title: String,
+}
+
+impl LogInterpreter {
+    fn new(
+        tx_log: mpsc::Sender<ServerLogMsg>,
+        max_rss: Arc<AtomicU64>,
+        limits: LogLimits,
+        clipboard: ClipboardPolicy,
+    ) -> Self {
+        Self {
+            tx_log,
+            capturing: false,
+            buffer: String::new(),
+            max_rss,
+            limits,
+            command_bytes: 0,
+            session_bytes: 0,
+            dropped: 0,
+            clipboard,
+            title: String::new(),
+        }
+    }
+
+    /// Account for `len` bytes of output, false if they're over a limit and must be dropped
+    fn within_quota(&mut self, len: u64) -> bool {
+        if self.command_bytes + len > self.limits.per_command
+            || self.session_bytes + len > self.limits.per_session
+        {
+            self.dropped += len;
+            return false;
+        }
+        self.command_bytes += len;
+        self.session_bytes += len;
+        true
+    }
+
+    fn flush(&mut self) {
+        if !self.buffer.is_empty() {
+            let _ = self.tx_log.blocking_send(ServerLogMsg::LogOutput {
+                data: std::mem::take(&mut self.buffer),
+            });
+        }
+    }
+}
+
+impl shell_markers::Perform for LogInterpreter {
+    fn marker(&mut self, marker: Marker) {
+        match marker {
+            Marker::Start { user, host, cwd } => {
+                self.capturing = true;
+                self.buffer.clear();
+                self.max_rss.store(0, Ordering::Relaxed);
+                self.command_bytes = 0;
+                self.dropped = 0;
+
+                let _ = self
+                    .tx_log
+                    .blocking_send(ServerLogMsg::LogStart { user, host, cwd });
+            }
+            Marker::End {
+                exit_code,
+                wall_ms,
+                cpu_ms,
+            } => {
+                // Flush pending buffer first
+                self.flush();
+
+                let max_rss_kb = match self.max_rss.load(Ordering::Relaxed) {
+                    0 => None,
+                    kb => Some(kb),
+                };
+
+                if self.dropped > 0 {
+                    let _ = self.tx_log.blocking_send(ServerLogMsg::Truncated {
+                        dropped_bytes: std::mem::take(&mut self.dropped),
+                    });
+                }
+
+                let _ = self.tx_log.blocking_send(ServerLogMsg::LogEnd {
+                    exit_code: exit_code.unwrap_or(0),
+                    wall_ms,
+                    cpu_ms,
+                    max_rss_kb,
+                });
+                self.capturing = false;
+            }
+            // The client knows what it ran, and the prompt's directory shows up in the next START
+            Marker::Command(_) | Marker::Cwd(_) => {}
+        }
+    }
+
+    fn print(&mut self, c: char) {
+        if self.capturing && self.within_quota(c.len_utf8() as u64) {
+            self.buffer.push(c);
+        }
+    }
+
+    fn execute(&mut self, byte: u8) {
+        if self.capturing {
+            // Handle basic control chars that are useful in logs: \n, \t, \r
+            if byte == b'\n' {
+                if self.within_quota(1) {
+                    self.buffer.push('\n');
+                }
+            } else if byte == b'\t' {
+                if self.within_quota(1) {
+                    self.buffer.push('\t');
+                }
+            } else if byte == b'\r' {
+                 // Ignore CR or handle it? Usually \r\n is processed.
+                 // For logs, simple \n is usually enough. 
+                 // If we push \r, it might mess up some simple log viewers, but let's keep it safe or ignore?
+                 // Let's ignore it to keep logs clean text.
+            }
+        }
+    }
+
+    fn osc(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        // OSC 0;<title> sets the icon name too, OSC 2;<title> only the title; a title can
+        // contain the separator
+        if let [b"0" | b"2", title @ ..] = params {
+            let text = title
+                .iter()
+                .map(|p| String::from_utf8_lossy(p))
+                .collect::<Vec<_>>()
+                .join(";");
+            if text != self.title {
+                self.title = text.clone();
+                let _ = self.tx_log.blocking_send(ServerLogMsg::Title { text });
+            }
+            return;
+        }
+
+        // OSC 52;<selection>;<base64>, "?" instead of data asks to read the clipboard, which we
+        // never answer
+        if params.first() == Some(&&b"52"[..]) && self.clipboard == ClipboardPolicy::Forward {
+            if let Some(data) = params.get(2).and_then(|data| STANDARD.decode(data).ok()) {
+                let _ = self.tx_log.blocking_send(ServerLogMsg::Clipboard {
+                    data: String::from_utf8_lossy(&data).into_owned(),
+                });
+            }
+        }
+    }
+}
+
+async fn run_piped(
+    mut cmd: tokio::process::Command,
+    id: &str,
+    tx_log: &mpsc::Sender<ServerLogMsg>,
+) -> std::io::Result<ExitStatus> {
+    let mut child = cmd.spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+
+    tokio::join!(
+        forward_pipe(stdout, ExecStream::Stdout, id, tx_log),
+        forward_pipe(stderr, ExecStream::Stderr, id, tx_log),
+    );
+    child.wait().await
+}
+
+async fn forward_pipe(
+    mut pipe: impl AsyncRead + Unpin,
+    stream: ExecStream,
+    id: &str,
+    tx_log: &mpsc::Sender<ServerLogMsg>,
+) {
+    let mut buf = [0u8; 2048];
+    let mut pending = Vec::new();
+
+    loop {
+        let n = pipe.read(&mut buf).await.unwrap_or(0);
+        pending.extend_from_slice(&buf[..n]);
+
+        // Hold back a UTF-8 sequence split across reads, unless the pipe is done
+        let keep = match std::str::from_utf8(&pending) {
+            Err(e) if n > 0 && e.error_len().is_none() => pending.len() - e.valid_up_to(),
+            _ => 0,
+        };
+        let rest = pending.split_off(pending.len() - keep);
+        if !pending.is_empty() {
+            let _ = tx_log
+                .send(ServerLogMsg::ExecOutput {
+                    id: id.to_string(),
+                    stream,
+                    data: String::from_utf8_lossy(&pending).into_owned(),
+                })
+                .await;
+        }
+        pending = rest;
+
+        if n == 0 {
+            break;
+        }
+    }
+}
+
+/// Removes OSC 52 sequences from the raw terminal stream, including ones split across reads
+#[derive(Default)]
+struct Osc52Filter {
+    state: Osc52State,
+}
+
+#[derive(Default, Clone, Copy)]
+enum Osc52State {
+    #[default]
+    Normal,
+    /// The first `n` bytes of [`OSC52_PREFIX`] were seen and are held back
+    Prefix(usize),
+    /// Inside a sequence being dropped
+    Swallow,
+    /// Saw ESC inside the sequence, `\` would terminate it
+    SwallowEsc,
+}
+
+const OSC52_PREFIX: &[u8] = b"\x1b]52;";
+
+impl Osc52Filter {
+    fn filter(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len());
+        let mut i = 0;
+
+        while i < data.len() {
+            let byte = data[i];
+            match self.state {
+                Osc52State::Normal => {
+                    if byte == OSC52_PREFIX[0] {
+                        self.state = Osc52State::Prefix(1);
+                    } else {
+                        out.push(byte);
+                    }
+                }
+                Osc52State::Prefix(n) => {
+                    if byte == OSC52_PREFIX[n] {
+                        self.state = if n + 1 == OSC52_PREFIX.len() {
+                            Osc52State::Swallow
+                        } else {
+                            Osc52State::Prefix(n + 1)
+                        };
+                    } else {
+                        // Not OSC 52 after all: release what was held and look at this byte again
+                        out.extend_from_slice(&OSC52_PREFIX[..n]);
+                        self.state = Osc52State::Normal;
+                        continue;
+                    }
+                }
+                Osc52State::Swallow => match byte {
+                    0x07 => self.state = Osc52State::Normal,
+                    0x1b => self.state = Osc52State::SwallowEsc,
+                    _ => {}
+                },
+                Osc52State::SwallowEsc => {
+                    self.state = Osc52State::Normal;
+                    // ESC followed by anything but `\` ends the OSC and starts a new sequence
+                    if byte != b'\\' {
+                        continue;
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        out
+    }
+}
+
+/// How often the foreground command's memory is sampled
+#[cfg(target_os = "linux")]
+const RSS_SAMPLE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Track the peak RSS of the PTY's foreground process group leader (the running command, or the
+/// first process of a pipeline) until the session is dropped
+#[cfg(target_os = "linux")]
+fn sample_rss(
+    master: std::sync::Weak<Mutex<Box<dyn MasterPty + Send>>>,
+    shell_pid: Option<u32>,
+    max_rss: Arc<AtomicU64>,
+) {
+    while let Some(master) = master.upgrade() {
+        let leader = master.lock().ok().and_then(|m| m.process_group_leader());
+        drop(master);
+
+        // At the prompt the shell itself is in the foreground
+        if let Some(pid) = leader.filter(|&pid| Some(pid as u32) != shell_pid) {
+            if let Some(kb) = peak_rss_kb(pid) {
+                max_rss.fetch_max(kb, Ordering::Relaxed);
+            }
+        }
+
+        thread::sleep(RSS_SAMPLE_INTERVAL);
+    }
+}
+
+/// `VmHWM` (peak RSS) of a process in KiB
+#[cfg(target_os = "linux")]
+fn peak_rss_kb(pid: i32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
@@ -0,0 +1,231 @@
+//! Time-limited, revocable share links for a single session
+//!
+//! A share token is `base64url(payload).base64url(hmac_sha256(payload))`. The signature stops
+//! anyone from forging links; the in-memory store is what makes them revocable.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::sync::watch;
+
+use crate::auth::AuthUser;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What a share token grants
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ShareGrant {
+    /// Share id, used to revoke it
+    pub id: String,
+    pub session: String,
+    /// Unix timestamp in seconds
+    pub expires_at: u64,
+    pub interactive: bool,
+}
+
+struct ShareRecord {
+    grant: ShareGrant,
+    created_by: String,
+    /// Dropped on revocation, which wakes every connection using the share
+    revoked: watch::Sender<()>,
+}
+
+pub struct ShareStore {
+    secret: Vec<u8>,
+    shares: Mutex<HashMap<String, ShareRecord>>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Signs with a random key. Shares only live in memory, so links die with the process anyway.
+impl Default for ShareStore {
+    fn default() -> Self {
+        let mut secret = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+
+        Self {
+            secret,
+            shares: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl ShareStore {
+    fn sign(&self, payload: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts any key size");
+        mac.update(payload);
+        mac
+    }
+
+    /// Mint a token for `session`, valid for `ttl`
+    pub fn create(
+        &self,
+        created_by: &str,
+        session: &str,
+        ttl: Duration,
+        interactive: bool,
+    ) -> (ShareGrant, String) {
+        let mut id = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut id);
+
+        let grant = ShareGrant {
+            id: URL_SAFE_NO_PAD.encode(id),
+            session: session.to_string(),
+            expires_at: now() + ttl.as_secs(),
+            interactive,
+        };
+
+        let payload = serde_json::to_vec(&grant).expect("grant serializes");
+        let signature = self.sign(&payload).finalize().into_bytes();
+        let token = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(signature)
+        );
+
+        let mut shares = self.shares.lock().unwrap();
+        let current = now();
+        shares.retain(|_, record| record.grant.expires_at > current);
+        shares.insert(
+            grant.id.clone(),
+            ShareRecord {
+                grant: grant.clone(),
+                created_by: created_by.to_string(),
+                revoked: watch::channel(()).0,
+            },
+        );
+
+        (grant, token)
+    }
+
+    /// Check a token's signature, expiry and revocation. Returns the grant and a receiver that
+    /// errors once the share is revoked.
+    pub fn verify(&self, token: &str) -> Result<(ShareGrant, watch::Receiver<()>), &'static str> {
+        let (payload, signature) = token.split_once('.').ok_or("Malformed share token")?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| "Malformed share token")?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| "Malformed share token")?;
+
+        self.sign(&payload)
+            .verify_slice(&signature)
+            .map_err(|_| "Bad share token signature")?;
+
+        let grant: ShareGrant = serde_json::from_slice(&payload).map_err(|_| "Malformed share token")?;
+        if grant.expires_at <= now() {
+            return Err("Share link expired");
+        }
+
+        let shares = self.shares.lock().unwrap();
+        let record = shares.get(&grant.id).ok_or("Share link revoked")?;
+        Ok((grant, record.revoked.subscribe()))
+    }
+
+    /// Revoke a share. Only its creator and admins may do so.
+    pub fn revoke(&self, id: &str, user: &AuthUser) -> Result<(), StatusCode> {
+        let mut shares = self.shares.lock().unwrap();
+        let record = shares.get(id).ok_or(StatusCode::NOT_FOUND)?;
+        if !user.is_admin() && record.created_by != user.name {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        shares.remove(id);
+        Ok(())
+    }
+}
+
+/// Resolves when the share expires or is revoked
+pub async fn share_ended(grant: ShareGrant, mut revoked: watch::Receiver<()>) {
+    let remaining = Duration::from_secs(grant.expires_at.saturating_sub(now()));
+    tokio::select! {
+        _ = tokio::time::sleep(remaining) => {}
+        // Only errors (sender dropped) on revocation, nothing is ever sent
+        _ = revoked.changed() => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn user(name: &str) -> AuthUser {
+        AuthUser::new(name.to_string(), Role::Operator, Vec::new(), Vec::new())
+    }
+
+    #[test]
+    fn tokens_verify_to_their_grant() {
+        let store = ShareStore::default();
+        let (grant, token) = store.create("alice", "7", HOUR, true);
+
+        let (verified, _) = store.verify(&token).unwrap();
+        assert_eq!(verified.id, grant.id);
+        assert_eq!(verified.session, "7");
+        assert!(verified.interactive);
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        let store = ShareStore::default();
+        let (grant, token) = store.create("alice", "7", HOUR, false);
+        let (_, signature) = token.split_once('.').unwrap();
+
+        // Same signature over a grant for another session, now interactive
+        let forged = ShareGrant {
+            session: "8".to_string(),
+            interactive: true,
+            ..grant
+        };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap());
+        let forged = format!("{}.{}", payload, signature);
+        assert_eq!(store.verify(&forged).unwrap_err(), "Bad share token signature");
+
+        assert_eq!(store.verify("garbage").unwrap_err(), "Malformed share token");
+        assert_eq!(store.verify("a.!!").unwrap_err(), "Malformed share token");
+    }
+
+    #[test]
+    fn tokens_from_another_process_are_rejected() {
+        let (_, token) = ShareStore::default().create("alice", "7", HOUR, false);
+        let store = ShareStore::default();
+        assert_eq!(store.verify(&token).unwrap_err(), "Bad share token signature");
+    }
+
+    #[test]
+    fn expired_tokens_are_rejected() {
+        let store = ShareStore::default();
+        let (_, token) = store.create("alice", "7", Duration::ZERO, false);
+        assert_eq!(store.verify(&token).unwrap_err(), "Share link expired");
+    }
+
+    #[test]
+    fn revoked_tokens_are_rejected() {
+        let store = ShareStore::default();
+        let (grant, token) = store.create("alice", "7", HOUR, false);
+        let (_, revoked) = store.verify(&token).unwrap();
+
+        assert_eq!(store.revoke(&grant.id, &user("bob")), Err(StatusCode::FORBIDDEN));
+        store.revoke(&grant.id, &user("alice")).unwrap();
+
+        assert_eq!(store.verify(&token).unwrap_err(), "Share link revoked");
+        // Connections already using the share see the sender go away
+        assert!(revoked.has_changed().is_err());
+    }
+}
@@ -0,0 +1,111 @@
+//! Log output, plus OpenTelemetry trace export (OTLP) when built with the `otel` feature
+
+use tracing::Span;
+use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+use crate::ServerLogMsg;
+
+/// Install the global subscriber. Traces are exported to `otlp_endpoint` if one is given.
+pub fn init(otlp_endpoint: Option<&str>) {
+    let registry = tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO));
+
+    #[cfg(feature = "otel")]
+    {
+        let mut error = None;
+        let otel = otlp_endpoint.and_then(|endpoint| match otlp_layer(endpoint) {
+            // WS message spans are at debug level
+            Ok(layer) => Some(layer.with_filter(LevelFilter::DEBUG)),
+            Err(e) => {
+                error = Some(e);
+                None
+            }
+        });
+        registry.with(otel).init();
+
+        match (error, otlp_endpoint) {
+            (Some(e), _) => tracing::error!("Failed to set up OTLP export: {}", e),
+            (None, Some(endpoint)) => tracing::info!("Exporting traces to {}", endpoint),
+            (None, None) => {}
+        }
+    }
+
+    #[cfg(not(feature = "otel"))]
+    {
+        registry.init();
+        if otlp_endpoint.is_some() {
+            tracing::warn!("Built without the otel feature, not exporting traces");
+        }
+    }
+}
+
+#[cfg(feature = "otel")]
+fn otlp_layer<S>(endpoint: &str) -> Result<impl Layer<S>, opentelemetry::trace::TraceError>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::{trace, Resource};
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .with_trace_config(
+            trace::config().with_resource(Resource::new([KeyValue::new("service.name", "remote-shell")])),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Opens a span per executed command from the START/END markers, as a child of the session span
+/// current when [`CommandSpans::observe`] is called
+#[derive(Default)]
+pub struct CommandSpans {
+    current: Option<Span>,
+}
+
+impl CommandSpans {
+    pub fn observe(&mut self, msg: &ServerLogMsg) {
+        match msg {
+            ServerLogMsg::LogStart { user, host, cwd } => {
+                self.current = Some(tracing::info_span!(
+                    "command",
+                    shell.user = %user,
+                    shell.host = %host,
+                    shell.cwd = %cwd,
+                    exit_code = tracing::field::Empty,
+                    wall_ms = tracing::field::Empty,
+                    cpu_ms = tracing::field::Empty,
+                    max_rss_kb = tracing::field::Empty,
+                    truncated_bytes = tracing::field::Empty,
+                ));
+            }
+            ServerLogMsg::Truncated { dropped_bytes } => {
+                if let Some(span) = &self.current {
+                    span.record("truncated_bytes", dropped_bytes);
+                }
+            }
+            ServerLogMsg::LogEnd {
+                exit_code,
+                wall_ms,
+                cpu_ms,
+                max_rss_kb,
+            } => {
+                // Dropping the span ends it
+                if let Some(span) = self.current.take() {
+                    span.record("exit_code", exit_code);
+                    span.record("wall_ms", wall_ms);
+                    span.record("cpu_ms", cpu_ms);
+                    span.record("max_rss_kb", max_rss_kb);
+                }
+            }
+            ServerLogMsg::LogOutput { .. }
+            | ServerLogMsg::ExecOutput { .. }
+            | ServerLogMsg::ExecEnd { .. }
+            | ServerLogMsg::Clipboard { .. }
+            | ServerLogMsg::Title { .. } => {}
+        }
+    }
+}
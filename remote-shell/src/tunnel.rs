@@ -0,0 +1,337 @@
+//! Agent tunnel: one outbound WebSocket from an agent carrying many PTY sessions
+//!
+//! Control frames are JSON text messages ([`TunnelFrame`]). Terminal output travels as binary
+//! messages prefixed with the big-endian `u64` session id, so raw bytes don't get inflated by JSON.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::IntoResponse,
+};
+use futures::{sink::SinkExt, stream::StreamExt};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{
+    config::HostConfig, proxy::ProxyResponse, registry::Frame, AppState, ClientMsg, ServerLogMsg,
+};
+
+/// How long the hub waits for an agent to answer a proxied request
+const PROXY_TIMEOUT: Duration = Duration::from_secs(35);
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TunnelFrame {
+    /// agent -> hub, must be the first frame on the tunnel
+    Register {
+        id: String,
+        /// Checked against the host's token in the hub config
+        token: Option<String>,
+        #[serde(default)]
+        labels: HashMap<String, String>,
+    },
+    /// hub -> agent: start a session. Parameters are resolved against the agent's own config.
+    Open {
+        session: u64,
+        cwd: Option<String>,
+        profile: Option<String>,
+        namespace: Option<String>,
+        pod: Option<String>,
+        /// tmux session name, used by tmux profiles
+        #[serde(default)]
+        tmux: Option<String>,
+    },
+    /// hub -> agent: a browser message for a session
+    Client { session: u64, msg: ClientMsg },
+    /// agent -> hub: a parsed log message from a session
+    Log { session: u64, msg: ServerLogMsg },
+    /// Either direction: the session is gone (shell exited or browser disconnected)
+    Close { session: u64 },
+    /// hub -> agent: GET `path` from `localhost:<port>` on the agent's machine
+    ProxyRequest { request: u64, port: u16, path: String },
+    /// agent -> hub: the answer to a `ProxyRequest`
+    ProxyResponse {
+        request: u64,
+        #[serde(default)]
+        response: Option<ProxyResponse>,
+        #[serde(default)]
+        error: Option<String>,
+    },
+}
+
+/// Prefix terminal output with its session id
+pub fn encode_output(session: u64, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(8 + data.len());
+    frame.extend_from_slice(&session.to_be_bytes());
+    frame.extend_from_slice(data);
+    frame
+}
+
+/// Split a binary tunnel message into session id and terminal output
+pub fn decode_output(frame: &[u8]) -> Option<(u64, &[u8])> {
+    let (id, data) = frame.split_at_checked(8)?;
+    Some((u64::from_be_bytes(id.try_into().ok()?), data))
+}
+
+/// A connected agent, as seen by the hub
+pub struct Agent {
+    pub id: String,
+    pub labels: HashMap<String, String>,
+    tx: mpsc::UnboundedSender<Message>,
+    sessions: Mutex<HashMap<u64, mpsc::Sender<Frame>>>,
+    next_session: AtomicU64,
+    /// Proxied requests waiting for the agent's answer
+    proxies: Mutex<HashMap<u64, oneshot::Sender<Result<ProxyResponse, String>>>>,
+    next_request: AtomicU64,
+}
+
+impl Agent {
+    fn send(&self, frame: &TunnelFrame) {
+        if let Ok(json) = serde_json::to_string(frame) {
+            let _ = self.tx.send(Message::Text(json));
+        }
+    }
+
+    /// Ask the agent to start a session; its output arrives on the returned receiver
+    pub fn open_session(
+        &self,
+        cwd: Option<String>,
+        profile: Option<String>,
+        namespace: Option<String>,
+        pod: Option<String>,
+        tmux: Option<String>,
+    ) -> (u64, mpsc::Receiver<Frame>) {
+        let session = self.next_session.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::channel(32);
+        self.sessions.lock().unwrap().insert(session, tx);
+
+        self.send(&TunnelFrame::Open {
+            session,
+            cwd,
+            profile,
+            namespace,
+            pod,
+            tmux,
+        });
+
+        (session, rx)
+    }
+
+    pub fn session_count(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    pub fn client_msg(&self, session: u64, msg: ClientMsg) {
+        self.send(&TunnelFrame::Client { session, msg });
+    }
+
+    pub fn close_session(&self, session: u64) {
+        if self.sessions.lock().unwrap().remove(&session).is_some() {
+            self.send(&TunnelFrame::Close { session });
+        }
+    }
+
+    /// Have the agent fetch `path` from one of its local ports
+    pub async fn proxy(&self, port: u16, path: String) -> Result<ProxyResponse, String> {
+        let request = self.next_request.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.proxies.lock().unwrap().insert(request, tx);
+
+        self.send(&TunnelFrame::ProxyRequest {
+            request,
+            port,
+            path,
+        });
+
+        let result = tokio::time::timeout(PROXY_TIMEOUT, rx).await;
+        self.proxies.lock().unwrap().remove(&request);
+        match result {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err("Agent disconnected".to_string()),
+            Err(_) => Err("Agent did not answer in time".to_string()),
+        }
+    }
+
+    async fn deliver(&self, session: u64, output: Frame) {
+        let tx = self.sessions.lock().unwrap().get(&session).cloned();
+        if let Some(tx) = tx {
+            let _ = tx.send(output).await;
+        }
+    }
+}
+
+/// Agents currently connected to this hub, by id
+#[derive(Default)]
+pub struct AgentRegistry {
+    agents: Mutex<HashMap<String, Arc<Agent>>>,
+}
+
+impl AgentRegistry {
+    pub fn get(&self, id: &str) -> Option<Arc<Agent>> {
+        self.agents.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn list(&self) -> Vec<Arc<Agent>> {
+        let mut agents: Vec<_> = self.agents.lock().unwrap().values().cloned().collect();
+        agents.sort_by(|a, b| a.id.cmp(&b.id));
+        agents
+    }
+}
+
+/// Whether an agent registering as `host` may connect. A host without a token is no better
+/// protected than an unlisted one, so it's only accepted along with those.
+fn host_token_matches(host: &HostConfig, token: Option<&str>, allow_unregistered: bool) -> bool {
+    match (&host.token, token) {
+        (Some(expected), Some(token)) => expected.as_bytes().ct_eq(token.as_bytes()).into(),
+        (Some(_), None) => false,
+        (None, _) => allow_unregistered,
+    }
+}
+
+pub async fn agent_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_agent(socket, state))
+}
+
+async fn handle_agent(socket: WebSocket, state: AppState) {
+    let registry = state.agents.clone();
+    let (mut sender, mut receiver) = socket.split();
+
+    let (id, token, mut labels) = match receiver.next().await {
+        Some(Ok(Message::Text(text))) => match serde_json::from_str::<TunnelFrame>(&text) {
+            Ok(TunnelFrame::Register { id, token, labels }) => (id, token, labels),
+            _ => {
+                tracing::warn!("Agent did not register, closing tunnel");
+                return;
+            }
+        },
+        _ => return,
+    };
+
+    match state.config.hosts.get(&id) {
+        Some(host) => {
+            if !host_token_matches(host, token.as_deref(), state.config.allow_unregistered_agents)
+            {
+                tracing::warn!("Agent {} presented a bad token, closing tunnel", id);
+                return;
+            }
+            // Labels from the hub config win over what the agent claims
+            labels.extend(host.labels.clone());
+        }
+        None if state.config.allow_unregistered_agents => {}
+        None => {
+            tracing::warn!("Agent {} is not a configured host, closing tunnel", id);
+            return;
+        }
+    }
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+    let agent = Arc::new(Agent {
+        id: id.clone(),
+        labels,
+        tx,
+        sessions: Mutex::new(HashMap::new()),
+        next_session: AtomicU64::new(1),
+        proxies: Mutex::new(HashMap::new()),
+        next_request: AtomicU64::new(1),
+    });
+
+    // A reconnecting agent replaces its stale entry
+    registry
+        .agents
+        .lock()
+        .unwrap()
+        .insert(id.clone(), agent.clone());
+    tracing::info!("Agent {} registered", id);
+
+    let send_task = tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            if sender.send(msg).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(msg)) = receiver.next().await {
+        match msg {
+            Message::Binary(frame) => {
+                if let Some((session, data)) = decode_output(&frame) {
+                    agent
+                        .deliver(session, Frame::Output(data.to_vec()))
+                        .await;
+                }
+            }
+            Message::Text(text) => match serde_json::from_str::<TunnelFrame>(&text) {
+                Ok(TunnelFrame::Log { session, msg }) => {
+                    agent.deliver(session, Frame::Log(msg)).await;
+                }
+                Ok(TunnelFrame::Close { session }) => {
+                    // Dropping the sender ends the browser side of the session
+                    agent.sessions.lock().unwrap().remove(&session);
+                }
+                Ok(TunnelFrame::ProxyResponse {
+                    request,
+                    response,
+                    error,
+                }) => {
+                    let result = response.ok_or_else(|| error.unwrap_or_default());
+                    if let Some(tx) = agent.proxies.lock().unwrap().remove(&request) {
+                        let _ = tx.send(result);
+                    }
+                }
+                Ok(frame) => tracing::warn!("Unexpected frame from agent {}: {:?}", id, frame),
+                Err(e) => tracing::warn!("Bad frame from agent {}: {}", id, e),
+            },
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+
+    send_task.abort();
+
+    // Only remove the entry if it is still ours (not a newer connection from the same agent)
+    let mut agents = registry.agents.lock().unwrap();
+    if agents.get(&id).is_some_and(|current| Arc::ptr_eq(current, &agent)) {
+        agents.remove(&id);
+    }
+    tracing::info!("Agent {} disconnected", id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(token: Option<&str>) -> HostConfig {
+        HostConfig {
+            token: token.map(str::to_string),
+            ..HostConfig::default()
+        }
+    }
+
+    #[test]
+    fn agents_must_present_the_hosts_token() {
+        let host = host(Some("secret"));
+        assert!(host_token_matches(&host, Some("secret"), false));
+        assert!(!host_token_matches(&host, Some("secreT"), true));
+        assert!(!host_token_matches(&host, Some("secret2"), true));
+        assert!(!host_token_matches(&host, None, true));
+    }
+
+    #[test]
+    fn hosts_without_a_token_count_as_unregistered() {
+        assert!(!host_token_matches(&host(None), Some("anything"), false));
+        assert!(!host_token_matches(&host(None), None, false));
+        assert!(host_token_matches(&host(None), None, true));
+    }
+}
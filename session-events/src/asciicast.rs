@@ -0,0 +1,122 @@
+//! asciinema `.cast` files (asciicast v2).
+//!
+//! Output and resizes map one to one. Command starts become markers (`"m"`) labelled with the
+//! command line; command ends and title changes have no equivalent and are left out.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Record, SessionEvent};
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Header {
+    version: u8,
+    width: u16,
+    height: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    env: HashMap<String, String>,
+}
+
+/// An event line: `[time, code, data]`
+type Line = (f64, String, String);
+
+pub fn write<W: Write>(records: &[Record], mut writer: W) -> io::Result<()> {
+    let (width, height, timestamp, shell) = match records.first().map(|r| &r.event) {
+        Some(SessionEvent::SessionStarted {
+            cols,
+            rows,
+            started_at,
+            shell,
+            ..
+        }) => (*cols, *rows, Some(*started_at), shell.clone()),
+        _ => (80, 24, None, None),
+    };
+    let header = Header {
+        version: 2,
+        width,
+        height,
+        timestamp,
+        env: shell.map(|shell| ("SHELL".to_string(), shell)).into_iter().collect(),
+    };
+    serde_json::to_writer(&mut writer, &header)?;
+    writer.write_all(b"\n")?;
+
+    for record in records {
+        let (code, data) = match &record.event {
+            SessionEvent::Output { data } => ("o", data.clone()),
+            SessionEvent::Resize { cols, rows } => ("r", format!("{}x{}", cols, rows)),
+            SessionEvent::CommandStarted { command, .. } => ("m", command.clone().unwrap_or_default()),
+            _ => continue,
+        };
+        let line: Line = (record.time, code.to_string(), data);
+        serde_json::to_writer(&mut writer, &line)?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()
+}
+
+pub fn read<R: BufRead>(reader: R) -> io::Result<Vec<Record>> {
+    let invalid = |e: serde_json::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+    let mut lines = reader.lines();
+
+    let header: Header = match lines.next() {
+        Some(line) => serde_json::from_str(&line?).map_err(invalid)?,
+        None => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Empty cast file")),
+    };
+    if header.version != 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsupported asciicast version {}", header.version),
+        ));
+    }
+
+    let mut records = vec![Record {
+        time: 0.0,
+        event: SessionEvent::SessionStarted {
+            cols: header.width,
+            rows: header.height,
+            started_at: header.timestamp.unwrap_or(0),
+            shell: header.env.get("SHELL").cloned(),
+            cwd: None,
+        },
+    }];
+
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (time, code, data): Line = serde_json::from_str(&line).map_err(invalid)?;
+        let event = match code.as_str() {
+            "o" => SessionEvent::Output { data },
+            "r" => match parse_size(&data) {
+                Some((cols, rows)) => SessionEvent::Resize { cols, rows },
+                None => continue,
+            },
+            "m" => SessionEvent::CommandStarted {
+                command: Some(data).filter(|label| !label.is_empty()),
+                user: String::new(),
+                host: String::new(),
+                cwd: String::new(),
+            },
+            // Input ("i") isn't part of the event model
+            _ => continue,
+        };
+        records.push(Record { time, event });
+    }
+
+    Ok(records)
+}
+
+/// `"<cols>x<rows>"`
+fn parse_size(size: &str) -> Option<(u16, u16)> {
+    let (cols, rows) = size.split_once('x')?;
+    Some((cols.parse().ok()?, rows.parse().ok()?))
+}
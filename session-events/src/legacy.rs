@@ -0,0 +1,192 @@
+//! pty-bash-hook's text log (`shell_commands.log`):
+//!
+//! ```text
+//! === Command Started ===
+//! Command: ls
+//! Time: SystemTime { tv_sec: 1760000000, tv_nsec: 0 }
+//! --- Output ---
+//! ...
+//! --- End Output ---
+//! Exit Code: 0
+//! Duration: 12.5ms
+//! === Command Ended ===
+//!
+//! [PWD] /tmp
+//! ```
+//!
+//! The log only has cleaned command output, so converting to it drops everything between
+//! commands, and converting from it gives output without colors or cursor movement.
+
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    time::{Duration, UNIX_EPOCH},
+};
+
+use crate::{plain_text, Record, SessionEvent};
+
+pub fn write<W: Write>(records: &[Record], mut writer: W) -> io::Result<()> {
+    let started_at = match records.first().map(|r| &r.event) {
+        Some(SessionEvent::SessionStarted { started_at, .. }) => *started_at as f64,
+        _ => 0.0,
+    };
+
+    // Start time and raw output of the command being written
+    let mut current: Option<(f64, String)> = None;
+    for record in records {
+        match &record.event {
+            SessionEvent::CommandStarted { command, .. } => {
+                let time = UNIX_EPOCH + Duration::from_secs_f64(started_at + record.time);
+                writeln!(writer, "\n=== Command Started ===")?;
+                writeln!(writer, "Command: {}", command.as_deref().unwrap_or_default())?;
+                writeln!(writer, "Time: {:?}", time)?;
+                current = Some((record.time, String::new()));
+            }
+            SessionEvent::Output { data } => {
+                if let Some((_, output)) = &mut current {
+                    output.push_str(data);
+                }
+            }
+            SessionEvent::CommandEnded {
+                exit_code, wall_ms, ..
+            } => {
+                let Some((start, output)) = current.take() else {
+                    continue;
+                };
+                let duration = match wall_ms {
+                    Some(ms) => Duration::from_millis(*ms),
+                    None => Duration::from_secs_f64((record.time - start).max(0.0)),
+                };
+                writeln!(writer, "--- Output ---")?;
+                write!(writer, "{}", plain_text(&output))?;
+                writeln!(writer, "\n--- End Output ---")?;
+                match exit_code {
+                    Some(code) => writeln!(writer, "Exit Code: {}", code)?,
+                    None => writeln!(writer, "Exit Code: unknown")?,
+                }
+                writeln!(writer, "Duration: {:?}", duration)?;
+                writeln!(writer, "=== Command Ended ===\n")?;
+            }
+            SessionEvent::SessionStarted { .. }
+            | SessionEvent::Resize { .. }
+            | SessionEvent::TitleChanged { .. } => {}
+        }
+    }
+
+    writer.flush()
+}
+
+/// Parse a text log. Times come from the `Time:` lines when they are in the Unix
+/// `SystemTime { tv_sec: .. }` form, otherwise every command is placed at the start.
+pub fn read(text: &str) -> Vec<Record> {
+    let mut records = Vec::new();
+    let mut started_at = None;
+
+    // Fields of the command being read
+    let mut command = None;
+    let mut time = 0.0;
+    let mut output = String::new();
+    let mut in_output = false;
+    let mut exit_code = None;
+    let mut wall_ms = None;
+    let mut cwd = String::new();
+
+    for line in text.lines() {
+        // Inside the output everything but the end marker is output
+        if in_output {
+            if line == "--- End Output ---" {
+                // The log adds a newline of its own after the output
+                if output.ends_with("\r\n") {
+                    output.truncate(output.len() - 2);
+                }
+                in_output = false;
+            } else {
+                let _ = write!(output, "{}\r\n", line);
+            }
+            continue;
+        }
+
+        if line == "=== Command Started ===" {
+            command = None;
+            time = 0.0;
+            output.clear();
+            exit_code = None;
+            wall_ms = None;
+        } else if let Some(rest) = line.strip_prefix("Command: ") {
+            command = Some(rest.to_string());
+        } else if let Some(rest) = line.strip_prefix("Time: ") {
+            if let Some(secs) = parse_system_time(rest) {
+                let base = *started_at.get_or_insert(secs);
+                time = secs.saturating_sub(base) as f64;
+            }
+        } else if line == "--- Output ---" {
+            output.clear();
+            in_output = true;
+        } else if let Some(rest) = line.strip_prefix("Exit Code: ") {
+            exit_code = rest.trim().parse().ok();
+        } else if let Some(rest) = line.strip_prefix("Duration: ") {
+            wall_ms = parse_duration(rest.trim()).map(|d| d.as_millis() as u64);
+        } else if line == "=== Command Ended ===" {
+            records.push(Record {
+                time,
+                event: SessionEvent::CommandStarted {
+                    command: command.take(),
+                    user: String::new(),
+                    host: String::new(),
+                    cwd: cwd.clone(),
+                },
+            });
+            let end = time + wall_ms.unwrap_or(0) as f64 / 1000.0;
+            if !output.is_empty() {
+                records.push(Record {
+                    time,
+                    event: SessionEvent::Output {
+                        data: std::mem::take(&mut output),
+                    },
+                });
+            }
+            records.push(Record {
+                time: end,
+                event: SessionEvent::CommandEnded {
+                    exit_code,
+                    wall_ms,
+                    cpu_ms: None,
+                },
+            });
+        } else if let Some(rest) = line.strip_prefix("[PWD] ") {
+            cwd = rest.to_string();
+        }
+    }
+
+    records.insert(
+        0,
+        Record {
+            time: 0.0,
+            event: SessionEvent::SessionStarted {
+                cols: 80,
+                rows: 24,
+                started_at: started_at.unwrap_or(0),
+                shell: None,
+                cwd: None,
+            },
+        },
+    );
+    records
+}
+
+/// `SystemTime { tv_sec: 1760000000, tv_nsec: 0 }`, as Unix seconds
+fn parse_system_time(s: &str) -> Option<u64> {
+    let rest = &s[s.find("tv_sec: ")? + "tv_sec: ".len()..];
+    let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+/// A `Duration` in its `Debug` form (`1.5s`, `250ms`, `12µs`, `5ns`)
+fn parse_duration(s: &str) -> Option<Duration> {
+    let units = [("ns", 1e-9), ("µs", 1e-6), ("ms", 1e-3), ("s", 1.0)];
+    let (value, scale) = units
+        .iter()
+        .find_map(|(unit, scale)| Some((s.strip_suffix(unit)?, *scale)))?;
+    let value: f64 = value.parse().ok()?;
+    Duration::try_from_secs_f64(value * scale).ok()
+}
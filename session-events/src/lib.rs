@@ -0,0 +1,310 @@
+//! One record format for terminal sessions, written by both pty-bash-hook and remote-shell.
+//!
+//! A recording is JSON lines, one [`Record`] each, starting with a
+//! [`SessionEvent::SessionStarted`]:
+//!
+//! ```text
+//! {"time":0.0,"type":"sessionStarted","cols":80,"rows":24,"startedAt":1760000000}
+//! {"time":0.31,"type":"output","data":"$ "}
+//! {"time":2.5,"type":"commandStarted","command":"ls","user":"alice","host":"box","cwd":"/tmp"}
+//! ```
+//!
+//! [`asciicast`] and [`legacy`] convert to and from asciinema `.cast` files and pty-bash-hook's
+//! old text log.
+
+pub mod asciicast;
+pub mod legacy;
+
+use std::{
+    io::{self, BufRead, Write},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SessionEvent {
+    #[serde(rename_all = "camelCase")]
+    SessionStarted {
+        cols: u16,
+        rows: u16,
+        /// Unix timestamp in seconds
+        started_at: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        shell: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cwd: Option<String>,
+    },
+    #[serde(rename_all = "camelCase")]
+    CommandStarted {
+        /// The command line, when the shell reported it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        command: Option<String>,
+        #[serde(default)]
+        user: String,
+        #[serde(default)]
+        host: String,
+        #[serde(default)]
+        cwd: String,
+    },
+    #[serde(rename_all = "camelCase")]
+    CommandEnded {
+        exit_code: Option<i32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        wall_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cpu_ms: Option<u64>,
+    },
+    /// Raw terminal output, escape sequences included
+    Output { data: String },
+    Resize { cols: u16, rows: u16 },
+    /// The window title was set (OSC 0/2)
+    TitleChanged { title: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Record {
+    /// Seconds since the session started
+    pub time: f64,
+    #[serde(flatten)]
+    pub event: SessionEvent,
+}
+
+/// Unix timestamp in seconds
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Writes a recording as events happen, timing them from its creation
+pub struct Recorder<W: Write> {
+    writer: W,
+    started: Instant,
+    /// A UTF-8 sequence split across output chunks, held until the rest arrives
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Start a recording with its [`SessionEvent::SessionStarted`]
+    pub fn new(
+        writer: W,
+        cols: u16,
+        rows: u16,
+        shell: Option<String>,
+        cwd: Option<String>,
+    ) -> io::Result<Self> {
+        let mut recorder = Self {
+            writer,
+            started: Instant::now(),
+            pending: Vec::new(),
+        };
+        recorder.record(SessionEvent::SessionStarted {
+            cols,
+            rows,
+            started_at: unix_now(),
+            shell,
+            cwd,
+        })?;
+        Ok(recorder)
+    }
+
+    pub fn record(&mut self, event: SessionEvent) -> io::Result<()> {
+        let record = Record {
+            time: self.started.elapsed().as_secs_f64(),
+            event,
+        };
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+        // A recording cut short by a crash should still have everything up to it
+        self.writer.flush()
+    }
+
+    /// Record a chunk of raw terminal output
+    pub fn output(&mut self, data: &[u8]) -> io::Result<()> {
+        self.pending.extend_from_slice(data);
+        let keep = match std::str::from_utf8(&self.pending) {
+            Err(e) if e.error_len().is_none() => self.pending.len() - e.valid_up_to(),
+            _ => 0,
+        };
+        let rest = self.pending.split_off(self.pending.len() - keep);
+        let chunk = std::mem::replace(&mut self.pending, rest);
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        self.record(SessionEvent::Output {
+            data: String::from_utf8_lossy(&chunk).into_owned(),
+        })
+    }
+}
+
+/// Read a recording. Blank lines are skipped.
+pub fn read<R: BufRead>(reader: R) -> io::Result<Vec<Record>> {
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Read a recording in whichever format it's in: session event JSON lines, an asciinema .cast
+/// file, or a pty-bash-hook text log
+pub fn parse(text: &str) -> io::Result<Vec<Record>> {
+    let first = text.lines().find(|line| !line.trim().is_empty()).unwrap_or_default();
+
+    // Both JSON formats start with an object: the cast header has a version, events a type
+    match serde_json::from_str::<serde_json::Value>(first) {
+        Ok(value) if value.get("version").is_some() => asciicast::read(text.as_bytes()),
+        Ok(value) if value.get("type").is_some() => read(text.as_bytes()),
+        _ => Ok(legacy::read(text)),
+    }
+}
+
+/// Terminal output as plain text: escape sequences removed, only newlines and tabs kept
+pub fn plain_text(output: &str) -> String {
+    struct Text(String);
+
+    impl shell_markers::Perform for Text {
+        fn marker(&mut self, _marker: shell_markers::Marker) {}
+
+        fn print(&mut self, c: char) {
+            self.0.push(c);
+        }
+
+        fn execute(&mut self, byte: u8) {
+            if byte == b'\n' || byte == b'\t' {
+                self.0.push(byte as char);
+            }
+        }
+    }
+
+    let mut text = Text(String::new());
+    shell_markers::MarkerParser::new().advance(&mut text, output.as_bytes());
+    text.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recordings_read_back_what_was_recorded() {
+        let mut buf = Vec::new();
+        let mut recorder = Recorder::new(&mut buf, 80, 24, Some("bash".to_string()), None).unwrap();
+        // "é" split across two chunks
+        recorder.output(b"caf\xc3").unwrap();
+        recorder.output(b"\xa9\r\n").unwrap();
+        recorder.record(SessionEvent::Resize { cols: 100, rows: 30 }).unwrap();
+        drop(recorder);
+
+        let events: Vec<_> = read(buf.as_slice())
+            .unwrap()
+            .into_iter()
+            .map(|record| record.event)
+            .collect();
+        assert!(matches!(
+            &events[0],
+            SessionEvent::SessionStarted { cols: 80, rows: 24, shell: Some(shell), .. }
+                if shell == "bash"
+        ));
+        assert_eq!(
+            events[1..],
+            [
+                SessionEvent::Output {
+                    data: "caf".to_string()
+                },
+                SessionEvent::Output {
+                    data: "é\r\n".to_string()
+                },
+                SessionEvent::Resize { cols: 100, rows: 30 },
+            ]
+        );
+    }
+
+    #[test]
+    fn cast_files_are_read() {
+        let text = "{\"version\":2,\"width\":120,\"height\":40,\"timestamp\":1760000000}\n\
+                    [0.5,\"o\",\"$ \"]\n\
+                    [1.0,\"m\",\"ls\"]\n";
+        let records = asciicast::read(text.as_bytes()).unwrap();
+        assert_eq!(
+            records[0].event,
+            SessionEvent::SessionStarted {
+                cols: 120,
+                rows: 40,
+                started_at: 1760000000,
+                shell: None,
+                cwd: None,
+            }
+        );
+        assert_eq!(records[1].time, 0.5);
+        assert!(matches!(
+            &records[2].event,
+            SessionEvent::CommandStarted { command: Some(command), .. } if command == "ls"
+        ));
+    }
+
+    #[test]
+    fn text_logs_are_read() {
+        let text = "\n=== Command Started ===\n\
+                    Command: ls\n\
+                    Time: SystemTime { tv_sec: 1760000000, tv_nsec: 0 }\n\
+                    --- Output ---\n\
+                    a.txt\n\
+                    --- End Output ---\n\
+                    Exit Code: 0\n\
+                    Duration: 12ms\n\
+                    === Command Ended ===\n";
+        let events: Vec<_> = legacy::read(text).into_iter().map(|record| record.event).collect();
+        assert!(matches!(
+            events[0],
+            SessionEvent::SessionStarted { started_at: 1760000000, .. }
+        ));
+        assert!(matches!(
+            &events[1],
+            SessionEvent::CommandStarted { command: Some(command), .. } if command == "ls"
+        ));
+        assert_eq!(
+            events[2],
+            SessionEvent::Output {
+                data: "a.txt".to_string()
+            }
+        );
+        assert_eq!(
+            events[3],
+            SessionEvent::CommandEnded {
+                exit_code: Some(0),
+                wall_ms: Some(12),
+                cpu_ms: None,
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_recordings_are_errors() {
+        let cast = "{\"version\":1,\"width\":80,\"height\":24}\n";
+        assert!(asciicast::read(cast.as_bytes()).is_err());
+        assert!(read("{\"type\":\"output\"}\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn parse_detects_the_format() {
+        let events = "{\"time\":0.0,\"type\":\"resize\",\"cols\":100,\"rows\":30}\n";
+        assert_eq!(parse(events).unwrap(), read(events.as_bytes()).unwrap());
+
+        let cast = "{\"version\":2,\"width\":120,\"height\":40}\n[0.5,\"o\",\"$ \"]\n";
+        assert_eq!(parse(cast).unwrap(), asciicast::read(cast.as_bytes()).unwrap());
+
+        let log = "\n=== Command Started ===\nCommand: ls\n=== Command Ended ===\n";
+        assert_eq!(parse(log).unwrap(), legacy::read(log));
+    }
+}
@@ -0,0 +1,248 @@
+//! `--to-text` and `--to-html`: the session's output as a document, escape sequences resolved to
+//! plain text or styled HTML
+
+use std::fmt::Write as _;
+
+use session_events::{plain_text, Record, SessionEvent};
+
+pub fn to_text(records: &[Record]) -> String {
+    // Joined first, an escape sequence may be split across events
+    let output: String = records
+        .iter()
+        .filter_map(|r| match &r.event {
+            SessionEvent::Output { data } => Some(data.as_str()),
+            _ => None,
+        })
+        .collect();
+    plain_text(&output)
+}
+
+pub fn to_html(records: &[Record], title: &str) -> String {
+    let mut html = Html::default();
+    let mut parser = vte::Parser::new();
+    let mut commands = 0;
+
+    for record in records {
+        match &record.event {
+            SessionEvent::Output { data } => parser.advance(&mut html, data.as_bytes()),
+            // Anchors, so a page can be linked at a command (#command-3)
+            SessionEvent::CommandStarted { command, .. } => {
+                commands += 1;
+                html.close_span();
+                let label = command.as_deref().map(escape).unwrap_or_default();
+                let _ = write!(
+                    html.body,
+                    "<a id=\"command-{}\" class=\"command\" title=\"{}\"></a>",
+                    commands, label
+                );
+                html.open_span();
+            }
+            _ => {}
+        }
+    }
+    html.close_span();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="UTF-8">
+<title>{title}</title>
+<style>
+body {{ margin: 0; background: #1e1e1e; }}
+pre {{ margin: 0; padding: 12px; color: #d4d4d4; font-family: Consolas, "Courier New", monospace; font-size: 13px; white-space: pre-wrap; }}
+a.command {{ display: block; border-top: 1px solid #333; }}
+</style>
+</head>
+<body>
+<pre>{body}</pre>
+</body>
+</html>
+"#,
+        title = escape(title),
+        body = html.body
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// xterm's default palette for the 16 basic colors
+const PALETTE: [&str; 16] = [
+    "#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5",
+    "#7f7f7f", "#ff0000", "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff",
+];
+
+/// A color from the 256-color palette
+fn indexed(n: u16) -> String {
+    match n {
+        0..=15 => PALETTE[n as usize].to_string(),
+        16..=231 => {
+            let n = n - 16;
+            let level = |v: u16| if v == 0 { 0 } else { 55 + v * 40 };
+            format!("#{:02x}{:02x}{:02x}", level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        _ => {
+            let gray = 8 + (n.min(255) - 232) * 10;
+            format!("#{:02x}{:02x}{:02x}", gray, gray, gray)
+        }
+    }
+}
+
+#[derive(Default, Clone, PartialEq)]
+struct Style {
+    fg: Option<String>,
+    bg: Option<String>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    inverse: bool,
+}
+
+impl Style {
+    fn css(&self) -> String {
+        let (fg, bg) = if self.inverse {
+            (
+                Some(self.bg.clone().unwrap_or_else(|| "#1e1e1e".to_string())),
+                Some(self.fg.clone().unwrap_or_else(|| "#d4d4d4".to_string())),
+            )
+        } else {
+            (self.fg.clone(), self.bg.clone())
+        };
+
+        let mut css = String::new();
+        if let Some(fg) = fg {
+            let _ = write!(css, "color:{};", fg);
+        }
+        if let Some(bg) = bg {
+            let _ = write!(css, "background:{};", bg);
+        }
+        if self.bold {
+            css.push_str("font-weight:bold;");
+        }
+        if self.italic {
+            css.push_str("font-style:italic;");
+        }
+        if self.underline {
+            css.push_str("text-decoration:underline;");
+        }
+        css
+    }
+
+    /// Apply an SGR sequence (`ESC [ ... m`)
+    fn apply(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            *self = Style::default();
+            return;
+        }
+
+        let mut params = params.iter().copied();
+        while let Some(p) = params.next() {
+            match p {
+                0 => *self = Style::default(),
+                1 => self.bold = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                7 => self.inverse = true,
+                22 => self.bold = false,
+                23 => self.italic = false,
+                24 => self.underline = false,
+                27 => self.inverse = false,
+                30..=37 => self.fg = Some(PALETTE[(p - 30) as usize].to_string()),
+                90..=97 => self.fg = Some(PALETTE[(p - 90 + 8) as usize].to_string()),
+                40..=47 => self.bg = Some(PALETTE[(p - 40) as usize].to_string()),
+                100..=107 => self.bg = Some(PALETTE[(p - 100 + 8) as usize].to_string()),
+                39 => self.fg = None,
+                49 => self.bg = None,
+                38 | 48 => {
+                    let color = match params.next() {
+                        Some(5) => params.next().map(indexed),
+                        Some(2) => match (params.next(), params.next(), params.next()) {
+                            (Some(r), Some(g), Some(b)) => Some(format!(
+                                "#{:02x}{:02x}{:02x}",
+                                r.min(255),
+                                g.min(255),
+                                b.min(255)
+                            )),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if p == 38 {
+                        self.fg = color;
+                    } else {
+                        self.bg = color;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Turns terminal output into HTML, one `<span>` per run of identically styled text
+#[derive(Default)]
+struct Html {
+    body: String,
+    style: Style,
+    span_open: bool,
+}
+
+impl Html {
+    fn open_span(&mut self) {
+        if self.style != Style::default() {
+            let _ = write!(self.body, "<span style=\"{}\">", self.style.css());
+            self.span_open = true;
+        }
+    }
+
+    fn close_span(&mut self) {
+        if self.span_open {
+            self.body.push_str("</span>");
+            self.span_open = false;
+        }
+    }
+}
+
+impl vte::Perform for Html {
+    fn print(&mut self, c: char) {
+        match c {
+            '&' => self.body.push_str("&amp;"),
+            '<' => self.body.push_str("&lt;"),
+            '>' => self.body.push_str("&gt;"),
+            _ => self.body.push(c),
+        }
+    }
+
+    fn execute(&mut self, byte: u8) {
+        if byte == b'\n' || byte == b'\t' {
+            self.body.push(byte as char);
+        }
+    }
+
+    fn csi_dispatch(
+        &mut self,
+        params: &vte::Params,
+        _intermediates: &[u8],
+        _ignore: bool,
+        action: char,
+    ) {
+        if action != 'm' {
+            return;
+        }
+
+        // `38;5;n` and `38:5:n` both end up as a flat list
+        let params: Vec<u16> = params.iter().flatten().copied().collect();
+        let mut style = self.style.clone();
+        style.apply(&params);
+        if style != self.style {
+            self.close_span();
+            self.style = style;
+            self.open_span();
+        }
+    }
+}
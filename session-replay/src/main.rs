@@ -0,0 +1,102 @@
+//! Replay sessions recorded by pty-bash-hook or remote-shell in the terminal, or export them as
+//! text or HTML
+
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use session_events::{Record, SessionEvent};
+
+mod export;
+mod player;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+struct Args {
+    /// Recording: session event JSON lines, an asciinema .cast file, or a pty-bash-hook text log
+    file: PathBuf,
+
+    /// Playback speed multiplier (+ and - change it while playing)
+    #[arg(short, long, default_value_t = 1.0)]
+    speed: f64,
+
+    /// Shorten pauses longer than this many seconds
+    #[arg(long, value_name = "SECONDS")]
+    idle_limit: Option<f64>,
+
+    /// Start at the Nth command (from 1); what came before is shown instantly, or left out of
+    /// exports
+    #[arg(short, long, value_name = "N")]
+    command: Option<usize>,
+
+    /// Print the session as plain text instead of playing it
+    #[arg(long, conflicts_with = "to_html")]
+    to_text: bool,
+
+    /// Print the session as a standalone HTML page instead of playing it
+    #[arg(long)]
+    to_html: bool,
+
+    /// Write the export to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    if args.speed.is_nan() || args.speed <= 0.0 {
+        bail!("--speed must be positive");
+    }
+    if args.idle_limit.is_some_and(|limit| limit.is_nan() || limit < 0.0) {
+        bail!("--idle-limit can't be negative");
+    }
+
+    let records = std::fs::read_to_string(&args.file)
+        .and_then(|text| session_events::parse(&text))
+        .with_context(|| format!("Failed to read {}", args.file.display()))?;
+
+    let start = match args.command {
+        Some(n) => command_index(&records, n)
+            .with_context(|| format!("The recording has no command {}", n))?,
+        None => 0,
+    };
+
+    if args.to_text || args.to_html {
+        let export = if args.to_html {
+            let title = args.file.file_name().unwrap_or_default().to_string_lossy();
+            export::to_html(&records[start..], &title)
+        } else {
+            export::to_text(&records[start..])
+        };
+
+        match &args.output {
+            Some(path) => std::fs::write(path, export)
+                .with_context(|| format!("Failed to write {}", path.display()))?,
+            None => io::stdout().write_all(export.as_bytes())?,
+        }
+        return Ok(());
+    }
+
+    player::play(
+        &records,
+        start,
+        player::Options {
+            speed: args.speed,
+            idle_limit: args.idle_limit,
+        },
+    )
+}
+
+/// Index of the `n`th (from 1) command start
+fn command_index(records: &[Record], n: usize) -> Option<usize> {
+    records
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| matches!(r.event, SessionEvent::CommandStarted { .. }))
+        .nth(n.checked_sub(1)?)
+        .map(|(i, _)| i)
+}
@@ -0,0 +1,176 @@
+//! Playing a recording back in the terminal, keeping the recorded timing.
+//!
+//! Keys while playing: space pauses, `+`/`-` double or halve the speed, `n` skips ahead to the
+//! next command, `q` quits.
+
+use std::{
+    io::{self, IsTerminal, Write},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal::{disable_raw_mode, enable_raw_mode},
+};
+use session_events::{Record, SessionEvent};
+
+pub struct Options {
+    pub speed: f64,
+    pub idle_limit: Option<f64>,
+}
+
+enum Action {
+    Continue,
+    /// Show everything up to the next command without waiting
+    Skip,
+    Quit,
+}
+
+/// Raw mode, so keys arrive without Enter, for as long as this lives
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        enable_raw_mode()?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+    }
+}
+
+/// Play `records`, showing everything before `start` instantly
+pub fn play(records: &[Record], start: usize, options: Options) -> Result<()> {
+    let mut stdout = io::stdout();
+    // Without a terminal to read keys from, just play
+    let raw_mode = if io::stdin().is_terminal() {
+        Some(RawMode::enable()?)
+    } else {
+        None
+    };
+
+    let mut speed = options.speed;
+    let mut paused = false;
+    let mut skipping = false;
+    let mut last_time = records.get(start).map_or(0.0, |r| r.time);
+
+    for (i, record) in records.iter().enumerate() {
+        if i >= start {
+            if skipping && matches!(record.event, SessionEvent::CommandStarted { .. }) {
+                skipping = false;
+            }
+
+            if !skipping {
+                let mut pause = (record.time - last_time).max(0.0);
+                if let Some(limit) = options.idle_limit {
+                    pause = pause.min(limit);
+                }
+                let action = wait(
+                    delay(pause, speed),
+                    &mut speed,
+                    &mut paused,
+                    raw_mode.is_some(),
+                )?;
+                match action {
+                    Action::Continue => {}
+                    Action::Skip => skipping = true,
+                    Action::Quit => break,
+                }
+            }
+            last_time = record.time;
+        }
+
+        // Resizes can't be applied to the terminal we're playing in, titles are in the output
+        if let SessionEvent::Output { data } = &record.event {
+            stdout.write_all(data.as_bytes())?;
+            stdout.flush()?;
+        }
+    }
+
+    // Don't leave the terminal in whatever colors the recording ended with
+    write!(stdout, "\x1b[0m\r\n")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Real time for a `pause` in the recording at `speed`. Very slow speeds can ask for more than a
+/// `Duration` holds, which waits as long as it can instead.
+fn delay(pause: f64, speed: f64) -> Duration {
+    let secs = pause / speed;
+    Duration::try_from_secs_f64(secs).unwrap_or(if secs > 0.0 {
+        Duration::MAX
+    } else {
+        Duration::ZERO
+    })
+}
+
+/// Sleep for `delay` (at the current speed), handling keys meanwhile
+fn wait(delay: Duration, speed: &mut f64, paused: &mut bool, keys: bool) -> Result<Action> {
+    if !keys {
+        std::thread::sleep(delay);
+        return Ok(Action::Continue);
+    }
+
+    let mut remaining = delay;
+    loop {
+        if !*paused && remaining.is_zero() {
+            return Ok(Action::Continue);
+        }
+
+        let timeout = if *paused {
+            Duration::from_millis(100)
+        } else {
+            remaining
+        };
+        let started = Instant::now();
+
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    match key.code {
+                        KeyCode::Char(' ') => *paused = !*paused,
+                        KeyCode::Char('+') | KeyCode::Char('=') => {
+                            *speed *= 2.0;
+                            remaining /= 2;
+                        }
+                        KeyCode::Char('-') => {
+                            *speed /= 2.0;
+                            remaining = remaining.saturating_mul(2);
+                        }
+                        KeyCode::Char('n') => return Ok(Action::Skip),
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            return Ok(Action::Quit)
+                        }
+                        KeyCode::Char('q') | KeyCode::Esc => return Ok(Action::Quit),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if !*paused {
+            remaining = remaining.saturating_sub(started.elapsed());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pauses_scale_with_speed() {
+        assert_eq!(delay(1.0, 2.0), Duration::from_millis(500));
+        assert_eq!(delay(0.0, 1e-300), Duration::ZERO);
+    }
+
+    #[test]
+    fn pauses_too_long_for_a_duration_are_clamped() {
+        assert_eq!(delay(1.0, 1e-300), Duration::MAX);
+        assert_eq!(delay(1.0, 0.0), Duration::MAX);
+    }
+}
@@ -0,0 +1,94 @@
+# Shell integration for bash: reports commands through OSC 6973 markers (see the shell-markers crate)
+
+# Sourced twice (--rcfile and by hand, say), the hooks would fire twice
+if [ -n "$__sm_installed" ]; then
+    return
+fi
+__sm_installed=1
+
+# --rcfile replaces ~/.bashrc, load it when asked to
+if [ -n "$SHELL_MARKERS_LOAD_RC" ]; then
+    unset SHELL_MARKERS_LOAD_RC
+    if [ -r "$HOME/.bashrc" ]; then
+        . "$HOME/.bashrc"
+    fi
+fi
+
+__sm_in_execution=""
+__sm_at_prompt=""
+
+# Emit an OSC 6973 marker. tmux drops unknown OSC sequences, so inside tmux it goes out wrapped
+# in a passthrough sequence.
+__sm_osc() {
+    if [ -n "$TMUX" ]; then
+        builtin printf "\033Ptmux;\033\033]6973;%s\007\033\\" "$1"
+    else
+        builtin printf "\033]6973;%s\007" "$1"
+    fi
+}
+
+__sm_clk_tck=$(getconf CLK_TCK 2>/dev/null || echo 100)
+
+# Milliseconds since the epoch ($EPOCHREALTIME needs bash 5, older versions get second precision)
+__sm_now_ms() {
+    if [ -n "$EPOCHREALTIME" ]; then
+        local us="${EPOCHREALTIME/[.,]/}"
+        __sm_now=$(( us / 1000 ))
+    else
+        __sm_now=$(( SECONDS * 1000 ))
+    fi
+}
+
+# CPU milliseconds used by the shell's finished children (cutime + cstime from /proc), empty if unknown
+__sm_child_cpu_ms() {
+    __sm_cpu=""
+    local stat fields
+    if read -r stat < "/proc/$$/stat" 2>/dev/null; then
+        # Skip "pid (comm) ", comm may contain spaces
+        read -r -a fields <<< "${stat##*) }"
+        __sm_cpu=$(( (fields[13] + fields[14]) * 1000 / __sm_clk_tck ))
+    fi
+}
+
+__sm_precmd_bash() {
+    local ret="$?"
+    # Until __sm_prompt_done, DEBUG traps come from PROMPT_COMMAND, not from a command
+    __sm_at_prompt=1
+    if [ -n "$__sm_in_execution" ]; then
+        __sm_now_ms
+        __sm_child_cpu_ms
+        local wall=$(( __sm_now - __sm_start ))
+        local cpu=""
+        if [ -n "$__sm_cpu" ] && [ -n "$__sm_start_cpu" ]; then
+            cpu=$(( __sm_cpu - __sm_start_cpu ))
+        fi
+        __sm_osc "END;$ret;$wall;$cpu"
+        __sm_in_execution=""
+    fi
+    __sm_osc "CWD;$PWD"
+}
+
+__sm_prompt_done() {
+    __sm_at_prompt=""
+}
+
+__sm_preexec_bash() {
+    # Completion functions also trigger DEBUG
+    if [ -n "$COMP_LINE" ] || [ -n "$__sm_at_prompt" ] || [ "$BASH_COMMAND" = "__sm_precmd_bash" ]; then
+        return
+    fi
+    if [ -z "$__sm_in_execution" ]; then
+        __sm_in_execution="yes"
+        __sm_now_ms
+        __sm_child_cpu_ms
+        __sm_start="$__sm_now"
+        __sm_start_cpu="$__sm_cpu"
+        __sm_osc "START;$USER;$HOSTNAME;$PWD"
+        # Only the first simple command of the line is known here
+        __sm_osc "CMD;$BASH_COMMAND"
+    fi
+}
+
+# Keep whatever PROMPT_COMMAND ~/.bashrc set up, between our hooks
+PROMPT_COMMAND="__sm_precmd_bash${PROMPT_COMMAND:+; $PROMPT_COMMAND}; __sm_prompt_done"
+trap '__sm_preexec_bash' DEBUG
@@ -0,0 +1,31 @@
+# Shell integration for fish: reports commands through OSC 6973 markers (see the shell-markers crate)
+
+if not set -q __sm_installed
+    set -g __sm_installed 1
+
+    # Emit an OSC 6973 marker. tmux drops unknown OSC sequences, so inside tmux it goes out
+    # wrapped in a passthrough sequence.
+    function __sm_osc
+        if set -q TMUX
+            printf '\033Ptmux;\033\033]6973;%s\007\033\\' $argv[1]
+        else
+            printf '\033]6973;%s\007' $argv[1]
+        end
+    end
+
+    function __sm_preexec --on-event fish_preexec
+        __sm_osc "START;$USER;$hostname;$PWD"
+        # $argv[1] is the command line as typed
+        __sm_osc "CMD;$argv[1]"
+    end
+
+    # fish measures wall time itself; CPU time isn't available
+    function __sm_postexec --on-event fish_postexec
+        set -l ret $status
+        __sm_osc "END;$ret;$CMD_DURATION;"
+    end
+
+    function __sm_prompt --on-event fish_prompt
+        __sm_osc "CWD;$PWD"
+    end
+end
@@ -0,0 +1,92 @@
+# Shell integration for PowerShell: reports commands through OSC 6973 markers (see the
+# shell-markers crate)
+
+# Markers and command output are read as UTF-8
+[Console]::OutputEncoding = [System.Text.Encoding]::UTF8
+$OutputEncoding = [System.Text.Encoding]::UTF8
+
+# Write straight to the console; Write-Host could add newlines or formatting
+function Global:__sm_osc {
+    param([string]$Payload)
+    [Console]::Write([char]0x1b + ']6973;' + $Payload + [char]0x07)
+}
+
+function Global:__sm_start {
+    param([string]$Command)
+    $user = if ($env:USERNAME) { $env:USERNAME } else { $env:USER }
+    __sm_osc "START;$user;$([Environment]::MachineName);$PWD"
+    __sm_osc "CMD;$Command"
+    $Global:__sm_in_execution = $true
+    $Global:__sm_started = [DateTime]::UtcNow
+}
+
+$Global:__sm_in_execution = $false
+$Global:__sm_use_psreadline = $false
+$Global:__sm_last_hist_id = -1
+
+# Commands start when Enter is pressed, as far as PSReadLine is concerned
+if (Get-Module -ListAvailable PSReadLine) {
+    if (-not (Get-Module PSReadLine)) {
+        Import-Module PSReadLine -ErrorAction SilentlyContinue
+    }
+
+    if (Get-Module PSReadLine) {
+        $Global:__sm_use_psreadline = $true
+        Set-PSReadLineKeyHandler -Key Enter -ScriptBlock {
+            param($key, $arg)
+
+            $line = $null
+            $cursor = $null
+            [Microsoft.PowerShell.PSConsoleReadLine]::GetBufferState([ref]$line, [ref]$cursor)
+
+            if (-not [string]::IsNullOrWhiteSpace($line)) {
+                __sm_start $line
+            }
+
+            [Microsoft.PowerShell.PSConsoleReadLine]::AcceptLine()
+        }
+    }
+}
+
+# prompt runs after every command, before the next prompt is shown
+if (Test-Path function:prompt) {
+    $Global:__sm_original_prompt = $function:prompt
+} else {
+    $Global:__sm_original_prompt = { "PS $PWD> " }
+}
+
+function Global:prompt {
+    # Capture the status before anything else changes it
+    $lastStatus = $?
+    $lastCode = $global:LASTEXITCODE
+
+    # $? is only true/false; use the native exit code when there is one, else 1 like a
+    # failed cmdlet
+    if ($lastStatus) {
+        $exitCode = 0
+    } elseif ($lastCode -ne 0 -and $lastCode -ne $null) {
+        $exitCode = $lastCode
+    } else {
+        $exitCode = 1
+    }
+
+    # Without PSReadLine (PowerShell 2.0 on Windows 7, say) the command is only known from the
+    # history once it has finished; START and END then go out back to back
+    if (-not $Global:__sm_use_psreadline) {
+        $recent = Get-History -Count 1
+        if ($recent -and $recent.Id -gt $Global:__sm_last_hist_id) {
+            __sm_start $recent.CommandLine
+            $Global:__sm_started = $recent.StartExecutionTime.ToUniversalTime()
+            $Global:__sm_last_hist_id = $recent.Id
+        }
+    }
+
+    if ($Global:__sm_in_execution) {
+        $wall = [int64]([DateTime]::UtcNow - $Global:__sm_started).TotalMilliseconds
+        __sm_osc "END;$exitCode;$wall;"
+        $Global:__sm_in_execution = $false
+    }
+    __sm_osc "CWD;$PWD"
+
+    & $Global:__sm_original_prompt
+}
@@ -0,0 +1,406 @@
+//! The marker protocol shells use to report command boundaries to whatever runs them behind a
+//! PTY, and the integration scripts that emit it.
+//!
+//! Markers are OSC 6973 sequences (`ESC ] 6973 ; <KIND> ; <fields...> BEL`), invisible to
+//! terminals that don't know them:
+//!
+//! - `START;<user>;<host>;<cwd>` when a command line starts running
+//! - `CMD;<command line>` right after `START`
+//! - `END;<exit code>;<wall ms>;<cpu ms>` before the next prompt; timings are empty when the
+//!   shell can't tell
+//! - `CWD;<path>` at every prompt
+//!
+//! The last field of a marker may contain `;`. Control characters can't be carried, terminal
+//! parsers drop them inside OSC strings.
+
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// OSC number markers are sent under
+pub const OSC_CODE: &str = "6973";
+
+/// Set (to anything) in the environment of a bash started with `--rcfile <script>` to have the
+/// script load `~/.bashrc` first, which `--rcfile` otherwise skips
+pub const LOAD_RC_ENV: &str = "SHELL_MARKERS_LOAD_RC";
+
+/// A decoded marker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Marker {
+    Start {
+        user: String,
+        host: String,
+        cwd: String,
+    },
+    /// The command line that just started
+    Command(String),
+    End {
+        /// `None` if the shell sent something that isn't a number
+        exit_code: Option<i32>,
+        wall_ms: Option<u64>,
+        cpu_ms: Option<u64>,
+    },
+    /// Working directory at the prompt
+    Cwd(String),
+}
+
+impl Marker {
+    /// Decode the parameters of an OSC sequence (`params[0]` being the OSC number), `None` if it
+    /// isn't a marker
+    pub fn parse(params: &[&[u8]]) -> Option<Marker> {
+        if params.first()? != &OSC_CODE.as_bytes() {
+            return None;
+        }
+
+        let text = |i: usize| {
+            params
+                .get(i)
+                .map(|p| String::from_utf8_lossy(p).into_owned())
+                .unwrap_or_default()
+        };
+        // The last field keeps its semicolons, which arrive split into separate params
+        let rest = |i: usize| {
+            params
+                .get(i..)
+                .unwrap_or_default()
+                .iter()
+                .map(|p| String::from_utf8_lossy(p))
+                .collect::<Vec<_>>()
+                .join(";")
+        };
+
+        match *params.get(1)? {
+            b"START" => Some(Marker::Start {
+                user: text(2),
+                host: text(3),
+                cwd: rest(4),
+            }),
+            b"CMD" => Some(Marker::Command(rest(2))),
+            b"END" => Some(Marker::End {
+                exit_code: number(params, 2),
+                wall_ms: number(params, 3),
+                cpu_ms: number(params, 4),
+            }),
+            b"CWD" => Some(Marker::Cwd(rest(2))),
+            _ => None,
+        }
+    }
+}
+
+fn number<T: FromStr>(params: &[&[u8]], i: usize) -> Option<T> {
+    std::str::from_utf8(params.get(i)?).ok()?.parse().ok()
+}
+
+/// The complete escape sequence, as a shell would print it
+impl fmt::Display for Marker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let opt = |n: Option<u64>| n.map(|n| n.to_string()).unwrap_or_default();
+
+        write!(f, "\x1b]{};", OSC_CODE)?;
+        match self {
+            Marker::Start { user, host, cwd } => write!(f, "START;{};{};{}", user, host, cwd)?,
+            Marker::Command(command) => write!(f, "CMD;{}", command)?,
+            Marker::End {
+                exit_code,
+                wall_ms,
+                cpu_ms,
+            } => write!(
+                f,
+                "END;{};{};{}",
+                exit_code.map(|c| c.to_string()).unwrap_or_default(),
+                opt(*wall_ms),
+                opt(*cpu_ms)
+            )?,
+            Marker::Cwd(path) => write!(f, "CWD;{}", path)?,
+        }
+        write!(f, "\x07")
+    }
+}
+
+/// Shells with an integration script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+}
+
+impl Shell {
+    pub const ALL: [Shell; 4] = [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell];
+
+    /// Guess the shell from its executable (`/bin/bash`, `pwsh.exe`, ...)
+    pub fn detect(program: &str) -> Option<Shell> {
+        let name = Path::new(program).file_stem()?.to_str()?;
+        match name {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "pwsh" | "powershell" => Some(Shell::PowerShell),
+            _ => None,
+        }
+    }
+
+    /// File name the script is written out under
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Shell::Bash => "shell-integration.bash",
+            Shell::Zsh => "shell-integration.zsh",
+            Shell::Fish => "shell-integration.fish",
+            Shell::PowerShell => "shell-integration.ps1",
+        }
+    }
+
+    pub fn script(self) -> &'static str {
+        match self {
+            Shell::Bash => include_str!("../scripts/shell-integration.bash"),
+            Shell::Zsh => include_str!("../scripts/shell-integration.zsh"),
+            Shell::Fish => include_str!("../scripts/shell-integration.fish"),
+            Shell::PowerShell => include_str!("../scripts/shell-integration.ps1"),
+        }
+    }
+}
+
+/// Write every integration script into a new directory under the system temp dir, for shells
+/// that need them as real files (`--rcfile`, `source`, `-File`). Returns the directory.
+///
+/// The directory gets a random name and (on Unix) mode 0700, and must not exist yet: an existing
+/// one at a guessable path could belong to another user, whose scripts every shell would run.
+pub fn extract(prefix: &str) -> io::Result<PathBuf> {
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+    let mut attempts = 0;
+    let dir = loop {
+        let dir = std::env::temp_dir().join(format!(
+            "{}-{}-{:016x}",
+            prefix,
+            std::process::id(),
+            random()
+        ));
+        match builder.create(&dir) {
+            Ok(()) => break dir,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempts < 16 => attempts += 1,
+            Err(e) => return Err(e),
+        }
+    };
+
+    for shell in Shell::ALL {
+        std::fs::write(dir.join(shell.file_name()), shell.script())?;
+    }
+    Ok(dir)
+}
+
+/// A random number without a dependency on an RNG: std seeds every `RandomState` randomly
+fn random() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish()
+}
+
+/// What a [`MarkerParser`] reports. Everything but [`Perform::marker`] is optional, for consumers
+/// that also want the text around the markers.
+pub trait Perform {
+    fn marker(&mut self, marker: Marker);
+
+    /// A printable character
+    fn print(&mut self, _c: char) {}
+
+    /// A C0 control byte (`\n`, `\t`, ...)
+    fn execute(&mut self, _byte: u8) {}
+
+    /// Any other OSC sequence
+    fn osc(&mut self, _params: &[&[u8]], _bell_terminated: bool) {}
+}
+
+/// Finds markers in raw terminal output. Sequences split across reads are picked up, the parser
+/// keeps its state between calls to [`MarkerParser::advance`].
+#[derive(Default)]
+pub struct MarkerParser {
+    parser: vte::Parser,
+}
+
+impl MarkerParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance<P: Perform>(&mut self, performer: &mut P, data: &[u8]) {
+        self.parser.advance(&mut Adapter(performer), data);
+    }
+}
+
+struct Adapter<'a, P>(&'a mut P);
+
+impl<P: Perform> vte::Perform for Adapter<'_, P> {
+    fn print(&mut self, c: char) {
+        self.0.print(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.0.execute(byte);
+    }
+
+    fn osc_dispatch(&mut self, params: &[&[u8]], bell_terminated: bool) {
+        match Marker::parse(params) {
+            Some(marker) => self.0.marker(marker),
+            // Unknown kinds under our code are dropped, they're ours to define
+            None if params.first() == Some(&OSC_CODE.as_bytes()) => {}
+            None => self.0.osc(params, bell_terminated),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Everything the parser reports, text and markers in order
+    #[derive(Default)]
+    struct Collect {
+        text: String,
+        markers: Vec<Marker>,
+        other: Vec<String>,
+    }
+
+    impl Perform for Collect {
+        fn marker(&mut self, marker: Marker) {
+            self.markers.push(marker);
+        }
+
+        fn print(&mut self, c: char) {
+            self.text.push(c);
+        }
+
+        fn execute(&mut self, byte: u8) {
+            self.text.push(byte as char);
+        }
+
+        fn osc(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+            let params: Vec<_> = params.iter().map(|p| String::from_utf8_lossy(p)).collect();
+            self.other.push(params.join(";"));
+        }
+    }
+
+    fn samples() -> Vec<Marker> {
+        vec![
+            Marker::Start {
+                user: "ann".to_string(),
+                host: "build-1".to_string(),
+                cwd: "/srv/a;b".to_string(),
+            },
+            Marker::Command("make -j8; echo done".to_string()),
+            Marker::End {
+                exit_code: Some(-1),
+                wall_ms: Some(1520),
+                cpu_ms: Some(30),
+            },
+            Marker::End {
+                exit_code: Some(0),
+                wall_ms: None,
+                cpu_ms: None,
+            },
+            Marker::Cwd("/home/ann".to_string()),
+        ]
+    }
+
+    fn params(text: &str) -> Vec<&[u8]> {
+        text.split(';').map(str::as_bytes).collect()
+    }
+
+    #[test]
+    fn markers_are_parsed() {
+        assert_eq!(
+            Marker::parse(&params("6973;START;ann;build-1;/srv")),
+            Some(Marker::Start {
+                user: "ann".to_string(),
+                host: "build-1".to_string(),
+                cwd: "/srv".to_string(),
+            })
+        );
+        assert_eq!(
+            Marker::parse(&params("6973;CMD;a; b;")),
+            Some(Marker::Command("a; b;".to_string()))
+        );
+        assert_eq!(
+            Marker::parse(&params("6973;END;x;;")),
+            Some(Marker::End {
+                exit_code: None,
+                wall_ms: None,
+                cpu_ms: None,
+            })
+        );
+        assert_eq!(Marker::parse(&params("6973;CWD;")), Some(Marker::Cwd(String::new())));
+    }
+
+    #[test]
+    fn other_sequences_are_not_markers() {
+        assert_eq!(Marker::parse(&params("0;title")), None);
+        assert_eq!(Marker::parse(&params("6973;NOPE;1")), None);
+        assert_eq!(Marker::parse(&params("6973")), None);
+        assert_eq!(Marker::parse(&[]), None);
+    }
+
+    #[test]
+    fn displayed_markers_parse_back() {
+        for marker in samples() {
+            let mut collect = Collect::default();
+            MarkerParser::new().advance(&mut collect, marker.to_string().as_bytes());
+            assert_eq!(collect.markers, [marker]);
+            assert_eq!(collect.text, "");
+        }
+    }
+
+    #[test]
+    fn markers_split_across_reads_are_found() {
+        let mut output = "$ make\r\n".to_string();
+        for marker in samples() {
+            output.push_str(&marker.to_string());
+            output.push_str("ok\r\n");
+        }
+        output.push_str("\x1b]0;title\x07");
+        let bytes = output.as_bytes();
+
+        for chunk in 1..=7 {
+            let mut collect = Collect::default();
+            let mut parser = MarkerParser::new();
+            for part in bytes.chunks(chunk) {
+                parser.advance(&mut collect, part);
+            }
+            assert_eq!(collect.markers, samples(), "{}-byte reads", chunk);
+            assert_eq!(collect.text, format!("$ make\r\n{}", "ok\r\n".repeat(5)));
+            assert_eq!(collect.other, ["0;title"]);
+        }
+    }
+
+    #[test]
+    fn unknown_kinds_are_dropped() {
+        let mut collect = Collect::default();
+        MarkerParser::new().advance(&mut collect, b"a\x1b]6973;NEW;1\x07b");
+        assert!(collect.markers.is_empty());
+        assert!(collect.other.is_empty());
+        assert_eq!(collect.text, "ab");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scripts_are_extracted_to_a_private_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let first = extract("shell-markers-test").unwrap();
+        let second = extract("shell-markers-test").unwrap();
+        assert_ne!(first, second);
+        let mode = std::fs::metadata(&first).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        for shell in Shell::ALL {
+            let script = std::fs::read_to_string(first.join(shell.file_name())).unwrap();
+            assert_eq!(script, shell.script());
+        }
+        std::fs::remove_dir_all(first).unwrap();
+        std::fs::remove_dir_all(second).unwrap();
+    }
+}
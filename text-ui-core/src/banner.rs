@@ -0,0 +1,361 @@
+//! [`Banner`]: the art colored, framed and placed, and the builder putting it together
+
+use std::{fmt, path::PathBuf};
+
+use crate::{
+    Align, BannerConfig, Border, Direction, Fit, GradientDirection, InfoPosition, Layout, Output,
+    SmushRule, border,
+    canvas::{self, Line},
+    color::{self, Depth, Paint},
+    export, fit, font, layout,
+};
+
+/// Why a banner couldn't be rendered: an unknown or broken font, a color that doesn't parse, or
+/// text the font has nothing for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error(message)
+    }
+}
+
+/// A rendered banner
+#[derive(Debug, Clone)]
+pub struct Banner {
+    lines: Vec<Line>,
+    output: Output,
+    depth: Depth,
+}
+
+impl Banner {
+    pub fn builder() -> BannerBuilder {
+        BannerBuilder::default()
+    }
+
+    /// The banner's lines, indent included
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// Columns the widest line takes up
+    pub fn width(&self) -> usize {
+        self.lines
+            .iter()
+            .map(|l| canvas::width(l))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The banner in its output format, a line of text per line of the banner
+    pub fn render(&self) -> String {
+        match self.output {
+            Output::Ansi => export::ansi(&self.lines, self.depth),
+            Output::Plain => export::plain(&self.lines),
+            Output::Svg => export::svg(&self.lines),
+            Output::Html => export::html(&self.lines),
+        }
+    }
+}
+
+/// Options for a [`Banner`], defaulting to those of [`BannerConfig`]
+#[derive(Debug, Clone, Default)]
+pub struct BannerBuilder {
+    config: BannerConfig,
+    width: Option<usize>,
+    depth: Option<Depth>,
+}
+
+impl From<BannerConfig> for BannerBuilder {
+    fn from(config: BannerConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+}
+
+impl BannerBuilder {
+    /// Text to render, a row of art per line
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.config.text = text.into();
+        self
+    }
+
+    /// A built-in font (slant, standard, shadow, small), or the name of a .flf or .tlf file in
+    /// [`font_dir`](Self::font_dir) or figlet's font directories
+    pub fn font(mut self, font: impl Into<String>) -> Self {
+        self.config.font = font.into();
+        self
+    }
+
+    /// A .flf or .tlf file to render with instead of [`font`](Self::font)
+    pub fn font_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.font_file = Some(path.into());
+        self
+    }
+
+    /// Where to look for fonts named with [`font`](Self::font) first
+    pub fn font_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.font_dir = Some(dir.into());
+        self
+    }
+
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.config.layout = layout;
+        self
+    }
+
+    /// Smushing rules used instead of the font's
+    pub fn smush_rules(mut self, rules: impl IntoIterator<Item = SmushRule>) -> Self {
+        self.config.smush_rules = rules.into_iter().collect();
+        self
+    }
+
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.config.direction = direction;
+        self
+    }
+
+    /// Drawn for characters the font doesn't have
+    pub fn fallback(mut self, fallback: impl Into<String>) -> Self {
+        self.config.fallback = Some(fallback.into());
+        self
+    }
+
+    /// Write characters the font doesn't have in ASCII before falling back to
+    /// [`fallback`](Self::fallback)
+    pub fn transliterate(mut self) -> Self {
+        self.config.transliterate = true;
+        self
+    }
+
+    /// Blank lines between rows of art
+    pub fn line_spacing(mut self, lines: usize) -> Self {
+        self.config.line_spacing = lines;
+        self
+    }
+
+    /// A color name (red, cyan, orange, ...) or `#rrggbb`
+    pub fn color(mut self, color: impl Into<String>) -> Self {
+        self.config.color = Some(color.into());
+        self
+    }
+
+    /// Blend between two colors, `<from>..<to>`
+    pub fn gradient(mut self, gradient: impl Into<String>, direction: GradientDirection) -> Self {
+        self.config.gradient = Some(gradient.into());
+        self.config.gradient_direction = direction;
+        self
+    }
+
+    /// lolcat-style rainbow
+    pub fn rainbow(mut self) -> Self {
+        self.config.rainbow = true;
+        self
+    }
+
+    /// Where the banner goes in [`width`](Self::width)
+    pub fn align(mut self, align: Align) -> Self {
+        self.config.align = align;
+        self
+    }
+
+    /// Columns to fit and align the banner in. Without it the art is left as wide as it comes
+    /// out.
+    pub fn width(mut self, columns: usize) -> Self {
+        self.width = Some(columns);
+        self
+    }
+
+    /// What to do when the art is wider than [`width`](Self::width)
+    pub fn fit(mut self, fit: Fit) -> Self {
+        self.config.fit = fit;
+        self
+    }
+
+    pub fn border(mut self, border: Border) -> Self {
+        self.config.border = border;
+        self
+    }
+
+    /// Spaces between the border and the art, and half as many blank lines
+    pub fn padding(mut self, padding: usize) -> Self {
+        self.config.padding = padding;
+        self
+    }
+
+    /// Set into the top of the border
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.config.title = Some(title.into());
+        self
+    }
+
+    /// Version info in the bottom right corner
+    pub fn info(mut self, info: impl Into<String>) -> Self {
+        self.config.info = Some(info.into());
+        self
+    }
+
+    pub fn info_position(mut self, position: InfoPosition) -> Self {
+        self.config.info_position = position;
+        self
+    }
+
+    pub fn output(mut self, output: Output) -> Self {
+        self.config.output = output;
+        self
+    }
+
+    /// Colors the terminal shows, for [`Output::Ansi`]. Guessed from the environment if not
+    /// given.
+    pub fn depth(mut self, depth: Depth) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn build(self) -> Result<Banner, Error> {
+        let config = &self.config;
+        let font = font::load(config)?;
+        let paint = Paint::from_config(config)?;
+
+        // Convert text to ASCII art, in whatever room the border leaves
+        let frame_width = border::extra_width(config.border, config.padding);
+        let available = self.width.map(|width| width.saturating_sub(frame_width));
+        let lines = fit::fit(&*font, &config.text, config, available);
+        if lines.is_empty() {
+            return Err(Error("Failed to convert text".to_string()));
+        }
+        let art_width = fit::width(&lines);
+        let mut lines = color::paint(lines, paint);
+
+        // The info line goes under the art unless the border or the art's last line has room
+        // for it. Inside a border it's right aligned to the art; without one it's a footer of
+        // its own, right aligned to the whole width when that's known.
+        let info_in_border =
+            config.info_position == InfoPosition::Border && config.border != Border::None;
+        let overlaid = config.info_position == InfoPosition::Overlay
+            && config
+                .info
+                .as_deref()
+                .is_some_and(|info| overlay(&mut lines, info, art_width));
+        let info = config
+            .info
+            .as_deref()
+            .filter(|_| !info_in_border && !overlaid);
+        let (info, footer) = match self.width.filter(|_| config.border == Border::None) {
+            Some(width) => (None, info.map(|info| layout::right_align(info, width))),
+            None => (info.map(|info| layout::right_align(info, art_width)), None),
+        };
+        let info = info.map(|info| canvas::plain(&info));
+        let content_width = art_width.max(info.as_deref().map_or(0, canvas::width));
+
+        let content: Vec<Line> = lines.into_iter().chain(info).collect();
+        let block = border::frame(
+            content,
+            content_width,
+            config.border,
+            config.padding,
+            config.title.as_deref(),
+            config.info.as_deref().filter(|_| info_in_border),
+        );
+
+        // The framed content is aligned as one block
+        let block_width = block.iter().map(|l| canvas::width(l)).max().unwrap_or(0);
+        let indent =
+            canvas::plain(&" ".repeat(layout::indent(config.align, block_width, self.width)));
+        let lines = block
+            .into_iter()
+            .map(|line| indent.iter().copied().chain(line).collect())
+            .chain(footer.map(|footer| canvas::plain(&footer)))
+            .collect();
+
+        Ok(Banner {
+            lines,
+            output: config.output,
+            depth: self.depth.unwrap_or_else(Depth::detect),
+        })
+    }
+
+    /// [`build`](Self::build) the banner and render it in its output format
+    pub fn render(self) -> Result<String, Error> {
+        self.build().map(|banner| banner.render())
+    }
+}
+
+/// Put `info` into the blank end of the art's last line, ending at column `width` and a space
+/// clear of the art. `false`, leaving the art as it was, if there isn't room.
+fn overlay(lines: &mut [Line], info: &str, width: usize) -> bool {
+    let Some(last) = lines.last_mut() else {
+        return false;
+    };
+    let drawn = last
+        .iter()
+        .rposition(|cell| !cell.ch.is_whitespace())
+        .map_or(0, |i| i + 1);
+    let info = canvas::plain(info);
+    let info_width = canvas::width(&info);
+    if canvas::width(&last[..drawn]) + 1 + info_width > width {
+        return false;
+    }
+
+    last.truncate(drawn);
+    let mut line = canvas::pad(std::mem::take(last), width - info_width);
+    line.extend(info);
+    *last = line;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(builder: BannerBuilder) -> Vec<String> {
+        let banner = builder.output(Output::Plain).build().unwrap();
+        banner.render().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn info_ends_at_the_terminal_edge() {
+        for align in [Align::Left, Align::Center, Align::Right] {
+            let lines = plain(Banner::builder().text("Hi").info("v1.0").width(60).align(align));
+            let info = lines.last().unwrap();
+            assert_eq!(info.trim(), "v1.0");
+            assert_eq!(layout::width(info), 60, "{:?}", align);
+        }
+    }
+
+    #[test]
+    fn info_follows_the_art_without_a_width() {
+        let lines = plain(Banner::builder().text("Hi").info("v1.0"));
+        let art_width = lines[..lines.len() - 1]
+            .iter()
+            .map(|line| layout::width(line))
+            .max()
+            .unwrap();
+        assert_eq!(layout::width(lines.last().unwrap()), art_width);
+    }
+
+    #[test]
+    fn framed_info_stays_inside_the_border() {
+        let lines = plain(
+            Banner::builder()
+                .text("Hi")
+                .info("v1.0")
+                .border(Border::Single)
+                .width(60),
+        );
+        let widths: Vec<_> = lines.iter().map(|line| layout::width(line.trim_end())).collect();
+        assert!(widths.iter().all(|&width| width == widths[0]), "{:?}", lines);
+        assert!(lines.iter().any(|line| line.contains("v1.0")));
+    }
+}
@@ -0,0 +1,224 @@
+//! Putting mixed-direction text in the order it's drawn, left to right, for fonts to render.
+//! A cut-down Unicode bidi algorithm: one paragraph level, no explicit embeddings, numbers
+//! keeping their order inside right-to-left text.
+
+use crate::Direction;
+
+/// What a character does to the order of the text around it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    /// Latin, CJK and every other left-to-right script
+    Left,
+    /// Hebrew, Arabic and the like
+    Right,
+    /// Digits: left to right themselves, but going with right-to-left text around them
+    Number,
+    /// Spaces and punctuation, going whichever way the text around them goes
+    Neutral,
+}
+
+/// Where the right-to-left scripts are
+const RTL: [(char, char); 6] = [
+    // Hebrew, Arabic, Syriac, Arabic Supplement, Thaana, NKo, Samaritan, Mandaic
+    ('\u{0590}', '\u{085F}'),
+    // Arabic Extended
+    ('\u{0870}', '\u{08FF}'),
+    // Hebrew and Arabic presentation forms
+    ('\u{FB1D}', '\u{FDFF}'),
+    ('\u{FE70}', '\u{FEFF}'),
+    // Cypriot, Phoenician, Kharoshthi, Old Turkic and other historic scripts
+    ('\u{10800}', '\u{10FFF}'),
+    // Mende Kikakui, Adlam and Arabic mathematical symbols
+    ('\u{1E800}', '\u{1EFFF}'),
+];
+
+/// Drawn the other way round in right-to-left text
+const MIRRORED: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>'), ('«', '»')];
+
+fn class(c: char) -> Class {
+    if c.is_numeric() {
+        Class::Number
+    } else if RTL.iter().any(|(start, end)| (*start..=*end).contains(&c)) {
+        Class::Right
+    } else if c.is_alphabetic() {
+        Class::Left
+    } else {
+        Class::Neutral
+    }
+}
+
+fn mirror(c: char) -> char {
+    MIRRORED
+        .iter()
+        .find_map(|&(open, close)| {
+            if c == open {
+                Some(close)
+            } else if c == close {
+                Some(open)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(c)
+}
+
+/// A line of `text` in the order its characters are drawn, for a paragraph running in
+/// `direction`. Right-to-left runs are reversed (their brackets turned round); runs going the
+/// paragraph's way stay put.
+pub fn visual(text: &str, direction: Direction) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let paragraph = match direction {
+        Direction::Ltr => Class::Left,
+        Direction::Rtl => Class::Right,
+    };
+
+    // Numbers after left-to-right text are just more of it
+    let mut classes: Vec<Class> = chars.iter().map(|&c| class(c)).collect();
+    let mut strong = paragraph;
+    for class in &mut classes {
+        match *class {
+            Class::Left | Class::Right => strong = *class,
+            Class::Number if strong == Class::Left => *class = Class::Left,
+            _ => {}
+        }
+    }
+
+    // A separator inside a number is part of it
+    for i in 1..classes.len().saturating_sub(1) {
+        if classes[i] == Class::Neutral
+            && matches!(chars[i], '.' | ',' | ':')
+            && classes[i - 1] == Class::Number
+            && classes[i + 1] == Class::Number
+        {
+            classes[i] = Class::Number;
+        }
+    }
+
+    // Neutrals go the way of the text on both sides of them if it agrees (numbers counting as
+    // right to left), else the paragraph's way
+    let direction = |class: Class| match class {
+        Class::Number => Class::Right,
+        class => class,
+    };
+    let mut i = 0;
+    while i < classes.len() {
+        if classes[i] != Class::Neutral {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < classes.len() && classes[i] == Class::Neutral {
+            i += 1;
+        }
+        let before = start
+            .checked_sub(1)
+            .map_or(paragraph, |j| direction(classes[j]));
+        let after = classes.get(i).map_or(paragraph, |&class| direction(class));
+        let resolved = if before == after { before } else { paragraph };
+        classes[start..i].fill(resolved);
+    }
+
+    // Levels: even runs left to right, odd ones right to left, numbers a level above the text
+    // they're in
+    let base = u8::from(paragraph == Class::Right);
+    let mut levels: Vec<u8> = classes
+        .iter()
+        .map(|class| match (class, base) {
+            (Class::Left, 0) => 0,
+            (Class::Right, _) => 1,
+            _ => 2,
+        })
+        .collect();
+    // Spaces at the end go the paragraph's way
+    for (level, c) in levels.iter_mut().zip(&chars).rev() {
+        if !c.is_whitespace() {
+            break;
+        }
+        *level = base;
+    }
+
+    // From the highest level down to the lowest odd one, reverse every run at that level or
+    // above
+    let mut drawn: Vec<(char, u8)> = chars.into_iter().zip(levels).collect();
+    let highest = drawn.iter().map(|&(_, level)| level).max().unwrap_or(0);
+    for level in (1..=highest).rev() {
+        let mut i = 0;
+        while i < drawn.len() {
+            if drawn[i].1 < level {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < drawn.len() && drawn[i].1 >= level {
+                i += 1;
+            }
+            drawn[start..i].reverse();
+        }
+    }
+
+    drawn
+        .into_iter()
+        .map(|(c, level)| if level % 2 == 1 { mirror(c) } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn left_to_right_text_is_left_alone() {
+        assert_eq!(
+            visual("Hello, world 1.0", Direction::Ltr),
+            "Hello, world 1.0"
+        );
+        assert_eq!(visual("版本 1.0", Direction::Ltr), "版本 1.0");
+    }
+
+    #[test]
+    fn right_to_left_text_is_reversed() {
+        assert_eq!(visual("שלום", Direction::Rtl), "םולש");
+        assert_eq!(visual("שלום", Direction::Ltr), "םולש");
+        assert_eq!(visual("مرحبا", Direction::Rtl), "ابحرم");
+    }
+
+    #[test]
+    fn left_to_right_words_keep_their_order_in_right_to_left_text() {
+        assert_eq!(visual("שלום abc", Direction::Rtl), "abc םולש");
+        assert_eq!(
+            visual("שלום abc def עולם", Direction::Rtl),
+            "םלוע abc def םולש"
+        );
+    }
+
+    #[test]
+    fn right_to_left_words_are_reversed_in_left_to_right_text() {
+        assert_eq!(visual("abc שלום def", Direction::Ltr), "abc םולש def");
+        assert_eq!(visual("abc שלום עולם", Direction::Ltr), "abc םלוע םולש");
+    }
+
+    #[test]
+    fn left_to_right_runs_swap_places_in_a_right_to_left_paragraph() {
+        assert_eq!(visual("release now", Direction::Rtl), "release now");
+        assert_eq!(visual("abc שלום def", Direction::Rtl), "def םולש abc");
+    }
+
+    #[test]
+    fn numbers_keep_their_order_in_right_to_left_text() {
+        assert_eq!(visual("גרסה 2.0", Direction::Rtl), "2.0 הסרג");
+        assert_eq!(visual("גרסה 2.0", Direction::Ltr), "2.0 הסרג");
+        assert_eq!(visual("v 2.0", Direction::Rtl), "v 2.0");
+    }
+
+    #[test]
+    fn brackets_turn_round_in_right_to_left_text() {
+        assert_eq!(visual("(שלום)", Direction::Rtl), "(םולש)");
+        assert_eq!(visual("abc (שלום)", Direction::Ltr), "abc (םולש)");
+    }
+
+    #[test]
+    fn trailing_spaces_stay_at_the_end() {
+        assert_eq!(visual("שלום ", Direction::Ltr), "םולש ");
+        assert_eq!(visual("abc ", Direction::Rtl), " abc");
+    }
+}
@@ -0,0 +1,136 @@
+//! `--border`: a box drawn around the banner, with an optional title in the top edge and the
+//! info string in the bottom one
+
+use crate::{
+    Border,
+    canvas::{self, Line},
+    layout,
+};
+
+struct Chars {
+    top_left: char,
+    top_right: char,
+    bottom_left: char,
+    bottom_right: char,
+    horizontal: char,
+    vertical: char,
+}
+
+impl Chars {
+    fn of(border: Border) -> Option<Self> {
+        let [
+            top_left,
+            top_right,
+            bottom_left,
+            bottom_right,
+            horizontal,
+            vertical,
+        ] = match border {
+            Border::None => return None,
+            Border::Single => ['┌', '┐', '└', '┘', '─', '│'],
+            Border::Double => ['╔', '╗', '╚', '╝', '═', '║'],
+            Border::Rounded => ['╭', '╮', '╰', '╯', '─', '│'],
+            Border::Ascii => ['+', '+', '+', '+', '-', '|'],
+        };
+        Some(Self {
+            top_left,
+            top_right,
+            bottom_left,
+            bottom_right,
+            horizontal,
+            vertical,
+        })
+    }
+}
+
+/// Columns a border with `padding` adds around the content
+pub fn extra_width(border: Border, padding: usize) -> usize {
+    match border {
+        Border::None => 0,
+        _ => 2 + 2 * padding,
+    }
+}
+
+/// Draw the border around `lines`, padding them to `width` columns. `padding` is the space left
+/// and right of the content; above and below get half as many blank lines, since terminal cells
+/// are about twice as tall as they are wide.
+pub fn frame(
+    lines: Vec<Line>,
+    width: usize,
+    border: Border,
+    padding: usize,
+    title: Option<&str>,
+    footer: Option<&str>,
+) -> Vec<Line> {
+    let Some(chars) = Chars::of(border) else {
+        return lines;
+    };
+
+    // Labels sit in the edges with a space either side and at least one line char beyond
+    let label_width = |label: Option<&str>| label.map_or(0, |l| layout::width(l) + 4);
+    let inner = (width + 2 * padding)
+        .max(label_width(title))
+        .max(label_width(footer));
+    let vertical = canvas::plain(&chars.vertical.to_string());
+    let side = canvas::plain(&" ".repeat(padding));
+    let row = |content: Line| {
+        let mut row = vertical.clone();
+        row.extend(side.iter().copied());
+        row.extend(canvas::pad(content, inner - 2 * padding));
+        row.extend(side.iter().copied());
+        row.extend(vertical.iter().copied());
+        row
+    };
+
+    let mut framed = vec![edge(
+        chars.top_left,
+        chars.top_right,
+        chars.horizontal,
+        inner,
+        title,
+        false,
+    )];
+    framed.extend((0..padding / 2).map(|_| row(Line::new())));
+    framed.extend(lines.into_iter().map(&row));
+    framed.extend((0..padding / 2).map(|_| row(Line::new())));
+    framed.push(edge(
+        chars.bottom_left,
+        chars.bottom_right,
+        chars.horizontal,
+        inner,
+        footer,
+        true,
+    ));
+    framed
+}
+
+/// A top or bottom edge `inner` columns wide between its corners, with `label` near the start
+/// or, with `at_end`, near the end
+fn edge(
+    left: char,
+    right: char,
+    line: char,
+    inner: usize,
+    label: Option<&str>,
+    at_end: bool,
+) -> Line {
+    let Some(label) = label else {
+        return canvas::plain(&format!(
+            "{}{}{}",
+            left,
+            line.to_string().repeat(inner),
+            right
+        ));
+    };
+
+    let rest = inner - layout::width(label) - 2;
+    let (before, after) = if at_end { (rest - 1, 1) } else { (1, rest - 1) };
+    canvas::plain(&format!(
+        "{}{} {} {}{}",
+        left,
+        line.to_string().repeat(before),
+        label,
+        line.to_string().repeat(after),
+        right
+    ))
+}
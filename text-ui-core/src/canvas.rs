@@ -0,0 +1,40 @@
+//! The banner as lines of cells: characters with the color they're drawn in
+
+use crate::{color::Rgb, layout};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub ch: char,
+    pub color: Option<Rgb>,
+}
+
+pub type Line = Vec<Cell>;
+
+/// Uncolored text
+pub fn plain(text: &str) -> Line {
+    text.chars().map(|ch| Cell { ch, color: None }).collect()
+}
+
+/// Columns a line takes up on screen
+pub fn width(line: &[Cell]) -> usize {
+    line.iter().map(|cell| layout::char_width(cell.ch)).sum()
+}
+
+/// `line` with spaces added up to `width` columns
+pub fn pad(mut line: Line, width: usize) -> Line {
+    let missing = width.saturating_sub(self::width(&line));
+    line.extend(plain(&" ".repeat(missing)));
+    line
+}
+
+/// Runs of identically colored text in a line
+pub fn runs(line: &[Cell]) -> Vec<(Option<Rgb>, String)> {
+    let mut runs: Vec<(Option<Rgb>, String)> = Vec::new();
+    for cell in line {
+        match runs.last_mut() {
+            Some((color, text)) if *color == cell.color => text.push(cell.ch),
+            _ => runs.push((cell.color, cell.ch.to_string())),
+        }
+    }
+    runs
+}
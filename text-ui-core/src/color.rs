@@ -0,0 +1,275 @@
+//! Coloring the rendered art: one color, a gradient or a rainbow, as truecolor escapes or the
+//! nearest 256/16 color when the terminal can't show truecolor
+
+use std::{f64::consts::PI, str::FromStr};
+
+use crate::{
+    BannerConfig, GradientDirection,
+    canvas::{self, Cell, Line},
+    layout,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// `#rrggbb`, for SVG and CSS
+    pub fn hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+
+    /// The color `t` (0 to 1) of the way from this one to `to`
+    pub fn mix(self, to: Rgb, t: f64) -> Rgb {
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Rgb(mix(self.0, to.0), mix(self.1, to.1), mix(self.2, to.2))
+    }
+}
+
+/// xterm's default palette for the 16 basic colors, in ANSI order
+const PALETTE: [Rgb; 16] = [
+    Rgb(0, 0, 0),
+    Rgb(205, 0, 0),
+    Rgb(0, 205, 0),
+    Rgb(205, 205, 0),
+    Rgb(0, 0, 238),
+    Rgb(205, 0, 205),
+    Rgb(0, 205, 205),
+    Rgb(229, 229, 229),
+    Rgb(127, 127, 127),
+    Rgb(255, 0, 0),
+    Rgb(0, 255, 0),
+    Rgb(255, 255, 0),
+    Rgb(92, 92, 255),
+    Rgb(255, 0, 255),
+    Rgb(0, 255, 255),
+    Rgb(255, 255, 255),
+];
+
+const NAMED: [(&str, Rgb); 13] = [
+    ("black", PALETTE[0]),
+    ("red", PALETTE[9]),
+    ("green", PALETTE[10]),
+    ("yellow", PALETTE[11]),
+    ("blue", PALETTE[12]),
+    ("magenta", PALETTE[13]),
+    ("cyan", PALETTE[14]),
+    ("white", PALETTE[15]),
+    ("gray", PALETTE[8]),
+    ("grey", PALETTE[8]),
+    ("orange", Rgb(255, 165, 0)),
+    ("purple", Rgb(128, 0, 128)),
+    ("pink", Rgb(255, 192, 203)),
+];
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    /// A color name or `#rgb`/`#rrggbb`
+    fn from_str(s: &str) -> Result<Self, String> {
+        if let Some(hex) = s.strip_prefix('#') {
+            let digits: Option<Vec<u8>> = hex
+                .chars()
+                .map(|c| c.to_digit(16).map(|d| d as u8))
+                .collect();
+            return match digits.as_deref() {
+                Some(&[r, g, b]) => Ok(Rgb(r * 17, g * 17, b * 17)),
+                Some(&[r1, r2, g1, g2, b1, b2]) => {
+                    Ok(Rgb(r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2))
+                }
+                _ => Err(format!("Invalid color {} (expected #rgb or #rrggbb)", s)),
+            };
+        }
+
+        NAMED
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(s))
+            .map(|(_, color)| *color)
+            .ok_or_else(|| {
+                let names: Vec<&str> = NAMED.iter().map(|(name, _)| *name).collect();
+                format!(
+                    "Unknown color {} (use #rrggbb or one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// How many colors the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+    None,
+}
+
+impl Depth {
+    /// Guess from the environment: `$NO_COLOR` turns colors off, `$COLORTERM` announces
+    /// truecolor, and `$TERM` says whether there are 256 colors
+    pub fn detect() -> Self {
+        if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            return Depth::None;
+        }
+
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+        // Windows Terminal doesn't set COLORTERM
+        if colorterm == "truecolor"
+            || colorterm == "24bit"
+            || std::env::var_os("WT_SESSION").is_some()
+        {
+            return Depth::TrueColor;
+        }
+
+        let term = std::env::var("TERM").unwrap_or_default();
+        if term == "dumb" {
+            Depth::None
+        } else if term.contains("256color") {
+            Depth::Ansi256
+        } else {
+            Depth::Ansi16
+        }
+    }
+
+    /// Escape sequence setting the foreground to `color`, or its nearest match
+    pub fn escape(self, color: Rgb) -> String {
+        match self {
+            Depth::TrueColor => format!("\x1b[38;2;{};{};{}m", color.0, color.1, color.2),
+            Depth::Ansi256 => format!("\x1b[38;5;{}m", ansi256(color)),
+            Depth::Ansi16 => match nearest(color, PALETTE.iter().copied()) {
+                i @ 0..=7 => format!("\x1b[{}m", 30 + i),
+                i => format!("\x1b[{}m", 90 + i - 8),
+            },
+            Depth::None => String::new(),
+        }
+    }
+}
+
+/// Index of the color in `palette` closest to `color`
+fn nearest(color: Rgb, palette: impl Iterator<Item = Rgb>) -> usize {
+    let distance = |other: Rgb| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(color.0, other.0) + d(color.1, other.1) + d(color.2, other.2)
+    };
+    palette
+        .enumerate()
+        .min_by_key(|(_, other)| distance(*other))
+        .map_or(0, |(i, _)| i)
+}
+
+/// Nearest color in the 6x6x6 cube or the gray ramp of the 256-color palette (16 and up)
+fn ansi256(color: Rgb) -> usize {
+    16 + nearest(color, (16..=255).map(indexed))
+}
+
+/// Color `n` of xterm's 256-color palette
+pub fn indexed(n: u8) -> Rgb {
+    let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+    match n {
+        0..=15 => PALETTE[n as usize],
+        16..=231 => {
+            let n = n - 16;
+            Rgb(level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        _ => {
+            let gray = 8 + (n - 232) * 10;
+            Rgb(gray, gray, gray)
+        }
+    }
+}
+
+/// How the art is colored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Paint {
+    Solid(Rgb),
+    Gradient {
+        from: Rgb,
+        to: Rgb,
+        direction: GradientDirection,
+    },
+    Rainbow,
+}
+
+impl Paint {
+    /// The coloring the config asks for, `None` to leave the art uncolored
+    pub fn from_config(config: &BannerConfig) -> Result<Option<Self>, String> {
+        if config.rainbow {
+            return Ok(Some(Paint::Rainbow));
+        }
+
+        if let Some(gradient) = &config.gradient {
+            let (from, to) = gradient
+                .split_once("..")
+                .ok_or_else(|| format!("Invalid gradient {} (expected <from>..<to>)", gradient))?;
+            return Ok(Some(Paint::Gradient {
+                from: from.parse()?,
+                to: to.parse()?,
+                direction: config.gradient_direction,
+            }));
+        }
+
+        config
+            .color
+            .as_deref()
+            .map(|color| color.parse().map(Paint::Solid))
+            .transpose()
+    }
+
+    /// Color at column `x` of line `y`, in art `width` columns wide and `height` lines high
+    fn at(self, x: usize, y: usize, width: usize, height: usize) -> Rgb {
+        match self {
+            Paint::Solid(color) => color,
+            Paint::Gradient {
+                from,
+                to,
+                direction,
+            } => {
+                let (position, length) = match direction {
+                    GradientDirection::Horizontal => (x, width),
+                    GradientDirection::Vertical => (y, height),
+                };
+                let t = if length > 1 {
+                    position as f64 / (length - 1) as f64
+                } else {
+                    0.0
+                };
+                from.mix(to, t)
+            }
+            // lolcat's rainbow: a sine wave per channel, a third of a turn apart, running
+            // diagonally across the art
+            Paint::Rainbow => {
+                let i = (x + y * 2) as f64 * 0.1;
+                let wave = |phase: f64| ((i + phase).sin() * 127.0 + 128.0) as u8;
+                Rgb(wave(0.0), wave(2.0 * PI / 3.0), wave(4.0 * PI / 3.0))
+            }
+        }
+    }
+}
+
+/// Color the art's lines, over any colors the font gave them. Without a paint the font's
+/// colors stay. Whitespace is left uncolored so nothing shows on a colored background.
+pub fn paint(lines: Vec<Line>, paint: Option<Paint>) -> Vec<Line> {
+    let Some(paint) = paint else {
+        return lines;
+    };
+    let width = lines.iter().map(|l| canvas::width(l)).max().unwrap_or(0);
+    let height = lines.len();
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(y, line)| {
+            // Colors go by screen column, so wide characters don't stretch a gradient
+            let mut x = 0;
+            line.into_iter()
+                .map(|Cell { ch, .. }| {
+                    let cell = Cell {
+                        ch,
+                        color: (!ch.is_whitespace()).then(|| paint.at(x, y, width, height)),
+                    };
+                    x += layout::char_width(ch);
+                    cell
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -0,0 +1,265 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Everything about a banner, as the `[banner]` section of the workspace config holds it.
+/// `animate`, `speed`, `splash`, `duration` and `watch` are for the text-ui binary; rendering
+/// doesn't look at them.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BannerConfig {
+    /// Text to print, `-` for stdin. Each line is rendered as a row of art.
+    pub text: String,
+
+    /// Blank lines between rows of art
+    pub line_spacing: usize,
+
+    /// Built-in font (slant, standard, shadow, small) or a .flf or .tlf font looked up by name
+    pub font: String,
+
+    /// .flf or .tlf file to use instead of `font`
+    pub font_file: Option<PathBuf>,
+
+    /// Directory searched for `font` before figlet's own font directories
+    pub font_dir: Option<PathBuf>,
+
+    /// How characters are put together: as the font says, or full width, kerned or smushed
+    pub layout: Layout,
+
+    /// Smushing rules used with `layout = "smush"` instead of the font's own. Empty for the
+    /// font's rules; a font without any smushes by overlapping.
+    pub smush_rules: Vec<SmushRule>,
+
+    /// Which way the text runs. Runs going the other way (Hebrew in English, numbers in
+    /// Arabic) are put in order within it either way.
+    pub direction: Direction,
+
+    /// Drawn for characters the font doesn't have, `?` say. They're left out without it.
+    pub fallback: Option<String>,
+
+    /// Write characters the font doesn't have in ASCII (`é` as e, `北京` as Bei Jing) before
+    /// falling back to `fallback`
+    pub transliterate: bool,
+
+    /// Version info shown in the bottom right corner
+    pub info: Option<String>,
+
+    /// Color of the art: a name (red, cyan, ...) or #rrggbb
+    pub color: Option<String>,
+
+    /// Blend from one color to another, `<from>..<to>`; wins over `color`
+    pub gradient: Option<String>,
+
+    /// Which way `gradient` runs
+    pub gradient_direction: GradientDirection,
+
+    /// lolcat-style rainbow; wins over `gradient` and `color`
+    pub rainbow: bool,
+
+    /// Where the banner goes in the width of the terminal
+    pub align: Align,
+
+    /// What to do when the art is wider than the terminal
+    pub fit: Fit,
+
+    /// Box drawn around the banner
+    pub border: Border,
+
+    /// Spaces between the border and the art
+    pub padding: usize,
+
+    /// Set into the top of the border
+    pub title: Option<String>,
+
+    /// Where `info` goes
+    pub info_position: InfoPosition,
+
+    /// Format the banner is written in
+    pub output: Output,
+
+    /// How the banner is drawn in the terminal
+    pub animate: Animation,
+
+    /// Speed multiplier for `animate`
+    pub speed: f64,
+
+    /// Show the banner centered on an otherwise empty screen, then put back what was there
+    pub splash: bool,
+
+    /// Seconds the splash screen stays up; until a key is pressed without it
+    pub duration: Option<f64>,
+
+    /// Stay on screen, rendered again to fit whenever the terminal is resized
+    pub watch: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Layout {
+    /// Whatever the font's header asks for
+    #[default]
+    Default,
+    /// Every character at its full width
+    Full,
+    /// Characters moved together until they touch
+    Kern,
+    /// Characters moved one column further, merging where they touch
+    Smush,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Direction {
+    /// Left to right
+    #[default]
+    Ltr,
+    /// Right to left, as Hebrew and Arabic are written: the text is put together from the
+    /// right, words in left-to-right scripts keeping their own order
+    Rtl,
+}
+
+/// figlet's horizontal smushing rules
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum SmushRule {
+    /// Two equal characters become one
+    Equal,
+    /// An underscore gives way to `|/\[]{}()<>`
+    Lowline,
+    /// Of `|`, `/\`, `[]`, `{}`, `()` and `<>`, the later class wins
+    Hierarchy,
+    /// Opposite brackets `[]`, `{}` and `()` become `|`
+    Pair,
+    /// `/\` becomes `|`, `\/` becomes `Y` and `><` becomes `X`
+    Bigx,
+    /// Two hardblanks become one
+    Hardblank,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum GradientDirection {
+    /// Left to right
+    #[default]
+    Horizontal,
+    /// Top to bottom
+    Vertical,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Fit {
+    /// Render with a narrower built-in font
+    Shrink,
+    /// Break the text onto more lines of art
+    #[default]
+    Wrap,
+    /// Cut the text short, ending in "..."
+    Truncate,
+    /// Let the terminal wrap it
+    Off,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Border {
+    #[default]
+    None,
+    Single,
+    Double,
+    Rounded,
+    Ascii,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum InfoPosition {
+    /// On a line of its own under the art
+    #[default]
+    Newline,
+    /// In the blank end of the art's last line, when it fits there (under the art otherwise)
+    Overlay,
+    /// In the bottom edge of the border (under the art without one)
+    Border,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Output {
+    /// Text with color escapes, as far as the terminal supports them
+    #[default]
+    Ansi,
+    /// Text without colors
+    Plain,
+    /// A standalone SVG image
+    Svg,
+    /// An HTML snippet with inline styles
+    Html,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+pub enum Animation {
+    /// All at once
+    #[default]
+    None,
+    /// Column by column
+    Typewriter,
+    /// Line by line
+    Scroll,
+    /// Fading in from dark
+    Fade,
+}
+
+impl Default for BannerConfig {
+    fn default() -> Self {
+        Self {
+            text: "vagent".to_string(),
+            line_spacing: 1,
+            font: "slant".to_string(),
+            font_file: None,
+            font_dir: None,
+            layout: Layout::default(),
+            smush_rules: Vec::new(),
+            direction: Direction::default(),
+            fallback: None,
+            transliterate: false,
+            info: None,
+            color: None,
+            gradient: None,
+            gradient_direction: GradientDirection::default(),
+            rainbow: false,
+            align: Align::default(),
+            fit: Fit::default(),
+            border: Border::default(),
+            padding: 1,
+            title: None,
+            info_position: InfoPosition::default(),
+            output: Output::default(),
+            animate: Animation::default(),
+            speed: 1.0,
+            splash: false,
+            duration: None,
+            watch: false,
+        }
+    }
+}
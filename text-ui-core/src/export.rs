@@ -0,0 +1,135 @@
+//! `--output`: the finished banner as terminal text (with or without colors), a standalone SVG
+//! image, or an HTML snippet
+
+use std::fmt::Write as _;
+
+use crate::{
+    canvas::{self, Cell, Line},
+    color::Depth,
+};
+
+/// Colors used where the banner has none, matching a dark terminal
+const BACKGROUND: &str = "#1e1e1e";
+const FOREGROUND: &str = "#d4d4d4";
+
+/// Text with color escapes for a terminal of `depth`
+pub fn ansi(lines: &[Line], depth: Depth) -> String {
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(&ansi_line(line, depth));
+        out.push('\n');
+    }
+    out
+}
+
+/// One line of `ansi`, without the line break
+pub fn ansi_line(line: &[Cell], depth: Depth) -> String {
+    let mut out = String::new();
+    let mut colored = false;
+    for (color, text) in canvas::runs(line) {
+        match color.filter(|_| depth != Depth::None) {
+            Some(color) => {
+                out.push_str(&depth.escape(color));
+                colored = true;
+            }
+            None if colored => {
+                out.push_str("\x1b[0m");
+                colored = false;
+            }
+            None => {}
+        }
+        out.push_str(&text);
+    }
+    if colored {
+        out.push_str("\x1b[0m");
+    }
+    out
+}
+
+pub fn plain(lines: &[Line]) -> String {
+    let mut out = String::new();
+    for line in lines {
+        out.extend(line.iter().map(|cell| cell.ch));
+        out.push('\n');
+    }
+    out
+}
+
+/// Size of a cell in the SVG, in pixels, for a 14px monospace font
+const CELL_WIDTH: f64 = 8.4;
+const LINE_HEIGHT: f64 = 17.0;
+const MARGIN: f64 = 10.0;
+
+pub fn svg(lines: &[Line]) -> String {
+    let columns = lines.iter().map(|l| canvas::width(l)).max().unwrap_or(0);
+    let width = columns as f64 * CELL_WIDTH + 2.0 * MARGIN;
+    let height = lines.len() as f64 * LINE_HEIGHT + 2.0 * MARGIN;
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">
+<rect width="100%" height="100%" fill="{BACKGROUND}"/>
+<g font-family="Consolas, 'DejaVu Sans Mono', monospace" font-size="14" fill="{FOREGROUND}" xml:space="preserve">
+"#
+    );
+    for (y, line) in lines.iter().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+        // textLength keeps the columns lined up whatever monospace font the viewer picks
+        let _ = write!(
+            svg,
+            r#"<text x="{}" y="{}" textLength="{}" lengthAdjust="spacingAndGlyphs">"#,
+            MARGIN,
+            MARGIN + (y + 1) as f64 * LINE_HEIGHT - 4.0,
+            canvas::width(line) as f64 * CELL_WIDTH
+        );
+        for (color, text) in canvas::runs(line) {
+            match color {
+                Some(color) => {
+                    let _ = write!(
+                        svg,
+                        r#"<tspan fill="{}">{}</tspan>"#,
+                        color.hex(),
+                        escape(&text)
+                    );
+                }
+                None => svg.push_str(&escape(&text)),
+            }
+        }
+        svg.push_str("</text>\n");
+    }
+    svg.push_str("</g>\n</svg>\n");
+    svg
+}
+
+/// A `<pre>` with inline styles, to paste into a page
+pub fn html(lines: &[Line]) -> String {
+    let mut html = format!(
+        r#"<pre style="margin: 0; padding: 12px; background: {BACKGROUND}; color: {FOREGROUND}; font-family: Consolas, 'Courier New', monospace; line-height: 1.2;">"#
+    );
+    for line in lines {
+        for (color, text) in canvas::runs(line) {
+            match color {
+                Some(color) => {
+                    let _ = write!(
+                        html,
+                        r#"<span style="color: {};">{}</span>"#,
+                        color.hex(),
+                        escape(&text)
+                    );
+                }
+                None => html.push_str(&escape(&text)),
+            }
+        }
+        html.push('\n');
+    }
+    html.push_str("</pre>\n");
+    html
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
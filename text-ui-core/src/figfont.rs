@@ -0,0 +1,545 @@
+//! FIGfont (`.flf`) fonts, and what they share with TOIlet's: the file layout, and putting
+//! characters side by side the way figlet does, full width, kerned or smushed together as the
+//! font (or `--layout`) says
+
+use std::collections::HashMap;
+
+use crate::{
+    Layout, SmushRule,
+    canvas::{self, Cell, Line},
+    font::Font,
+};
+
+// Horizontal bits of the header's full_layout field
+const EQUAL: u32 = 1;
+const LOWLINE: u32 = 2;
+const HIERARCHY: u32 = 4;
+const PAIR: u32 = 8;
+const BIGX: u32 = 16;
+const HARDBLANK: u32 = 32;
+const RULES: u32 = 63;
+const KERN: u32 = 64;
+const SMUSH: u32 = 128;
+
+/// Characters every font has after printable ASCII and before any code-tagged ones: the
+/// Deutsch umlauts and ß
+const DEUTSCH: [u32; 7] = [196, 214, 220, 228, 246, 252, 223];
+
+#[derive(Debug, Clone)]
+pub struct FigFont(Glyphs);
+
+impl FigFont {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        Glyphs::parse(content, "flf2a", canvas::plain).map(FigFont)
+    }
+}
+
+impl Font for FigFont {
+    fn render(&self, text: &str) -> Vec<Line> {
+        self.0.render(text)
+    }
+
+    fn has(&self, c: char) -> bool {
+        self.0.has(c)
+    }
+
+    fn set_layout(&mut self, layout: Layout, rules: &[SmushRule]) {
+        self.0.set_layout(layout, rules);
+    }
+}
+
+/// The characters of a FIGlet-style font, and how they're put together
+#[derive(Debug, Clone)]
+pub struct Glyphs {
+    hardblank: char,
+    height: usize,
+    /// Horizontal layout, in the bits of full_layout
+    layout: u32,
+    glyphs: HashMap<char, Vec<Line>>,
+}
+
+impl Glyphs {
+    /// Read a font whose header starts with `signature`, turning each line of a glyph (its
+    /// endmarks removed) into cells with `row`
+    pub fn parse(
+        content: &str,
+        signature: &str,
+        row: impl Fn(&str) -> Line,
+    ) -> Result<Self, String> {
+        let mut lines = content.lines();
+        let header = lines.next().ok_or("empty file")?;
+        let mut rest = header
+            .strip_prefix(signature)
+            .ok_or_else(|| format!("no {} signature", signature))?
+            .chars();
+        let hardblank = rest.next().ok_or("no hardblank in the header")?;
+        // height baseline max_length old_layout comment_lines print_direction full_layout ...
+        let fields: Vec<i64> = rest
+            .as_str()
+            .split_whitespace()
+            .map_while(|field| field.parse().ok())
+            .collect();
+        let field = |i: usize| fields.get(i).copied();
+        let height = field(0)
+            .filter(|height| *height > 0)
+            .ok_or("no height in the header")? as usize;
+        let layout = match (field(6), field(3).unwrap_or(0)) {
+            (Some(full), _) => full as u32 & (RULES | KERN | SMUSH),
+            (None, -1) => 0,
+            (None, 0) => KERN,
+            (None, old) => SMUSH | (old as u32 & RULES),
+        };
+
+        let mut lines = lines.skip(field(4).unwrap_or(0).max(0) as usize);
+        let mut glyphs = HashMap::new();
+        for code in (32..127).chain(DEUTSCH) {
+            // Some fonts stop early
+            let Some(glyph) = read_glyph(&mut lines, height, &row) else {
+                break;
+            };
+            glyphs.extend(char::from_u32(code).map(|c| (c, glyph)));
+        }
+        // The rest each start with a line holding their code
+        while let Some(tag) = lines.next() {
+            let code = tag.split_whitespace().next().and_then(parse_code);
+            let Some(glyph) = read_glyph(&mut lines, height, &row) else {
+                break;
+            };
+            // Negative codes are for translation tables, not characters
+            if let Some(c) = code
+                .and_then(|code| u32::try_from(code).ok())
+                .and_then(char::from_u32)
+            {
+                glyphs.insert(c, glyph);
+            }
+        }
+
+        Ok(Self {
+            hardblank,
+            height,
+            layout,
+            glyphs,
+        })
+    }
+
+    /// Put characters together as `layout` says instead of the font. `rules` replace the
+    /// font's smushing rules, if any are given.
+    pub fn set_layout(&mut self, layout: Layout, rules: &[SmushRule]) {
+        let rules = match rules {
+            [] => self.layout & RULES,
+            rules => rules
+                .iter()
+                .map(|rule| rule_bit(*rule))
+                .fold(0, |a, b| a | b),
+        };
+        self.layout = match layout {
+            Layout::Default if self.layout & SMUSH != 0 => SMUSH | rules,
+            Layout::Default => self.layout,
+            Layout::Full => 0,
+            Layout::Kern => KERN,
+            Layout::Smush => SMUSH | rules,
+        };
+    }
+
+    /// Whether the font has a glyph for `c`
+    pub fn has(&self, c: char) -> bool {
+        self.glyphs.contains_key(&c)
+    }
+
+    /// `text` as rows of art, as many as the font is high. Characters the font doesn't have
+    /// are left out.
+    pub fn render(&self, text: &str) -> Vec<Line> {
+        let mut rows: Vec<Line> = vec![Line::new(); self.height];
+        let mut previous_width = 0;
+        for c in text.chars() {
+            let Some(glyph) = self.glyphs.get(&c) else {
+                continue;
+            };
+            let width = glyph.iter().map(Vec::len).max().unwrap_or(0);
+            let overlap = self.overlap(&rows, glyph, previous_width, width);
+            for (row, part) in rows.iter_mut().zip(glyph) {
+                let mut part = part.clone();
+                part.resize(
+                    width,
+                    Cell {
+                        ch: ' ',
+                        color: None,
+                    },
+                );
+                let len = row.len();
+                // Columns hanging off the start of the line are dropped, like figlet does
+                for (k, &right) in part.iter().enumerate().take(overlap) {
+                    if let Some(i) = (len + k).checked_sub(overlap) {
+                        let left = row[i];
+                        let ch = self
+                            .smush(left.ch, right.ch, previous_width, width)
+                            .unwrap_or(right.ch);
+                        // The character that won keeps its color
+                        let color = if ch == left.ch && ch != right.ch {
+                            left.color
+                        } else {
+                            right.color
+                        };
+                        row[i] = Cell { ch, color };
+                    }
+                }
+                row.extend(&part[overlap..]);
+            }
+            previous_width = width;
+        }
+
+        rows.into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|cell| {
+                        if cell.ch == self.hardblank {
+                            Cell {
+                                ch: ' ',
+                                color: None,
+                            }
+                        } else {
+                            cell
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// How many columns the next character can move left into the line, the least any of
+    /// its rows can
+    fn overlap(&self, rows: &[Line], glyph: &[Line], previous_width: usize, width: usize) -> usize {
+        if self.layout & (KERN | SMUSH) == 0 {
+            return 0;
+        }
+        rows.iter()
+            .zip(glyph)
+            .map(|(row, part)| {
+                let start = part
+                    .iter()
+                    .position(|cell| cell.ch != ' ')
+                    .unwrap_or(part.len());
+                match row.iter().rposition(|cell| cell.ch != ' ') {
+                    // Blank up to here, so the character can slide all the way over
+                    None => start + row.len(),
+                    Some(end) => {
+                        let gap = start + row.len() - 1 - end;
+                        let touches = part.get(start).is_some_and(|right| {
+                            self.smush(row[end].ch, right.ch, previous_width, width)
+                                .is_some()
+                        });
+                        gap + usize::from(touches)
+                    }
+                }
+            })
+            .fold(width, usize::min)
+    }
+
+    /// What `left` and `right` become in one column, if they can share one
+    fn smush(&self, left: char, right: char, previous_width: usize, width: usize) -> Option<char> {
+        if left == ' ' {
+            return Some(right);
+        }
+        if right == ' ' {
+            return Some(left);
+        }
+        // Characters one column wide never overlap
+        if previous_width < 2 || width < 2 || self.layout & SMUSH == 0 {
+            return None;
+        }
+
+        let hardblank = self.hardblank;
+        if self.layout & RULES == 0 {
+            // Universal smushing: the later character wins, except over a hardblank
+            return Some(if right == hardblank { left } else { right });
+        }
+        if self.layout & HARDBLANK != 0 && left == hardblank && right == hardblank {
+            return Some(hardblank);
+        }
+        if left == hardblank || right == hardblank {
+            return None;
+        }
+        if self.layout & EQUAL != 0 && left == right {
+            return Some(left);
+        }
+        if self.layout & LOWLINE != 0 {
+            if left == '_' && "|/\\[]{}()<>".contains(right) {
+                return Some(right);
+            }
+            if right == '_' && "|/\\[]{}()<>".contains(left) {
+                return Some(left);
+            }
+        }
+        if self.layout & HIERARCHY != 0
+            && let (Some(l), Some(r)) = (class(left), class(right))
+            && l != r
+        {
+            return Some(if l > r { left } else { right });
+        }
+        if self.layout & PAIR != 0
+            && matches!(
+                (left, right),
+                ('[', ']') | (']', '[') | ('{', '}') | ('}', '{') | ('(', ')') | (')', '(')
+            )
+        {
+            return Some('|');
+        }
+        if self.layout & BIGX != 0 {
+            match (left, right) {
+                ('/', '\\') => return Some('|'),
+                ('\\', '/') => return Some('Y'),
+                ('>', '<') => return Some('X'),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+fn rule_bit(rule: SmushRule) -> u32 {
+    match rule {
+        SmushRule::Equal => EQUAL,
+        SmushRule::Lowline => LOWLINE,
+        SmushRule::Hierarchy => HIERARCHY,
+        SmushRule::Pair => PAIR,
+        SmushRule::Bigx => BIGX,
+        SmushRule::Hardblank => HARDBLANK,
+    }
+}
+
+/// Rank of a character for the hierarchy rule
+fn class(c: char) -> Option<usize> {
+    ["|", "/\\", "[]", "{}", "()", "<>"]
+        .iter()
+        .position(|class| class.contains(c))
+}
+
+/// The next `height` lines, without their endmarks
+fn read_glyph<'a>(
+    lines: &mut impl Iterator<Item = &'a str>,
+    height: usize,
+    row: impl Fn(&str) -> Line,
+) -> Option<Vec<Line>> {
+    (0..height)
+        .map(|_| {
+            let line = lines.next()?.trim_end();
+            // The endmark is the last character, doubled on a character's last line
+            let line = match line.chars().last() {
+                Some(endmark) => line.trim_end_matches(endmark),
+                None => line,
+            };
+            Some(row(line))
+        })
+        .collect()
+}
+
+/// A character code as fonts write them: decimal, 0x hex or 0 octal, maybe negative
+fn parse_code(code: &str) -> Option<i64> {
+    let (negative, digits) = match code.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, code),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines
+            .iter()
+            .map(|line| line.iter().map(|cell| cell.ch).collect())
+            .collect()
+    }
+
+    /// A one row font with `$` as hardblank: `glyphs` as given, every other printable ASCII
+    /// character two blank columns
+    fn font(glyphs: &[(char, &str)], layout: Layout, rules: &[SmushRule]) -> Glyphs {
+        let mut content = "flf2a$ 1 1 4 -1 0\n".to_string();
+        for code in 32..127u8 {
+            let glyph = glyphs
+                .iter()
+                .find(|(c, _)| *c == code as char)
+                .map_or("  ", |(_, glyph)| glyph);
+            content.push_str(&format!("{}@@\n", glyph));
+        }
+        let mut font = Glyphs::parse(&content, "flf2a", canvas::plain).unwrap();
+        font.set_layout(layout, rules);
+        font
+    }
+
+    fn render(font: &Glyphs, text: &str) -> String {
+        font.render(text)[0].iter().map(|cell| cell.ch).collect()
+    }
+
+    #[test]
+    fn each_rule_smushes_its_own_pairs() {
+        let cases = [
+            (SmushRule::Equal, [('|', '|', '|'), ('#', '#', '#')].as_slice()),
+            (SmushRule::Lowline, &[('_', '/', '/'), ('[', '_', '[')]),
+            (SmushRule::Hierarchy, &[('|', '/', '/'), ('<', '{', '<'), (')', ']', ')')]),
+            (SmushRule::Pair, &[('[', ']', '|'), ('}', '{', '|'), ('(', ')', '|')]),
+            (SmushRule::Bigx, &[('/', '\\', '|'), ('\\', '/', 'Y'), ('>', '<', 'X')]),
+            (SmushRule::Hardblank, &[('$', '$', '$')]),
+        ];
+        for (rule, pairs) in cases {
+            let font = font(&[], Layout::Smush, &[rule]);
+            for &(left, right, smushed) in pairs {
+                assert_eq!(font.smush(left, right, 2, 2), Some(smushed), "{:?}", rule);
+            }
+            // None of the other rules' pairs
+            for (other, pairs) in cases.iter().filter(|(other, _)| *other != rule) {
+                for &(left, right, _) in *pairs {
+                    // Hierarchy ranks all but one of the other brackets and slashes
+                    if rule == SmushRule::Hierarchy && class(left).zip(class(right)).is_some() {
+                        continue;
+                    }
+                    assert_eq!(font.smush(left, right, 2, 2), None, "{:?} {:?}", rule, other);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unrelated_characters_stay_apart() {
+        let font = font(&[], Layout::Smush, &[]);
+        let all = [
+            SmushRule::Equal,
+            SmushRule::Lowline,
+            SmushRule::Hierarchy,
+            SmushRule::Pair,
+            SmushRule::Bigx,
+            SmushRule::Hardblank,
+        ];
+        let ruled = self::font(&[], Layout::Smush, &all);
+        assert_eq!(font.smush(' ', 'a', 2, 2), Some('a'));
+        assert_eq!(ruled.smush('a', ' ', 2, 2), Some('a'));
+        for (left, right) in [('a', 'b'), ('a', '|'), ('/', '/'), ('$', 'a'), ('<', '>')] {
+            if left == right {
+                assert_eq!(ruled.smush(left, right, 2, 2), Some(left));
+            } else {
+                assert_eq!(ruled.smush(left, right, 2, 2), None, "{:?}", (left, right));
+            }
+        }
+        // Never when either character is a single column
+        assert_eq!(ruled.smush('|', '|', 1, 2), None);
+        assert_eq!(ruled.smush('|', '|', 2, 1), None);
+    }
+
+    #[test]
+    fn universal_smushing_lets_the_later_character_win() {
+        let font = font(&[], Layout::Smush, &[]);
+        assert_eq!(font.layout & RULES, 0);
+        assert_eq!(font.smush('a', 'b', 2, 2), Some('b'));
+        assert_eq!(font.smush('a', '$', 2, 2), Some('a'));
+        assert_eq!(font.smush('$', 'a', 2, 2), Some('a'));
+    }
+
+    #[test]
+    fn layouts_move_characters_together() {
+        let glyphs = [('a', "a "), ('b', " b"), ('m', "mm")];
+        let full = font(&glyphs, Layout::Full, &[]);
+        assert_eq!(render(&full, "ab"), "a  b");
+        assert_eq!(render(&full, "mm"), "mmmm");
+
+        let kern = font(&glyphs, Layout::Kern, &[]);
+        assert_eq!(render(&kern, "ab"), "ab");
+        assert_eq!(render(&kern, "mm"), "mmmm");
+        // Blank columns in front of the first character go too
+        assert_eq!(render(&kern, "ba"), "ba ");
+
+        let smush = font(&glyphs, Layout::Smush, &[SmushRule::Equal]);
+        assert_eq!(render(&smush, "ab"), "ab");
+        assert_eq!(render(&smush, "mm"), "mmm");
+        assert_eq!(render(&smush, "mma"), "mmma ");
+    }
+
+    #[test]
+    fn hardblanks_hold_characters_apart_and_render_blank() {
+        let glyphs = [('a', "a$"), ('b', "$b")];
+        assert_eq!(render(&font(&glyphs, Layout::Kern, &[]), "ab"), "a  b");
+        let equal = font(&glyphs, Layout::Smush, &[SmushRule::Equal]);
+        assert_eq!(render(&equal, "ab"), "a  b");
+        let hardblank = font(&glyphs, Layout::Smush, &[SmushRule::Hardblank]);
+        assert_eq!(render(&hardblank, "ab"), "a b");
+        assert_eq!(render(&font(&glyphs, Layout::Smush, &[]), "ab"), "a b");
+    }
+
+    /// What figlet prints for the same text with the embedded fonts
+    #[test]
+    fn embedded_fonts_render_like_figlet() {
+        let cases = [
+            (
+                include_str!("../fonts/standard.flf"),
+                "Hello World",
+                [
+                    r" _   _      _ _        __        __         _     _ ",
+                    r"| | | | ___| | | ___   \ \      / /__  _ __| | __| |",
+                    r"| |_| |/ _ \ | |/ _ \   \ \ /\ / / _ \| '__| |/ _` |",
+                    r"|  _  |  __/ | | (_) |   \ V  V / (_) | |  | | (_| |",
+                    r"|_| |_|\___|_|_|\___/     \_/\_/ \___/|_|  |_|\__,_|",
+                    r"                                                    ",
+                ]
+                .as_slice(),
+            ),
+            (
+                include_str!("../fonts/standard.flf"),
+                "figlet",
+                &[
+                    r"  __ _       _      _   ",
+                    r" / _(_) __ _| | ___| |_ ",
+                    r"| |_| |/ _` | |/ _ \ __|",
+                    r"|  _| | (_| | |  __/ |_ ",
+                    r"|_| |_|\__, |_|\___|\__|",
+                    r"       |___/            ",
+                ],
+            ),
+            (
+                include_str!("../fonts/slant.flf"),
+                "Hello World",
+                &[
+                    r"    __  __     ____         _       __           __    __",
+                    r"   / / / /__  / / /___     | |     / /___  _____/ /___/ /",
+                    r"  / /_/ / _ \/ / / __ \    | | /| / / __ \/ ___/ / __  / ",
+                    r" / __  /  __/ / / /_/ /    | |/ |/ / /_/ / /  / / /_/ /  ",
+                    r"/_/ /_/\___/_/_/\____/     |__/|__/\____/_/  /_/\__,_/   ",
+                    r"                                                         ",
+                ],
+            ),
+            (
+                include_str!("../fonts/small.flf"),
+                "Hello",
+                &[
+                    r" _  _     _ _     ",
+                    r"| || |___| | |___ ",
+                    r"| __ / -_) | / _ \",
+                    r"|_||_\___|_|_\___/",
+                    r"                  ",
+                ],
+            ),
+            (
+                include_str!("../fonts/shadow.flf"),
+                "Hello",
+                &[
+                    r" |   |      | |       ",
+                    r" |   |  _ \ | |  _ \  ",
+                    r" ___ |  __/ | | (   | ",
+                    r"_|  _|\___|_|_|\___/  ",
+                    r"                      ",
+                ],
+            ),
+        ];
+        for (content, input, expected) in cases {
+            let font = FigFont::parse(content).unwrap();
+            assert_eq!(text(&font.render(input)), expected, "{}", input);
+        }
+    }
+}
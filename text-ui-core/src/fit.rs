@@ -0,0 +1,169 @@
+//! Rendering text with a font, one row of art per line of text, and `--fit`: what to do when
+//! the art is wider than the terminal
+
+use crate::{
+    BannerConfig, Fit, bidi,
+    canvas::{self, Line},
+    font::{self, Font},
+};
+
+/// A line of `text` as lines of art, without trailing blank lines: missing characters
+/// substituted, then put in the order they're drawn in
+pub fn render(font: &dyn Font, text: &str, config: &BannerConfig) -> Vec<Line> {
+    let text = bidi::visual(&font::substitute(font, text, config), config.direction);
+    let mut lines = font.render(&text);
+    // Remove trailing blank lines to keep control over spacing
+    while lines
+        .last()
+        .is_some_and(|line| line.iter().all(|cell| cell.ch.is_whitespace()))
+    {
+        lines.pop();
+    }
+    lines
+}
+
+/// Widest line of some art
+pub fn width(lines: &[Line]) -> usize {
+    lines.iter().map(|l| canvas::width(l)).max().unwrap_or(0)
+}
+
+/// Rows of art on top of each other, `spacing` blank lines apart
+fn stack(rows: Vec<Vec<Line>>, spacing: usize) -> Vec<Line> {
+    let mut lines = Vec::new();
+    for (i, row) in rows.into_iter().filter(|row| !row.is_empty()).enumerate() {
+        if i > 0 {
+            lines.extend(std::iter::repeat_n(Line::new(), spacing));
+        }
+        lines.extend(row);
+    }
+    lines
+}
+
+/// Render each line of `text` as a row of art, fitting them into `available` columns as
+/// `config.fit` says
+pub fn fit(
+    font: &dyn Font,
+    text: &str,
+    config: &BannerConfig,
+    available: Option<usize>,
+) -> Vec<Line> {
+    let spacing = config.line_spacing;
+    let texts: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let rows: Vec<Vec<Line>> = texts
+        .iter()
+        .map(|text| render(font, text, config))
+        .collect();
+    let Some(available) = available else {
+        return stack(rows, spacing);
+    };
+    let fits = |row: &Vec<Line>| width(row) <= available;
+    if rows.iter().all(&fits) {
+        return stack(rows, spacing);
+    }
+
+    let rows = match config.fit {
+        Fit::Off => rows,
+        Fit::Shrink => shrink(&texts, config, available).unwrap_or(rows),
+        Fit::Wrap => texts
+            .iter()
+            .zip(rows)
+            .flat_map(|(text, row)| {
+                if fits(&row) {
+                    vec![row]
+                } else {
+                    wrap(font, text, config, available)
+                }
+            })
+            .collect(),
+        Fit::Truncate => texts
+            .iter()
+            .zip(rows)
+            .map(|(text, row)| {
+                if fits(&row) {
+                    row
+                } else {
+                    truncate(font, text, config, available).unwrap_or(row)
+                }
+            })
+            .collect(),
+    };
+    stack(rows, spacing)
+}
+
+/// Every line in the widest built-in font they all fit in, or else the narrowest
+fn shrink(texts: &[&str], config: &BannerConfig, available: usize) -> Option<Vec<Vec<Line>>> {
+    let widest = |rows: &Vec<Vec<Line>>| rows.iter().map(|row| width(row)).max().unwrap_or(0);
+    let mut renders: Vec<Vec<Vec<Line>>> = font::embedded(config)
+        .map(|font| {
+            texts
+                .iter()
+                .map(|text| render(&*font, text, config))
+                .collect()
+        })
+        .collect();
+    renders.sort_by_key(widest);
+    let narrowest = renders.first().cloned();
+    renders
+        .into_iter()
+        .rev()
+        .find(|rows| widest(rows) <= available)
+        .or(narrowest)
+}
+
+/// Break a line of text over several rows of art, between words where possible
+fn wrap(font: &dyn Font, text: &str, config: &BannerConfig, available: usize) -> Vec<Vec<Line>> {
+    let fits = |text: &str| width(&render(font, text, config)) <= available;
+
+    let mut texts: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let joined = if current.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", current, word)
+        };
+        if fits(&joined) {
+            current = joined;
+            continue;
+        }
+
+        if !current.is_empty() {
+            texts.push(std::mem::take(&mut current));
+        }
+        // A word too wide on its own is split wherever it has to be
+        for c in word.chars() {
+            let longer = format!("{}{}", current, c);
+            if current.is_empty() || fits(&longer) {
+                current = longer;
+            } else {
+                texts.push(std::mem::replace(&mut current, c.to_string()));
+            }
+        }
+    }
+    if !current.is_empty() {
+        texts.push(current);
+    }
+
+    texts
+        .iter()
+        .map(|text| render(font, text, config))
+        .collect()
+}
+
+/// The longest start of a line of text that fits with "..." after it
+fn truncate(
+    font: &dyn Font,
+    text: &str,
+    config: &BannerConfig,
+    available: usize,
+) -> Option<Vec<Line>> {
+    let chars: Vec<char> = text.trim_end().chars().collect();
+    (0..chars.len()).rev().find_map(|len| {
+        let shortened: String = chars[..len].iter().collect();
+        let lines = render(font, &format!("{}...", shortened.trim_end()), config);
+        (width(&lines) <= available).then_some(lines)
+    })
+}
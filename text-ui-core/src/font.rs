@@ -0,0 +1,148 @@
+//! Finding the font to render with: one of the fonts built into the binary, a `--font-file`, or
+//! `<name>.flf` or `<name>.tlf` in `--font-dir` and the places figlet (and TOIlet) install
+//! their fonts
+
+use std::path::{Path, PathBuf};
+
+use crate::{BannerConfig, Layout, SmushRule, canvas::Line, figfont::FigFont, tlf::TlfFont};
+
+/// A font text can be rendered in, whatever its file format
+pub trait Font {
+    /// `text` as rows of art, as many as the font is high. Characters the font doesn't have
+    /// are left out.
+    fn render(&self, text: &str) -> Vec<Line>;
+
+    /// Whether the font has a glyph for `c`
+    fn has(&self, c: char) -> bool;
+
+    /// Put characters together as `layout` says instead of the font. `rules` replace the
+    /// font's smushing rules, if any are given.
+    fn set_layout(&mut self, layout: Layout, rules: &[SmushRule]);
+}
+
+const EMBEDDED: [(&str, &str); 4] = [
+    ("slant", include_str!("../fonts/slant.flf")),
+    ("standard", include_str!("../fonts/standard.flf")),
+    ("shadow", include_str!("../fonts/shadow.flf")),
+    ("small", include_str!("../fonts/small.flf")),
+];
+
+/// Where figlet packages put their fonts
+const SYSTEM_DIRS: [&str; 5] = [
+    "/usr/share/figlet",
+    "/usr/share/figlet/fonts",
+    "/usr/local/share/figlet",
+    "/usr/local/share/figlet/fonts",
+    "/opt/homebrew/share/figlet/fonts",
+];
+
+/// `text` with the characters `font` doesn't have written in ASCII (`é` as e, `北京` as Bei
+/// Jing) with `transliterate`, and whatever is still missing replaced with `fallback`. Left
+/// for the font to leave out without either.
+pub fn substitute(font: &dyn Font, text: &str, config: &BannerConfig) -> String {
+    let mut substituted = String::new();
+    let mut push = |c: char| match &config.fallback {
+        _ if font.has(c) => substituted.push(c),
+        Some(fallback) => substituted.push_str(fallback),
+        None => {}
+    };
+    for c in text.chars() {
+        match deunicode::deunicode_char(c).filter(|_| config.transliterate && !font.has(c)) {
+            Some(ascii) => ascii.chars().for_each(&mut push),
+            None => push(c),
+        }
+    }
+    // Transliterations end with a space to keep words apart, not wanted after the last one
+    if !text.ends_with(char::is_whitespace) {
+        substituted.truncate(substituted.trim_end().len());
+    }
+    substituted
+}
+
+/// The configured font, laid out as `layout` says
+pub fn load(config: &BannerConfig) -> Result<Box<dyn Font>, String> {
+    let mut font = find(config)?;
+    font.set_layout(config.layout, &config.smush_rules);
+    Ok(font)
+}
+
+fn find(config: &BannerConfig) -> Result<Box<dyn Font>, String> {
+    if let Some(path) = &config.font_file {
+        return from_file(path);
+    }
+
+    let name = config.font.strip_suffix(".flf").unwrap_or(&config.font);
+    if let Some((_, content)) = EMBEDDED.iter().find(|(embedded, _)| *embedded == name) {
+        return parse(content);
+    }
+
+    // A FIGfont wins over a TOIlet font of the same name, as in TOIlet
+    let file_names = match name.strip_suffix(".tlf") {
+        Some(_) => vec![name.to_string()],
+        None => vec![format!("{}.flf", name), format!("{}.tlf", name)],
+    };
+    match search_dirs(config)
+        .into_iter()
+        .flat_map(|dir| file_names.iter().map(move |file_name| dir.join(file_name)))
+        .find(|path| path.is_file())
+    {
+        Some(path) => from_file(&path),
+        None => Err(format!(
+            "Unknown font {} (built in: {}; searched {})",
+            name,
+            EMBEDDED.map(|(name, _)| name).join(", "),
+            search_dirs(config)
+                .iter()
+                .map(|dir| dir.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// The fonts built into the binary, laid out as `layout` says
+pub fn embedded(config: &BannerConfig) -> impl Iterator<Item = Box<dyn Font>> {
+    EMBEDDED.iter().filter_map(|(_, content)| {
+        let mut font = parse(content).ok()?;
+        font.set_layout(config.layout, &config.smush_rules);
+        Some(font)
+    })
+}
+
+/// `--font-dir` first, then `$FIGLET_FONTDIR` (as figlet itself uses), then the system dirs
+fn search_dirs(config: &BannerConfig) -> Vec<PathBuf> {
+    config
+        .font_dir
+        .iter()
+        .cloned()
+        .chain(std::env::var_os("FIGLET_FONTDIR").map(PathBuf::from))
+        .chain(SYSTEM_DIRS.iter().map(PathBuf::from))
+        .collect()
+}
+
+/// A font in either format, told apart by the signature it starts with
+fn parse(content: &str) -> Result<Box<dyn Font>, String> {
+    if content.starts_with("tlf2a") {
+        Ok(Box::new(TlfFont::parse(content)?))
+    } else {
+        Ok(Box::new(FigFont::parse(content)?))
+    }
+}
+
+fn from_file(path: &Path) -> Result<Box<dyn Font>, String> {
+    let bytes =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    // figlet and TOIlet also read fonts stored as a zip archive, which we can't
+    if bytes.starts_with(b"PK\x03\x04") {
+        return Err(format!(
+            "Font {} is zipped; unzip it to use it",
+            path.display()
+        ));
+    }
+    // Older fonts are Latin-1 rather than UTF-8
+    let content = match String::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(e) => e.into_bytes().iter().map(|&b| b as char).collect(),
+    };
+    parse(&content).map_err(|e| format!("Invalid font {}: {}", path.display(), e))
+}
@@ -0,0 +1,99 @@
+//! Measuring text on screen, and placing the banner in the columns it has
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::Align;
+
+/// Columns taken up by `text` on screen: two for CJK and most emoji, none for combining marks
+pub fn width(text: &str) -> usize {
+    text.width()
+}
+
+/// Columns taken up by one character
+pub fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// `text` with spaces in front to end it at column `width`
+pub fn right_align(text: &str, width: usize) -> String {
+    let padding = width.saturating_sub(self::width(text));
+    format!("{:padding$}{}", "", text, padding = padding)
+}
+
+/// Spaces to put in front of a block `width` columns wide to align it in `available` columns
+pub fn indent(align: Align, width: usize, available: Option<usize>) -> usize {
+    let free = available.map_or(0, |available| available.saturating_sub(width));
+    match align {
+        Align::Left => 0,
+        Align::Center => free / 2,
+        Align::Right => free,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas;
+
+    #[test]
+    fn ascii_is_one_column_per_char() {
+        assert_eq!(width("v0.1.0"), 6);
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        assert_eq!(width("版本"), 4);
+        assert_eq!(width("リリース 2"), 10);
+        assert_eq!(width("🚀"), 2);
+        assert_eq!(width("ok 🚀"), 5);
+    }
+
+    #[test]
+    fn combining_marks_take_no_columns() {
+        // e + COMBINING ACUTE ACCENT
+        assert_eq!(width("e\u{301}"), 1);
+        assert_eq!(width("cafe\u{301}"), 4);
+        assert_eq!(char_width('\u{301}'), 0);
+    }
+
+    #[test]
+    fn right_align_pads_by_display_width() {
+        let line = right_align("版本 1.0", 12);
+        assert_eq!(line, "    版本 1.0");
+        assert_eq!(width(&line), 12);
+
+        let line = right_align("cafe\u{301}", 6);
+        assert_eq!(line, "  cafe\u{301}");
+        assert_eq!(width(&line), 6);
+    }
+
+    #[test]
+    fn right_align_leaves_text_wider_than_the_art() {
+        assert_eq!(right_align("構築完了しました", 10), "構築完了しました");
+    }
+
+    #[test]
+    fn cells_are_measured_like_strings() {
+        for text in ["plain", "版本 1.0", "e\u{301}t\u{e9}", "🚀 launch"] {
+            assert_eq!(canvas::width(&canvas::plain(text)), width(text), "{}", text);
+        }
+    }
+
+    #[test]
+    fn padding_fills_to_display_width() {
+        let line = canvas::pad(canvas::plain("日本"), 6);
+        assert_eq!(canvas::width(&line), 6);
+        assert_eq!(line.len(), 4);
+    }
+
+    #[test]
+    fn indent_centers_by_display_width() {
+        let block = width("ｗｉｄｅ");
+        assert_eq!(block, 8);
+        assert_eq!(indent(Align::Left, block, Some(20)), 0);
+        assert_eq!(indent(Align::Center, block, Some(20)), 6);
+        assert_eq!(indent(Align::Right, block, Some(20)), 12);
+        assert_eq!(indent(Align::Right, block, Some(4)), 0);
+        assert_eq!(indent(Align::Center, block, None), 0);
+    }
+}
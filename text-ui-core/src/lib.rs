@@ -0,0 +1,38 @@
+//! Rendering text as FIGlet banners, the library behind the text-ui binary: fonts and their
+//! layout, fitting the art into the columns there are, colors, borders, and the output formats.
+//!
+//! ```no_run
+//! use text_ui_core::{Align, Banner};
+//!
+//! let banner = Banner::builder()
+//!     .text("vagent")
+//!     .font("small")
+//!     .color("cyan")
+//!     .align(Align::Center)
+//!     .width(80)
+//!     .render()?;
+//! print!("{}", banner);
+//! # Ok::<(), text_ui_core::Error>(())
+//! ```
+//!
+//! [`BannerBuilder::from`] a [`BannerConfig`] takes every option at once, as the workspace
+//! config's `[banner]` section has them.
+
+mod banner;
+mod bidi;
+mod border;
+pub mod canvas;
+pub mod color;
+mod config;
+pub mod export;
+mod figfont;
+mod fit;
+mod font;
+pub mod layout;
+mod tlf;
+
+pub use banner::{Banner, BannerBuilder, Error};
+pub use config::{
+    Align, Animation, BannerConfig, Border, Direction, Fit, GradientDirection, InfoPosition,
+    Layout, Output, SmushRule,
+};
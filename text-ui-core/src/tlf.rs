@@ -0,0 +1,101 @@
+//! TOIlet (`.tlf`) fonts: FIGfonts in UTF-8 under a `tlf2a` signature, often drawn with block
+//! and box characters. Color fonts draw their glyphs with SGR escapes, which become the colors
+//! of the cells.
+
+use crate::{
+    Layout, SmushRule,
+    canvas::{Cell, Line},
+    color::{self, Rgb},
+    figfont::Glyphs,
+    font::Font,
+};
+
+#[derive(Debug, Clone)]
+pub struct TlfFont(Glyphs);
+
+impl TlfFont {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        Glyphs::parse(content, "tlf2a", colored).map(TlfFont)
+    }
+}
+
+impl Font for TlfFont {
+    fn render(&self, text: &str) -> Vec<Line> {
+        self.0.render(text)
+    }
+
+    fn has(&self, c: char) -> bool {
+        self.0.has(c)
+    }
+
+    fn set_layout(&mut self, layout: Layout, rules: &[SmushRule]) {
+        self.0.set_layout(layout, rules);
+    }
+}
+
+/// A line of a glyph, with its SGR escapes turned into the colors of the cells after them.
+/// Other escapes are dropped.
+fn colored(line: &str) -> Line {
+    let mut cells = Line::new();
+    let mut color = None;
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\x1b' {
+            let color = color.filter(|_| !ch.is_whitespace());
+            cells.push(Cell { ch, color });
+            continue;
+        }
+        if chars.next() != Some('[') {
+            continue;
+        }
+        // Parameters up to the final byte
+        let mut params = String::new();
+        for c in chars.by_ref() {
+            if ('\x40'..='\x7e').contains(&c) {
+                if c == 'm' {
+                    color = sgr(&params, color);
+                }
+                break;
+            }
+            params.push(c);
+        }
+    }
+    cells
+}
+
+/// The foreground color after the SGR sequence with `params`
+fn sgr(params: &str, mut color: Option<Rgb>) -> Option<Rgb> {
+    // An empty parameter is 0, reset
+    let mut codes = params
+        .split(';')
+        .map(|code| code.parse::<u8>().unwrap_or(0));
+    while let Some(code) = codes.next() {
+        match code {
+            0 | 39 => color = None,
+            30..=37 => color = Some(color::indexed(code - 30)),
+            90..=97 => color = Some(color::indexed(code - 90 + 8)),
+            38 => match codes.next() {
+                Some(5) => color = codes.next().map(color::indexed),
+                Some(2) => {
+                    if let (Some(r), Some(g), Some(b)) = (codes.next(), codes.next(), codes.next())
+                    {
+                        color = Some(Rgb(r, g, b));
+                    }
+                }
+                _ => {}
+            },
+            // Backgrounds aren't drawn, but their arguments mustn't be read as codes
+            48 => match codes.next() {
+                Some(5) => {
+                    codes.next();
+                }
+                Some(2) => {
+                    codes.nth(2);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    color
+}
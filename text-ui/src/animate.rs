@@ -0,0 +1,201 @@
+//! `--animate`: drawing the banner bit by bit. Ctrl-C stops the animation and any other key
+//! skips to the end; either way the cursor comes back.
+
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor::{Hide, MoveUp, Show},
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute, queue,
+    terminal::{Clear, ClearType, disable_raw_mode, enable_raw_mode},
+};
+use text_ui_core::{
+    canvas::{self, Cell, Line},
+    color::{Depth, Rgb},
+    export, layout,
+};
+use workspace_config::Animation;
+
+/// Steps a fade takes from dark to the banner's colors
+const FADE_STEPS: usize = 12;
+
+/// Where uncolored text fades from and to
+const FADE_FROM: Rgb = Rgb(48, 48, 48);
+const FADE_TO: Rgb = Rgb(229, 229, 229);
+
+/// Raw mode (so keys arrive without Enter) and a hidden cursor, for as long as this lives
+pub struct Terminal {
+    /// Whether keys can be read
+    pub raw: bool,
+}
+
+impl Terminal {
+    pub fn enter() -> io::Result<Self> {
+        // Without a terminal to read keys from, just animate
+        let raw = enable_raw_mode().is_ok();
+        execute!(io::stdout(), Hide)?;
+        Ok(Terminal { raw })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show);
+        if self.raw {
+            let _ = disable_raw_mode();
+        }
+    }
+}
+
+/// Play the animation, ending with the whole banner on screen. `Ok(false)` if it was
+/// interrupted with Ctrl-C.
+pub fn play(block: &[Line], animation: Animation, speed: f64, depth: Depth) -> io::Result<bool> {
+    run(&Terminal::enter()?, block, animation, speed, depth)
+}
+
+/// [`play`] in a terminal already set up for it
+pub fn run(
+    terminal: &Terminal,
+    block: &[Line],
+    animation: Animation,
+    speed: f64,
+    depth: Depth,
+) -> io::Result<bool> {
+    let (frames, delay) = frames(block, animation);
+    let delay = Duration::from_secs_f64(delay.as_secs_f64() / speed);
+
+    let mut stdout = io::stdout();
+    let height = block.len() as u16;
+
+    // Make room first, so redrawing in place never scrolls the screen
+    write!(stdout, "{}", "\r\n".repeat(block.len()))?;
+    queue!(stdout, MoveUp(height))?;
+
+    for frame in &frames {
+        draw(&mut stdout, frame, depth)?;
+        queue!(stdout, MoveUp(height))?;
+        stdout.flush()?;
+
+        match wait(delay, terminal.raw)? {
+            Key::None => {}
+            Key::Skip => break,
+            Key::Interrupt => {
+                draw(&mut stdout, frame, depth)?;
+                stdout.flush()?;
+                return Ok(false);
+            }
+        }
+    }
+
+    draw(&mut stdout, block, depth)?;
+    stdout.flush()?;
+    Ok(true)
+}
+
+/// The frames of an animation and the time between them at normal speed
+fn frames(block: &[Line], animation: Animation) -> (Vec<Vec<Line>>, Duration) {
+    match animation {
+        Animation::None => (Vec::new(), Duration::ZERO),
+        // Column by column
+        Animation::Typewriter => {
+            let width = block.iter().map(|l| canvas::width(l)).max().unwrap_or(0);
+            let frames = (0..=width)
+                .map(|columns| {
+                    block
+                        .iter()
+                        .map(|line| take_columns(line, columns))
+                        .collect()
+                })
+                .collect();
+            (frames, Duration::from_millis(12))
+        }
+        // Line by line, top to bottom
+        Animation::Scroll => {
+            let frames = (0..=block.len())
+                .map(|shown| {
+                    // Always as tall as the banner, so each frame covers the last
+                    let mut frame = block[..shown].to_vec();
+                    frame.resize(block.len(), Line::new());
+                    frame
+                })
+                .collect();
+            (frames, Duration::from_millis(70))
+        }
+        // Everything at once, from dark to full color
+        Animation::Fade => {
+            let frames = (0..FADE_STEPS)
+                .map(|step| {
+                    let t = step as f64 / FADE_STEPS as f64;
+                    block
+                        .iter()
+                        .map(|line| {
+                            line.iter()
+                                .map(|cell| Cell {
+                                    ch: cell.ch,
+                                    color: (!cell.ch.is_whitespace())
+                                        .then(|| FADE_FROM.mix(cell.color.unwrap_or(FADE_TO), t)),
+                                })
+                                .collect()
+                        })
+                        .collect()
+                })
+                .collect();
+            (frames, Duration::from_millis(60))
+        }
+    }
+}
+
+/// The start of `line` that fits in `columns`
+fn take_columns(line: &[Cell], columns: usize) -> Line {
+    let mut used = 0;
+    line.iter()
+        .take_while(|cell| {
+            used += layout::char_width(cell.ch);
+            used <= columns
+        })
+        .copied()
+        .collect()
+}
+
+/// Draw a frame from the cursor down. Lines a frame doesn't have are cleared.
+fn draw(stdout: &mut io::Stdout, frame: &[Line], depth: Depth) -> io::Result<()> {
+    for line in frame {
+        write!(stdout, "\r{}", export::ansi_line(line, depth))?;
+        queue!(stdout, Clear(ClearType::UntilNewLine))?;
+        write!(stdout, "\r\n")?;
+    }
+    Ok(())
+}
+
+pub enum Key {
+    None,
+    Skip,
+    Interrupt,
+}
+
+/// Sleep for `delay`, watching for keys if there's a terminal to read them from
+pub fn wait(delay: Duration, keys: bool) -> io::Result<Key> {
+    if !keys {
+        std::thread::sleep(delay);
+        return Ok(Key::None);
+    }
+
+    let started = Instant::now();
+    while let Some(remaining) = delay.checked_sub(started.elapsed()) {
+        if !event::poll(remaining)? {
+            break;
+        }
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                return Ok(Key::Interrupt);
+            }
+            return Ok(Key::Skip);
+        }
+    }
+    Ok(Key::None)
+}
@@ -1,60 +1,302 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
 use clap::Parser;
-use figlet_rs::FIGfont;
+use text_ui_core::{BannerBuilder, color::Depth};
+use workspace_config::{
+    Align, Animation, BannerConfig, Border, Direction, Fit, GradientDirection, InfoPosition,
+    Layout, Output, SmushRule,
+};
+
+mod animate;
+mod splash;
+mod watch;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Text to print
-    #[arg(default_value = "vagent")]
-    text: String,
+    /// Text to print (default "vagent"), or - to read it from stdin. Each line becomes a row
+    /// of art.
+    text: Option<String>,
+
+    /// Blank lines between rows of art (default 1)
+    #[arg(long, value_name = "N")]
+    line_spacing: Option<usize>,
+
+    /// Font: slant (the default), standard, shadow, small, or the name of a .flf or .tlf file in
+    /// --font-dir or figlet's font directories
+    #[arg(short, long)]
+    font: Option<String>,
+
+    /// Render with this .flf (FIGfont) or .tlf (TOIlet) font file, ignoring --font
+    #[arg(long, value_name = "PATH")]
+    font_file: Option<PathBuf>,
+
+    /// Directory to look for fonts named with --font in, before figlet's own
+    #[arg(long, value_name = "DIR")]
+    font_dir: Option<PathBuf>,
+
+    /// Put characters together at full width, kerned until they touch or smushed into each
+    /// other (default: as the font says)
+    #[arg(long, value_enum)]
+    layout: Option<Layout>,
 
-    /// Font style (slant, standard, shadow, small)
-    #[arg(short, long, default_value = "slant")]
-    font: String,
+    /// Smushing rules for --layout smush instead of the font's, comma separated
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "RULES")]
+    smush_rules: Option<Vec<SmushRule>>,
+
+    /// Which way the text runs: ltr (the default) or rtl, for Hebrew, Arabic and the like
+    #[arg(long, value_enum)]
+    direction: Option<Direction>,
+
+    /// Draw this for characters the font doesn't have, rather than leaving them out
+    #[arg(long, value_name = "TEXT")]
+    fallback: Option<String>,
+
+    /// Write characters the font doesn't have in ASCII (é as e, 北京 as Bei Jing) before
+    /// falling back to --fallback
+    #[arg(long)]
+    transliterate: bool,
 
     /// Version info to display in the bottom right corner
     #[arg(long)]
     info: Option<String>,
+
+    /// Color the art: a name (red, cyan, orange, ...) or #rrggbb
+    #[arg(long, value_name = "COLOR")]
+    color: Option<String>,
+
+    /// Blend between two colors, e.g. red..#00ffff
+    #[arg(long, value_name = "FROM..TO", conflicts_with = "color")]
+    gradient: Option<String>,
+
+    /// Run the gradient left to right (the default) or top to bottom
+    #[arg(long, value_enum, value_name = "DIRECTION")]
+    gradient_direction: Option<GradientDirection>,
+
+    /// lolcat-style rainbow
+    #[arg(long, conflicts_with_all = ["color", "gradient"])]
+    rainbow: bool,
+
+    /// Place the banner at the left (the default), center or right of the terminal
+    #[arg(long, value_enum)]
+    align: Option<Align>,
+
+    /// Draw a box around the banner
+    #[arg(long, value_enum)]
+    border: Option<Border>,
+
+    /// Spaces between the border and the art (default 1), and half as many blank lines
+    #[arg(long, value_name = "N")]
+    padding: Option<usize>,
+
+    /// Title set into the top of the border
+    #[arg(long)]
+    title: Option<String>,
+
+    /// Put --info on a line under the art (newline, the default), into the blank end of the
+    /// art's last line if it fits there (overlay), or into the bottom of the border
+    #[arg(long, value_enum)]
+    info_position: Option<InfoPosition>,
+
+    /// Draw the banner bit by bit: column by column (typewriter), line by line (scroll) or
+    /// fading in (fade). Only when writing to a terminal.
+    #[arg(long, value_enum)]
+    animate: Option<Animation>,
+
+    /// Animation speed multiplier (default 1.0)
+    #[arg(long)]
+    speed: Option<f64>,
+
+    /// Show the banner in the middle of a cleared screen, then bring back what was there. Only
+    /// when writing to a terminal.
+    #[arg(long)]
+    splash: bool,
+
+    /// Keep the splash screen up for this many seconds rather than until a key is pressed
+    #[arg(long, value_name = "SECONDS")]
+    duration: Option<f64>,
+
+    /// Keep the banner on screen, rendering it again to fit whenever the terminal is resized,
+    /// until q is pressed. Only when writing to a terminal.
+    #[arg(long)]
+    watch: bool,
+
+    /// Write the banner as terminal text with colors (ansi, the default), without them
+    /// (plain), as a standalone SVG image or as an HTML snippet
+    #[arg(long, value_enum)]
+    output: Option<Output>,
+
+    /// Write to this file instead of stdout. The banner isn't fitted or aligned to the terminal
+    /// then.
+    #[arg(long, value_name = "PATH")]
+    out_file: Option<PathBuf>,
+
+    /// When the art is wider than the terminal: wrap the text onto more lines (the default),
+    /// shrink to a narrower built-in font, truncate with "...", or leave it (off)
+    #[arg(long, value_enum)]
+    fit: Option<Fit>,
+
+    /// Style the banner with a theme from ~/.config/text-ui/config.toml, e.g. release, error or
+    /// dev. Flags given as well override the theme's options.
+    #[arg(long, value_name = "NAME")]
+    theme: Option<String>,
+
+    /// Config file with a [banner] section (default: $WORKSPACE_CONFIG or ./workspace.toml)
+    #[arg(long)]
+    config: Option<PathBuf>,
+}
+
+/// Width of the terminal the banner is printed to. When stdout isn't a terminal there is only
+/// `$COLUMNS` to go by, and without that nothing to align against.
+fn terminal_width() -> Option<usize> {
+    if io::stdout().is_terminal()
+        && let Ok((cols, _)) = crossterm::terminal::size()
+    {
+        return Some(cols as usize);
+    }
+    std::env::var("COLUMNS").ok()?.parse().ok()
 }
 
 fn main() {
-    let args = Args::parse();
-    
-    // Select the font data based on the argument
-    let font_data = match args.font.as_str() {
-        "standard" => include_str!("../fonts/standard.flf"),
-        "shadow" => include_str!("../fonts/shadow.flf"),
-        "small" => include_str!("../fonts/small.flf"),
-        _ => include_str!("../fonts/slant.flf"),
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let cli = Args::parse();
+    let theme = cli
+        .theme
+        .as_deref()
+        .map(workspace_config::theme)
+        .transpose()
+        .map_err(|e| e.to_string())?
+        .unwrap_or_default();
+    // Defaults, then the config file, then TEXT_UI_* variables, then the theme, then the flags
+    // above
+    let mut config: BannerConfig = workspace_config::load(
+        cli.config.as_deref(),
+        theme
+            .options
+            .clone()
+            .set("text", cli.text)
+            .set("line_spacing", cli.line_spacing)
+            .set("font", cli.font)
+            .set("font_file", cli.font_file)
+            .set("font_dir", cli.font_dir)
+            .set("layout", cli.layout)
+            .set("smush_rules", cli.smush_rules)
+            .set("direction", cli.direction)
+            .set("fallback", cli.fallback)
+            .set("transliterate", cli.transliterate.then_some(true))
+            .set("info", cli.info)
+            .set("color", cli.color)
+            .set("gradient", cli.gradient)
+            .set("gradient_direction", cli.gradient_direction)
+            .set("rainbow", cli.rainbow.then_some(true))
+            .set("align", cli.align)
+            .set("fit", cli.fit)
+            .set("border", cli.border)
+            .set("padding", cli.padding)
+            .set("title", cli.title)
+            .set("info_position", cli.info_position)
+            .set("output", cli.output)
+            .set("animate", cli.animate)
+            .set("speed", cli.speed)
+            .set("splash", cli.splash.then_some(true))
+            .set("duration", cli.duration)
+            .set("watch", cli.watch.then_some(true)),
+    )
+    .map_err(|e| e.to_string())?;
+    if let Some(info) = theme.fill_info(config.info.as_deref()) {
+        config.info = Some(info);
+    }
+
+    // Only text going to the terminal is fitted and aligned to it
+    let to_terminal =
+        matches!(config.output, Output::Ansi | Output::Plain) && cli.out_file.is_none();
+    let interactive = to_terminal && io::stdout().is_terminal();
+    let splash = config.splash && interactive;
+    let watch = config.watch && interactive;
+    if config.animate != Animation::None && config.speed <= 0.0 {
+        return Err("--speed must be positive".to_string());
+    }
+    let duration = config
+        .duration
+        .map(|seconds| {
+            Duration::try_from_secs_f64(seconds)
+                .map_err(|_| format!("Invalid --duration {} (expected seconds)", seconds))
+        })
+        .transpose()?;
+
+    // Read once, however many times the banner is rendered
+    let text = match config.text.as_str() {
+        "-" => {
+            io::read_to_string(io::stdin()).map_err(|e| format!("Failed to read stdin: {}", e))?
+        }
+        text => text.to_string(),
     };
-    
-    // Parse the font
-    let font = FIGfont::from_content(font_data).expect("Failed to parse font");
-    
-    // Convert text to ASCII art
-    match font.convert(&args.text) {
-        Some(figure) => {
-            let output = figure.to_string();
-            // Remove trailing newlines to keep control over spacing
-            let trimmed_output = output.trim_end();
-            println!("{}", trimmed_output);
-
-            if let Some(info) = args.info {
-                let lines: Vec<&str> = trimmed_output.lines().collect();
-                if let Some(max_width) = lines.iter().map(|l| l.len()).max() {
-                    // Check if we can fit the version on the last line?
-                    // For now, let's print it on the next line, right aligned to the art.
-                    if max_width >= info.len() {
-                        // Using padding to align right
-                        let padding = max_width - info.len();
-                        println!("{:padding$}{}", "", info, padding = padding);
-                    } else {
-                        // If info is longer than the art, just print it
-                        println!("{}", info);
-                    }
-                }
-            }
-        },
-        None => eprintln!("Failed to convert text"),
+    let render = |width: Option<usize>| {
+        let mut builder = BannerBuilder::from(config.clone()).text(text.as_str());
+        if let Some(width) = width {
+            builder = builder.width(width);
+        }
+        if splash {
+            builder = builder.align(Align::Center);
+        }
+        builder.build().map_err(|e| e.to_string())
+    };
+
+    if watch {
+        if !watch::run(|width| render(Some(width)), Depth::detect())? {
+            std::process::exit(130);
+        }
+        return Ok(());
+    }
+
+    let banner = render(terminal_width().filter(|_| to_terminal))?;
+
+    if splash {
+        let finished = splash::show(
+            banner.lines(),
+            config.animate,
+            config.speed,
+            duration,
+            Depth::detect(),
+        )
+        .map_err(|e| e.to_string())?;
+        if !finished {
+            std::process::exit(130);
+        }
+        return Ok(());
+    }
+
+    if config.animate != Animation::None && interactive {
+        let finished = animate::play(
+            banner.lines(),
+            config.animate,
+            config.speed,
+            Depth::detect(),
+        )
+        .map_err(|e| e.to_string())?;
+        if !finished {
+            // Like any program stopped by Ctrl-C
+            std::process::exit(130);
+        }
+        return Ok(());
+    }
+
+    let output = banner.render();
+    match &cli.out_file {
+        Some(path) => std::fs::write(path, output)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e)),
+        None => io::stdout()
+            .write_all(output.as_bytes())
+            .map_err(|e| e.to_string()),
     }
 }
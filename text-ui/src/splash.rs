@@ -0,0 +1,90 @@
+//! `--splash`: the banner alone in the middle of the screen, on the alternate screen so
+//! whatever was there before comes back afterwards
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use crossterm::{
+    cursor::MoveTo,
+    execute, queue,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use text_ui_core::{canvas::Line, color::Depth, export};
+use workspace_config::Animation;
+
+use crate::animate::{self, Key, Terminal};
+
+/// How long to wait for a key at a time when holding until one comes
+const POLL: Duration = Duration::from_secs(60);
+
+/// The alternate screen, for as long as this lives
+pub struct AlternateScreen;
+
+impl AlternateScreen {
+    pub fn enter() -> io::Result<Self> {
+        execute!(io::stdout(), EnterAlternateScreen, Clear(ClearType::All))?;
+        Ok(AlternateScreen)
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+/// Show `block`, already centered across the terminal, halfway down it. Holds for `duration`
+/// or, without one, until a key is pressed; a key ends a `duration` early too. `Ok(false)` if
+/// Ctrl-C ended it.
+pub fn show(
+    block: &[Line],
+    animation: Animation,
+    speed: f64,
+    duration: Option<Duration>,
+    depth: Depth,
+) -> io::Result<bool> {
+    let _screen = AlternateScreen::enter()?;
+    let terminal = Terminal::enter()?;
+
+    let top = middle(block)?;
+    if animation == Animation::None {
+        draw(block, top, depth)?;
+    } else {
+        execute!(io::stdout(), MoveTo(0, top))?;
+        if !animate::run(&terminal, block, animation, speed, depth)? {
+            return Ok(false);
+        }
+    }
+
+    let key = match duration {
+        Some(duration) => animate::wait(duration, terminal.raw)?,
+        // Nothing to wait for without keys to read
+        None if !terminal.raw => Key::None,
+        None => loop {
+            match animate::wait(POLL, true)? {
+                Key::None => continue,
+                key => break key,
+            }
+        },
+    };
+    Ok(!matches!(key, Key::Interrupt))
+}
+
+/// The row to start `block` on for it to be halfway down the terminal
+pub fn middle(block: &[Line]) -> io::Result<u16> {
+    let (_, rows) = terminal::size()?;
+    Ok(rows.saturating_sub(block.len() as u16) / 2)
+}
+
+/// Draw `block` from row `top` down. Line by line, so a banner as tall as the screen doesn't
+/// scroll it.
+pub fn draw(block: &[Line], top: u16, depth: Depth) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    for (row, line) in (top..).zip(block) {
+        queue!(stdout, MoveTo(0, row))?;
+        write!(stdout, "{}", export::ansi_line(line, depth))?;
+    }
+    stdout.flush()
+}
@@ -0,0 +1,53 @@
+//! `--watch`: the banner kept on the alternate screen and rendered again whenever the terminal
+//! is resized, so it's always fitted to the terminal as it is now. q or Esc quits, as does
+//! Ctrl-C.
+
+use std::io;
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{self, Clear, ClearType},
+};
+use text_ui_core::{Banner, canvas::Line, color::Depth};
+
+use crate::{
+    animate::Terminal,
+    splash::{self, AlternateScreen},
+};
+
+/// Show the banner `render` makes for a terminal so many columns wide, halfway down it, until
+/// a key quits. `Ok(false)` if that was Ctrl-C.
+pub fn run(render: impl Fn(usize) -> Result<Banner, String>, depth: Depth) -> Result<bool, String> {
+    let _screen = AlternateScreen::enter().map_err(|e| e.to_string())?;
+    let _terminal = Terminal::enter().map_err(|e| e.to_string())?;
+
+    let (mut cols, _) = terminal::size().map_err(|e| e.to_string())?;
+    loop {
+        let banner = render(cols as usize)?;
+        redraw(banner.lines(), depth).map_err(|e| e.to_string())?;
+
+        // Nothing to do until the size changes
+        loop {
+            match event::read().map_err(|e| e.to_string())? {
+                Event::Resize(columns, _) => {
+                    cols = columns;
+                    break;
+                }
+                Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(false);
+                    }
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+fn redraw(block: &[Line], depth: Depth) -> io::Result<()> {
+    execute!(io::stdout(), Clear(ClearType::All))?;
+    splash::draw(block, splash::middle(block)?, depth)
+}
@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use text_ui_core::BannerConfig;
+use toml::{Table, Value};
+
+use crate::{Error, Overrides, Section};
+
+/// `[banner]`: text-ui
+impl Section for BannerConfig {
+    const NAME: &'static str = "banner";
+    const ENV_PREFIX: &'static str = "TEXT_UI_";
+}
+
+/// A named set of `[banner]` options from text-ui's own config file, picked with `--theme`
+#[derive(Debug, Clone, Default)]
+pub struct Theme {
+    /// The theme's options, to start the command line flags' overrides from
+    pub options: Overrides,
+
+    /// The theme's `info`, with `{info}` where the info given otherwise goes
+    pub info: Option<String>,
+}
+
+impl Theme {
+    /// The theme's info filled in with `info`
+    pub fn fill_info(&self, info: Option<&str>) -> Option<String> {
+        let template = self.info.as_deref()?;
+        Some(
+            template
+                .replace("{info}", info.unwrap_or(""))
+                .trim()
+                .to_string(),
+        )
+    }
+}
+
+/// text-ui's own config file, holding its themes: `$XDG_CONFIG_HOME/text-ui/config.toml`, or
+/// `~/.config/text-ui/config.toml`
+pub fn themes_file() -> PathBuf {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("~/.config"));
+    config_home.join("text-ui").join("config.toml")
+}
+
+/// Theme `name`, the `[themes.<name>]` table of the [`themes_file`]:
+///
+/// ```toml
+/// [themes.release]
+/// font = "standard"
+/// gradient = "green..cyan"
+/// border = "rounded"
+/// align = "center"
+/// info = "release {info}"
+/// ```
+pub fn theme(name: &str) -> Result<Theme, Error> {
+    let path = themes_file();
+    let text = std::fs::read_to_string(&path).map_err(|source| Error::Read {
+        path: path.clone(),
+        source,
+    })?;
+    let mut file: Table = toml::from_str(&text).map_err(|source| Error::Parse {
+        path: path.clone(),
+        source,
+    })?;
+
+    let mut themes = match file.remove("themes") {
+        Some(Value::Table(themes)) => themes,
+        _ => Table::new(),
+    };
+    let Some(Value::Table(mut options)) = themes.remove(name) else {
+        return Err(Error::UnknownTheme {
+            path,
+            name: name.to_string(),
+            known: themes.keys().cloned().collect(),
+        });
+    };
+
+    let info = match options.remove("info") {
+        Some(Value::String(info)) => Some(info),
+        Some(other) => {
+            return Err(Error::Invalid {
+                section: "themes",
+                source: serde::de::Error::custom(format!(
+                    "info of theme {} should be a string, found {}",
+                    name,
+                    other.type_str()
+                )),
+            })
+        }
+        None => None,
+    };
+    Ok(Theme {
+        options: Overrides(options),
+        info,
+    })
+}
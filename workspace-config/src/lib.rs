@@ -0,0 +1,277 @@
+//! Configuration shared by the workspace's binaries. Each tool reads its own section, built up
+//! in layers where every layer overrides the ones before it:
+//!
+//! 1. the section's defaults
+//! 2. its table in the config file (`[recorder]`, `[server]` or `[banner]`)
+//! 3. environment variables named `<PREFIX><KEY>`, with `__` between nested keys
+//!    (`REMOTE_SHELL_LOG_LIMITS__PER_COMMAND=4096`)
+//! 4. command line flags
+//!
+//! text-ui's `--theme` comes in under its flags: a theme from text-ui's own config file (see
+//! [`theme`]) starts the [`Overrides`] the flags are set on.
+//!
+//! The file is the one given with `--config`, else `$WORKSPACE_CONFIG`, else `workspace.toml`
+//! in the working directory if there is one.
+//!
+//! ```toml
+//! [recorder]
+//! log_file = "/var/log/shell_commands.log"
+//!
+//! [server]
+//! listen = "127.0.0.1:3000"
+//!
+//! [banner]
+//! font = "small"
+//! ```
+
+mod banner;
+mod recorder;
+mod server;
+
+pub use banner::{theme, themes_file, Theme};
+pub use recorder::RecorderConfig;
+pub use server::ServerConfig;
+pub use text_ui_core::{
+    Align, Animation, BannerConfig, Border, Direction, Fit, GradientDirection, InfoPosition,
+    Layout, Output, SmushRule,
+};
+
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use toml::{Table, Value};
+
+/// Environment variable naming the config file when `--config` isn't given
+pub const CONFIG_ENV: &str = "WORKSPACE_CONFIG";
+
+/// Config file looked up in the working directory when neither `--config` nor
+/// `$WORKSPACE_CONFIG` is given
+pub const DEFAULT_FILE: &str = "workspace.toml";
+
+/// One tool's table in the config file
+pub trait Section: DeserializeOwned {
+    /// Name of the table
+    const NAME: &'static str;
+
+    /// Prefix of the environment variables overriding it, e.g. `REMOTE_SHELL_`
+    const ENV_PREFIX: &'static str;
+
+    /// A file of the tool's own holding just this section, read instead of `workspace.toml`.
+    /// For tools that had a config file before the shared one.
+    fn own_file() -> Option<PathBuf> {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Read {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// The layers together don't make a valid section
+    Invalid {
+        section: &'static str,
+        source: toml::de::Error,
+    },
+    /// No `[themes.<name>]` in the themes file
+    UnknownTheme {
+        path: PathBuf,
+        name: String,
+        known: Vec<String>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Read { path, source } => {
+                write!(f, "Failed to read {}: {}", path.display(), source)
+            }
+            Error::Parse { path, source } => {
+                write!(f, "Failed to parse {}: {}", path.display(), source)
+            }
+            Error::Invalid { section, source } => {
+                write!(f, "Invalid [{}] config: {}", section, source)
+            }
+            Error::UnknownTheme { path, name, known } if known.is_empty() => {
+                write!(
+                    f,
+                    "No theme {} in {}: it has no themes",
+                    name,
+                    path.display()
+                )
+            }
+            Error::UnknownTheme { path, name, known } => write!(
+                f,
+                "No theme {} in {} (it has {})",
+                name,
+                path.display(),
+                known.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Read { source, .. } => Some(source),
+            Error::Parse { source, .. } | Error::Invalid { source, .. } => Some(source),
+            Error::UnknownTheme { .. } => None,
+        }
+    }
+}
+
+/// Values from command line flags, the last layer. Flags that weren't given are left out, so
+/// they don't hide what the file or environment set.
+#[derive(Debug, Default, Clone)]
+pub struct Overrides(Table);
+
+impl Overrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `key` (dotted for nested tables) if `value` was given
+    pub fn set<T: Serialize>(mut self, key: &str, value: Option<T>) -> Self {
+        if let Some(value) = value.and_then(|v| Value::try_from(v).ok()) {
+            let path: Vec<&str> = key.split('.').collect();
+            insert(&mut self.0, &path, value);
+        }
+        self
+    }
+}
+
+/// Load section `S`, from the file at `path` if one is given
+pub fn load<S: Section>(path: Option<&Path>, overrides: Overrides) -> Result<S, Error> {
+    let mut table = match file::<S>(path) {
+        Some((path, whole)) => {
+            let text = std::fs::read_to_string(&path).map_err(|source| Error::Read {
+                path: path.clone(),
+                source,
+            })?;
+            let mut file: Table =
+                toml::from_str(&text).map_err(|source| Error::Parse { path, source })?;
+            if whole {
+                file
+            } else {
+                match file.remove(S::NAME) {
+                    Some(Value::Table(table)) => table,
+                    None => Table::new(),
+                    Some(other) => {
+                        return Err(Error::Invalid {
+                            section: S::NAME,
+                            source: serde::de::Error::custom(format!(
+                                "expected a table, found {}",
+                                other.type_str()
+                            )),
+                        })
+                    }
+                }
+            }
+        }
+        None => Table::new(),
+    };
+
+    let vars = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+    apply_env::<S>(&mut table, vars);
+    merge(&mut table, overrides.0);
+
+    Value::Table(table)
+        .try_into()
+        .map_err(|source| Error::Invalid {
+            section: S::NAME,
+            source,
+        })
+}
+
+/// The file to read, and whether it holds just this section rather than a table per tool
+fn file<S: Section>(path: Option<&Path>) -> Option<(PathBuf, bool)> {
+    if let Some(path) = path {
+        return Some((path.to_path_buf(), false));
+    }
+    if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|p| !p.is_empty()) {
+        return Some((path.into(), false));
+    }
+    if let Some(path) = S::own_file() {
+        return Some((path, true));
+    }
+    Path::new(DEFAULT_FILE)
+        .exists()
+        .then(|| (PathBuf::from(DEFAULT_FILE), false))
+}
+
+/// Layer `<PREFIX><KEY>` variables over `table`. A value is read as TOML (`8080`, `true`,
+/// `["a", "b"]`) where the section accepts that, else as a plain string, else as a
+/// `$PATH`-style list of paths.
+fn apply_env<S: Section>(table: &mut Table, vars: impl Iterator<Item = (String, String)>) {
+    for (name, raw) in vars {
+        let Some(key) = name.strip_prefix(S::ENV_PREFIX).filter(|k| !k.is_empty()) else {
+            continue;
+        };
+        let key = key.to_lowercase();
+        let path: Vec<&str> = key.split("__").collect();
+
+        let typed = toml::from_str::<Table>(&format!("value = {}", raw))
+            .ok()
+            .and_then(|mut t| t.remove("value"));
+        let paths = Value::Array(
+            std::env::split_paths(&raw)
+                .map(|p| Value::String(p.to_string_lossy().into_owned()))
+                .collect(),
+        );
+        let fits = typed
+            .into_iter()
+            .chain([Value::String(raw.clone()), paths])
+            .find(|value| {
+                let mut candidate = table.clone();
+                insert(&mut candidate, &path, value.clone());
+                Value::Table(candidate).try_into::<S>().is_ok()
+            });
+
+        // When nothing fits, the string is kept so the error shows what was set
+        insert(table, &path, fits.unwrap_or(Value::String(raw)));
+    }
+}
+
+fn insert(table: &mut Table, path: &[&str], value: Value) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+
+    let mut table = table;
+    for key in parents {
+        let entry = table
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+        if !entry.is_table() {
+            *entry = Value::Table(Table::new());
+        }
+        table = match entry {
+            Value::Table(table) => table,
+            _ => unreachable!(),
+        };
+    }
+    table.insert(last.to_string(), value);
+}
+
+/// Merge `over` into `base`, table by table
+fn merge(base: &mut Table, over: Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(over)) => merge(base, over),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
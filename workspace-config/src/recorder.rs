@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::Section;
+
+/// `[recorder]`: pty-bash-hook
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RecorderConfig {
+    /// Text log every command and its output is appended to
+    pub log_file: PathBuf,
+
+    /// Directory each run's session event recording is written to
+    pub recordings_dir: PathBuf,
+
+    /// Bytes of each command's output kept in the text log. Past it the first and last halves
+    /// are kept, with what's between left out.
+    pub output_limit: usize,
+
+    /// Size of the terminal the shell starts in
+    pub cols: u16,
+    pub rows: u16,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            log_file: PathBuf::from("shell_commands.log"),
+            recordings_dir: PathBuf::from("recordings"),
+            output_limit: 1024 * 1024,
+            cols: 80,
+            rows: 24,
+        }
+    }
+}
+
+impl Section for RecorderConfig {
+    const NAME: &'static str = "recorder";
+    const ENV_PREFIX: &'static str = "BASH_PTY_RECORDER_";
+}
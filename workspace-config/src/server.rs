@@ -0,0 +1,35 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::Section;
+
+/// `[server]`: how remote-shell runs. remote-shell reads the rest of its settings (profiles,
+/// users, ...) from the same table.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Address to serve on: host:port or unix:/path/to.sock
+    pub listen: String,
+
+    /// Serve the frontend from this directory instead of the copy built into the binary
+    pub static_dir: Option<PathBuf>,
+
+    /// OTLP (gRPC) collector to export traces to, e.g. http://localhost:4317
+    pub otlp_endpoint: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            listen: "0.0.0.0:3000".to_string(),
+            static_dir: None,
+            otlp_endpoint: None,
+        }
+    }
+}
+
+impl Section for ServerConfig {
+    const NAME: &'static str = "server";
+    const ENV_PREFIX: &'static str = "REMOTE_SHELL_";
+}